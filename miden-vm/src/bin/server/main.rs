@@ -0,0 +1,586 @@
+//! Local prover service sequencing incoming orders against the books and proving them on a
+//! bounded pool of workers.
+//!
+//! Usage: `server <input_file> [address] [feed_address]`, the input file holding the initial
+//! books, the instrument parameters, the fees, the allocation, the balances, the nullifiers and
+//! the signatures flag.
+//!
+//! - `POST /orders` takes an `IncomingOrder` as JSON, with its `signature_advice` when orders
+//!   must be signed, and answers with the ID of its job.
+//! - `GET /jobs/{id}` answers with the job, its proof bundle once proven.
+//! - `GET /jobs/{id}/bundle` answers with the proof bundle of the job alone, once proven.
+//! - `GET /book` answers with the tree root and the advice map of every book.
+//! - `GET /snapshots/{instrument_id}` answers with the `Snapshot` of the book of the instrument.
+//!
+//! WebSocket clients of the feed address receive a `FeedUpdate` as JSON for every proven job, in
+//! job order, its proof referenced by the job ID. Their pings are answered, and so is their close
+//! frame, which ends the subscription.
+//!
+//! Accepted orders and their proofs are appended to the journal under `server-state`, next to
+//! the jobs. On restart the books are rebuilt by replaying the journal over the books of the
+//! input file, and jobs still queued or proving are proven again.
+//!
+//! A job whose proof fails rolls the books back to the state before it, failing the jobs after it
+//! still queued or proving, as their transitions start from the books it led to.
+
+mod websocket;
+
+use miden_clob::{
+    books_to_advice_map, prove_execution, recover, FeedUpdate, IncomingOrder, Journal,
+    JournalRecord, ProofBundle, Sequencer, Transition, UnprovenOrder,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_FEED_ADDRESS: &str = "127.0.0.1:8081";
+const STATE_DIR: &str = "server-state";
+
+/// Number of proofs generated at the same time.
+const WORKERS: usize = 2;
+
+/// Largest number of jobs waiting for a worker, further orders are refused.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Longest wait for a feed subscriber to take an update before it is dropped.
+const FEED_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longest wait for a feed client to send its opening handshake.
+const FEED_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for a client to send its request or take the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request head and body read from a client, larger bodies are refused.
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Queued,
+    Proving,
+    Done,
+    Failed,
+}
+
+/// Body of `POST /orders`.
+#[derive(Deserialize)]
+struct OrderRequest {
+    #[serde(flatten)]
+    order: IncomingOrder,
+    /// Advice map entries of the signature of the order, see `signature_advice_map`.
+    #[serde(default)]
+    signature_advice: BTreeMap<String, Vec<u64>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Job {
+    id: u64,
+    status: JobStatus,
+    order: IncomingOrder,
+    transition: Transition,
+    update: FeedUpdate,
+    error: Option<String>,
+    bundle: Option<ProofBundle>,
+}
+
+struct Service {
+    state_dir: PathBuf,
+    sequencer: Mutex<Sequencer>,
+    /// Sequence number of the last order in the journal, changed under the sequencer lock.
+    last_sequence: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    journal: Mutex<Journal>,
+    feed: Mutex<Feed>,
+    /// Held while sending updates to the subscribers, so they get them in job order without the
+    /// feed and jobs locks being held.
+    sending: Mutex<()>,
+}
+
+struct Feed {
+    /// Subscribers by the number they subscribed with, locked while a frame is sent to them so
+    /// updates and answers to their control frames do not interleave.
+    subscribers: BTreeMap<u64, Arc<Mutex<TcpStream>>>,
+    subscribed: u64,
+    /// ID of the last job whose update was published.
+    published: u64,
+}
+
+impl Service {
+    /// Sequences an order into a new queued job, returning its ID. The job ID is the sequence
+    /// number of the order in the journal.
+    fn accept(&self, request: OrderRequest) -> Result<u64, String> {
+        let OrderRequest { order, signature_advice } = request;
+        let mut sequencer = self.sequencer.lock().unwrap();
+        let queued = {
+            let jobs = self.jobs.lock().unwrap();
+            jobs.values().filter(|job| job.status == JobStatus::Queued).count()
+        };
+        if queued >= QUEUE_CAPACITY {
+            return Err(format!("{queued} jobs are already queued"));
+        }
+
+        let id = self.last_sequence.load(Ordering::SeqCst) + 1;
+        let mut next = sequencer.clone();
+        let transition = next.sequence(&order, &signature_advice)?;
+        self.journal.lock().unwrap().append(&JournalRecord::Order {
+            sequence: id,
+            order: order.clone(),
+            signature_advice,
+            old_roots: sequencer.roots(),
+            new_roots: next.roots(),
+        })?;
+        let update = FeedUpdate::new(
+            id,
+            order.instrument_id,
+            &sequencer.books,
+            &next.books,
+            &transition.outputs,
+        );
+        *sequencer = next;
+        self.last_sequence.store(id, Ordering::SeqCst);
+
+        let job = Job {
+            id,
+            status: JobStatus::Queued,
+            order,
+            transition,
+            update,
+            error: None,
+            bundle: None,
+        };
+        self.save_job(&job)?;
+        self.jobs.lock().unwrap().insert(id, job);
+        Ok(id)
+    }
+
+    /// Changes a job and saves it.
+    fn update<T>(&self, id: u64, change: impl FnOnce(&mut Job) -> T) -> Result<T, String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(&id).ok_or(format!("job {id} does not exist"))?;
+        let result = change(job);
+        self.save_job(job)?;
+        Ok(result)
+    }
+
+    /// Saves the proof bundle of a job, unless the job was rolled back while it was proven.
+    fn complete(&self, id: u64, bundle: ProofBundle) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(&id).ok_or(format!("job {id} does not exist"))?;
+        if job.status != JobStatus::Proving {
+            return Ok(());
+        }
+
+        let record = JournalRecord::Proof { sequence: id, bundle: bundle.clone() };
+        self.journal.lock().unwrap().append(&record)?;
+        job.status = JobStatus::Done;
+        job.bundle = Some(bundle);
+        self.save_job(job)
+    }
+
+    /// Fails a job whose proof failed, unless it was rolled back already, and rolls the books
+    /// back to the state before it. The jobs after it still queued or proving fail too, as their
+    /// transitions start from the books the failed job led to, those proven already keep their
+    /// proofs.
+    fn roll_back(&self, id: u64, error: String) -> Result<(), String> {
+        let mut sequencer = self.sequencer.lock().unwrap();
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&id).ok_or(format!("job {id} does not exist"))?;
+        if job.status != JobStatus::Proving {
+            return Ok(());
+        }
+
+        let books_before = Sequencer::from_inputs(&job.transition.inputs)?;
+        self.journal.lock().unwrap().append(&JournalRecord::Rollback { sequence: id })?;
+        *sequencer = books_before;
+        for job in jobs.range_mut(id..).map(|(_, job)| job).filter(|job| is_pending(job)) {
+            job.status = JobStatus::Failed;
+            job.bundle = None;
+            job.error = Some(if job.id == id {
+                error.clone()
+            } else {
+                format!("rolled back after the proof of job {id} failed")
+            });
+            self.save_job(job)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the updates of the jobs proven since the last published one, stopping at the
+    /// first job still pending so subscribers get them in job order.
+    fn publish(&self) {
+        let _sending = self.sending.lock().unwrap();
+        let (updates, subscribers) = {
+            let mut feed = self.feed.lock().unwrap();
+            let jobs = self.jobs.lock().unwrap();
+            let mut updates = Vec::new();
+            for job in jobs.range(feed.published + 1..).map(|(_, job)| job) {
+                if is_pending(job) {
+                    break;
+                }
+                if job.status == JobStatus::Done {
+                    updates.push(serde_json::to_string(&job.update).unwrap_or_default());
+                }
+                feed.published = job.id;
+            }
+            let subscribers = feed
+                .subscribers
+                .iter()
+                .map(|(number, subscriber)| (*number, subscriber.clone()))
+                .collect::<Vec<_>>();
+            (updates, subscribers)
+        };
+        if updates.is_empty() {
+            return;
+        }
+
+        for (number, subscriber) in subscribers {
+            let mut stream = subscriber.lock().unwrap();
+            if updates.iter().any(|update| websocket::send_text(&mut stream, update).is_err()) {
+                drop(stream);
+                self.unsubscribe(number);
+            }
+        }
+    }
+
+    /// Adds a subscriber to the feed and answers its control frames until it leaves.
+    fn subscribe(&self, subscriber: TcpStream) -> Result<(), String> {
+        subscriber.set_write_timeout(Some(FEED_WRITE_TIMEOUT)).map_err(|e| e.to_string())?;
+        // Subscribers send nothing but control frames, however long they stay
+        subscriber.set_read_timeout(None).map_err(|e| e.to_string())?;
+        let mut reader = subscriber.try_clone().map_err(|e| e.to_string())?;
+        let writer = Arc::new(Mutex::new(subscriber));
+        let number = {
+            let mut feed = self.feed.lock().unwrap();
+            feed.subscribed += 1;
+            let number = feed.subscribed;
+            feed.subscribers.insert(number, writer.clone());
+            number
+        };
+
+        let left = loop {
+            let frame = match websocket::read_frame(&mut reader) {
+                Ok(frame) => frame,
+                Err(e) => break Err(e),
+            };
+            let mut writer = writer.lock().unwrap();
+            let answered = match frame.opcode {
+                websocket::PING_OPCODE => {
+                    websocket::send_frame(&mut writer, websocket::PONG_OPCODE, &frame.payload)
+                }
+                // The close frame is echoed with the status code of the client
+                websocket::CLOSE_OPCODE => {
+                    let status = &frame.payload[..frame.payload.len().min(2)];
+                    break websocket::send_frame(&mut writer, websocket::CLOSE_OPCODE, status)
+                        .map_err(|e| e.to_string());
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = answered {
+                break Err(e.to_string());
+            }
+        };
+        self.unsubscribe(number);
+        left
+    }
+
+    /// Drops a subscriber, ending the connection so the thread reading its frames returns.
+    fn unsubscribe(&self, number: u64) {
+        let subscriber = self.feed.lock().unwrap().subscribers.remove(&number);
+        if let Some(subscriber) = subscriber {
+            let _ = subscriber.lock().unwrap().shutdown(Shutdown::Both);
+        }
+    }
+
+    fn save_job(&self, job: &Job) -> Result<(), String> {
+        let path = self.state_dir.join("jobs").join(format!("{}.json", job.id));
+        save(&path, &serde_json::to_string(job).map_err(|e| e.to_string())?)
+    }
+
+    fn book(&self) -> serde_json::Value {
+        let sequencer = self.sequencer.lock().unwrap();
+        let roots = sequencer
+            .books
+            .iter()
+            .map(|(instrument_id, book)| (instrument_id.to_string(), book.root()))
+            .collect::<BTreeMap<_, _>>();
+        serde_json::json!({ "roots": roots, "advice_map": books_to_advice_map(&sequencer.books) })
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let input_file = args.next().ok_or("usage: server <input_file> [address] [feed_address]")?;
+    let address = args.next().unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let feed_address = args.next().unwrap_or_else(|| DEFAULT_FEED_ADDRESS.to_string());
+
+    let service = Arc::new(load_service(Path::new(&input_file), Path::new(STATE_DIR))?);
+    let (queue, receiver) = sync_channel(QUEUE_CAPACITY);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let (service, receiver) = (service.clone(), receiver.clone());
+        thread::spawn(move || work(&service, &receiver));
+    }
+
+    let pending = {
+        let jobs = service.jobs.lock().unwrap();
+        jobs.values().filter(|job| is_pending(job)).map(|job| job.id).collect::<Vec<_>>()
+    };
+    let listener =
+        TcpListener::bind(&address).map_err(|e| format!("failed to bind {address} - {e}"))?;
+    let feed_listener = TcpListener::bind(&feed_address)
+        .map_err(|e| format!("failed to bind {feed_address} - {e}"))?;
+    let feed_service = service.clone();
+    thread::spawn(move || {
+        // A slow client only holds up its own handshake
+        for stream in feed_listener.incoming().flatten() {
+            let feed_service = feed_service.clone();
+            thread::spawn(move || {
+                let subscriber = stream
+                    .set_read_timeout(Some(FEED_HANDSHAKE_TIMEOUT))
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| websocket::accept(stream));
+                match subscriber {
+                    Ok(subscriber) => {
+                        if let Err(e) = feed_service.subscribe(subscriber) {
+                            eprintln!("feed subscriber left - {e}");
+                        }
+                    }
+                    Err(e) => eprintln!("failed to accept feed subscriber - {e}"),
+                }
+            });
+        }
+    });
+    println!("serving on {address}, feed on {feed_address}, {} pending jobs", pending.len());
+    for id in pending {
+        queue.send(id).map_err(|e| e.to_string())?;
+    }
+
+    // A slow client only holds up its own request
+    for stream in listener.incoming().flatten() {
+        let (service, queue) = (service.clone(), queue.clone());
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &service, &queue) {
+                eprintln!("failed to handle request - {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Builds the service from the books of the input file moved on by the journal, and the saved
+/// jobs.
+fn load_service(input_file: &Path, state_dir: &Path) -> Result<Service, String> {
+    let input = fs::read_to_string(input_file)
+        .map_err(|e| format!("failed to read {} - {e}", input_file.display()))?;
+    let sequencer = Sequencer::from_inputs(&input)
+        .map_err(|e| format!("failed to load {} - {e}", input_file.display()))?;
+
+    let journal_path = state_dir.join("journal.jsonl");
+    let recovery = recover(sequencer, &Journal::read(&journal_path)?)
+        .map_err(|e| format!("failed to replay the journal - {e}"))?;
+
+    let jobs_dir = state_dir.join("jobs");
+    fs::create_dir_all(&jobs_dir).map_err(|e| e.to_string())?;
+    let mut jobs = BTreeMap::<u64, Job>::new();
+    for entry in fs::read_dir(&jobs_dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let job = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let job: Job = serde_json::from_str(&job)
+            .map_err(|e| format!("failed to read job {} - {e}", path.display()))?;
+        jobs.insert(job.id, job);
+    }
+
+    // A crash between the journal and the job file loses the job, not the order
+    for (id, UnprovenOrder { order, transition, update }) in recovery.unproven {
+        jobs.entry(id).or_insert_with(|| Job {
+            id,
+            status: JobStatus::Queued,
+            order,
+            transition,
+            update,
+            error: None,
+            bundle: None,
+        });
+    }
+    let save_job = |job: &Job| {
+        let path = jobs_dir.join(format!("{}.json", job.id));
+        save(&path, &serde_json::to_string(job).map_err(|e| e.to_string())?)
+    };
+    // A crash during a rollback leaves jobs it had to fail pending
+    for id in recovery.rolled_back {
+        if let Some(job) = jobs.get_mut(&id).filter(|job| is_pending(job)) {
+            job.status = JobStatus::Failed;
+            job.error = Some("rolled back after a failed proof".to_string());
+            save_job(job)?;
+        }
+    }
+    // Workers only take queued jobs
+    for job in jobs.values_mut().filter(|job| job.status == JobStatus::Proving) {
+        job.status = JobStatus::Queued;
+        save_job(job)?;
+    }
+
+    // Jobs proven before the first pending one were published before the restart
+    let first_pending = jobs.values().find(|job| is_pending(job)).map(|job| job.id);
+    let published = first_pending.map_or(recovery.last_sequence, |id| id - 1);
+    Ok(Service {
+        state_dir: state_dir.to_path_buf(),
+        sequencer: Mutex::new(recovery.sequencer),
+        last_sequence: AtomicU64::new(recovery.last_sequence),
+        jobs: Mutex::new(jobs),
+        journal: Mutex::new(Journal::open(&journal_path)?),
+        feed: Mutex::new(Feed { subscribers: BTreeMap::new(), subscribed: 0, published }),
+        sending: Mutex::new(()),
+    })
+}
+
+/// Proves queued jobs until the queue closes.
+fn work(service: &Service, receiver: &Mutex<Receiver<u64>>) {
+    loop {
+        let Ok(id) = receiver.lock().unwrap().recv() else {
+            return;
+        };
+        // A job failed by a rollback while it was queued is not proven
+        let inputs = service.update(id, |job| {
+            (job.status == JobStatus::Queued).then(|| {
+                job.status = JobStatus::Proving;
+                job.transition.inputs.clone()
+            })
+        });
+        let Ok(Some(inputs)) = inputs else {
+            continue;
+        };
+
+        let saved = match prove_execution(&inputs) {
+            Ok(outputs) => service.complete(id, ProofBundle::new(&inputs, outputs)),
+            Err(e) => service.roll_back(id, e),
+        };
+        if let Err(e) = saved {
+            eprintln!("failed to save job {id} - {e}");
+        }
+        service.publish();
+    }
+}
+
+fn handle(mut stream: TcpStream, service: &Service, queue: &SyncSender<u64>) -> Result<(), String> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(Read::take(&mut stream, MAX_HEAD_LEN + MAX_BODY_LEN as u64));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|e| e.to_string())?;
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| "bad content length")?;
+            }
+        }
+    }
+    let (status, response) = if content_length > MAX_BODY_LEN {
+        let error = format!("request body is larger than {MAX_BODY_LEN} bytes");
+        ("413 Payload Too Large", serde_json::json!({ "error": error }))
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+        route(&request_line, &body, service, queue)?
+    };
+
+    let response = response.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Answers a request with its status and JSON response.
+fn route(
+    request_line: &str,
+    body: &[u8],
+    service: &Service,
+    queue: &SyncSender<u64>,
+) -> Result<(&'static str, serde_json::Value), String> {
+    let mut parts = request_line.split_whitespace();
+    Ok(match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/orders")) => match serde_json::from_slice::<OrderRequest>(body) {
+            Ok(request) => match service.accept(request) {
+                Ok(id) => {
+                    queue.send(id).map_err(|e| e.to_string())?;
+                    ("202 Accepted", serde_json::json!({ "id": id }))
+                }
+                Err(e) => ("422 Unprocessable Entity", serde_json::json!({ "error": e })),
+            },
+            Err(e) => ("400 Bad Request", serde_json::json!({ "error": e.to_string() })),
+        },
+        (Some("GET"), Some(path)) if path.starts_with("/jobs/") => {
+            let id = &path["/jobs/".len()..];
+            let (id, bundle_only) = match id.strip_suffix("/bundle") {
+                Some(id) => (id, true),
+                None => (id, false),
+            };
+            let jobs = service.jobs.lock().unwrap();
+            match id.parse::<u64>().ok().and_then(|id| jobs.get(&id)) {
+                Some(job) if bundle_only => match &job.bundle {
+                    Some(bundle) => {
+                        ("200 OK", serde_json::to_value(bundle).map_err(|e| e.to_string())?)
+                    }
+                    None => ("404 Not Found", serde_json::json!({ "error": "job is not proven" })),
+                },
+                Some(job) => ("200 OK", serde_json::to_value(job).map_err(|e| e.to_string())?),
+                None => ("404 Not Found", serde_json::json!({ "error": "no such job" })),
+            }
+        }
+        (Some("GET"), Some("/book")) => ("200 OK", service.book()),
+        (Some("GET"), Some(path)) if path.starts_with("/snapshots/") => {
+            let sequencer = service.sequencer.lock().unwrap();
+            let instrument_id = path["/snapshots/".len()..].parse::<u64>().ok();
+            match instrument_id.and_then(|id| sequencer.books.get(&id)) {
+                Some(book) => {
+                    ("200 OK", serde_json::to_value(book.snapshot()).map_err(|e| e.to_string())?)
+                }
+                None => ("404 Not Found", serde_json::json!({ "error": "no such book" })),
+            }
+        }
+        _ => ("404 Not Found", serde_json::json!({ "error": "no such endpoint" })),
+    })
+}
+
+fn is_pending(job: &Job) -> bool {
+    matches!(job.status, JobStatus::Queued | JobStatus::Proving)
+}
+
+/// Writes a file through a temporary one, so a crash never leaves it half written.
+fn save(path: &Path, contents: &str) -> Result<(), String> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)
+        .map_err(|e| format!("failed to write {} - {e}", path.display()))?;
+    fs::rename(&temporary, path).map_err(|e| format!("failed to write {} - {e}", path.display()))
+}
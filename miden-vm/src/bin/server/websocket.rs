@@ -0,0 +1,134 @@
+//! Server side of the WebSocket protocol (RFC 6455), as much of it as a feed pushing text
+//! messages to its subscribers and answering their control frames needs.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+/// Appended to the key of a client to answer its opening handshake.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub const TEXT_OPCODE: u8 = 0x1;
+pub const CLOSE_OPCODE: u8 = 0x8;
+pub const PING_OPCODE: u8 = 0x9;
+pub const PONG_OPCODE: u8 = 0xa;
+
+/// Largest frame payload read from a client, larger frames fail the connection.
+const MAX_PAYLOAD_LEN: u64 = 64 * 1024;
+
+/// A frame sent by a client, its payload unmasked.
+pub struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Answers the opening handshake of a client, returning the stream to send it messages on.
+pub fn accept(mut stream: TcpStream) -> Result<TcpStream, String> {
+    let mut reader = BufReader::new(&stream);
+    let mut key = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    let key = key.ok_or("request is not a WebSocket handshake")?;
+
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+/// Reads the next frame of a client. Client frames are masked, and fragments are read as frames of
+/// their own.
+pub fn read_frame(stream: &mut impl Read) -> Result<Frame, String> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head).map_err(|e| e.to_string())?;
+    let opcode = head[0] & 0x0f;
+    if head[1] & 0x80 == 0 {
+        return Err("client frame is not masked".to_string());
+    }
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).map_err(|e| e.to_string())?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len).map_err(|e| e.to_string())?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_PAYLOAD_LEN {
+        return Err(format!("client frame is larger than {MAX_PAYLOAD_LEN} bytes"));
+    }
+
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask).map_err(|e| e.to_string())?;
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload).map_err(|e| e.to_string())?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Frame { opcode, payload })
+}
+
+/// Sends a text message in a single frame, unmasked as server frames are.
+pub fn send_text(stream: &mut TcpStream, text: &str) -> std::io::Result<()> {
+    send_frame(stream, TEXT_OPCODE, text.as_bytes())
+}
+
+/// Sends a frame with the given opcode, unmasked as server frames are.
+pub fn send_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)
+}
+
+/// Returns the `Sec-WebSocket-Accept` answering the `Sec-WebSocket-Key` of a client.
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(Sha1::digest(format!("{key}{HANDSHAKE_GUID}").as_bytes()))
+}
+
+#[test]
+fn test_accept_key() {
+    assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn test_read_frame() {
+    // Masked ping with payload "Hello", as in RFC 6455 section 5.7
+    let ping = [0x89, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+    let frame = read_frame(&mut &ping[..]).unwrap();
+    assert_eq!(frame.opcode, PING_OPCODE);
+    assert_eq!(frame.payload, b"Hello");
+
+    let unmasked = [0x88, 0x00];
+    assert!(read_frame(&mut &unmasked[..]).is_err());
+}
@@ -1,12 +1,54 @@
+mod utils_allocation;
+mod utils_balances;
+mod utils_best_execution;
+mod utils_feed;
+mod utils_fees;
 mod utils_input;
+mod utils_instrument;
+mod utils_journal;
 mod utils_masm_code;
+mod utils_notes;
+mod utils_nullifiers;
+mod utils_order;
+mod utils_orderbook;
+mod utils_output;
 mod utils_program;
+mod utils_proof;
+mod utils_query;
+mod utils_sequencer;
+mod utils_signatures;
+mod utils_snapshot;
 use miden_vm::{ProgramInfo, ProvingOptions, StackOutputs};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+pub use utils_allocation::{
+    Allocation, ALLOCATION_POSITION, FIFO_ALLOCATION, HYBRID_ALLOCATION, PRO_RATA_ALLOCATION,
+};
+pub use utils_balances::{Balance, Balances, BALANCES_ROOT_POSITION};
+pub use utils_best_execution::{BestExecutionReport, FillExecution};
+pub use utils_feed::{FeedUpdate, LevelDelta, Trade};
+pub use utils_fees::{FeeSchedule, FeeTier, FEE_COMMITMENT_POSITION, MAX_FEE_BPS};
+pub use utils_instrument::{validate_inputs, InstrumentParams};
+pub use utils_journal::{recover, Journal, JournalRecord, Recovery, UnprovenOrder};
+pub use utils_notes::{LockedOrder, Market, MockChain, OrderNote, PaymentNote};
+pub use utils_nullifiers::{NullifierStore, NULLIFIER_ROOT_POSITION};
+pub use utils_order::{batch_commitment, IncomingOrder, BATCH_COMMITMENT_POSITION, MARKET_ORDER};
+pub use utils_orderbook::{apply_outputs, books_from_advice_map, books_to_advice_map, OrderBook};
+pub use utils_proof::{chain_proofs, ProofBundle, ProofChain};
+pub use utils_query::{
+    BookQuery, DepthLevel, QuantityUpTo, TopOfBook, DEPTH_QUERY, QUANTITY_UP_TO_QUERY, QUERY_MODE,
+    TOP_OF_BOOK_QUERY,
+};
+pub use utils_sequencer::{Sequencer, Transition};
+pub use utils_signatures::{
+    key_account_id, signature_advice_map, verify_order, OrderSigner, SignatureAdviceProvider,
+    SIGNATURES_POSITION,
+};
+pub use utils_snapshot::Snapshot;
+
 #[wasm_bindgen(getter_with_clone)]
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Outputs {
     pub stack_output: Vec<u64>,
     pub trace_len: Option<usize>,
@@ -18,6 +60,11 @@ pub struct Outputs {
 /// Proves the program with the given inputs
 #[wasm_bindgen]
 pub fn prove_program(inputs_frontend: &str) -> Result<Outputs, JsValue> {
+    Ok(prove_execution(inputs_frontend)?)
+}
+
+/// Proves the program with the given inputs, with errors usable outside of WASM
+pub fn prove_execution(inputs_frontend: &str) -> Result<Outputs, String> {
     let mut program = utils_program::MidenProgram::new(
         &utils_masm_code::get_masm_code().to_string(),
         utils_program::DEBUG_OFF,
@@ -28,6 +75,8 @@ pub fn prove_program(inputs_frontend: &str) -> Result<Outputs, JsValue> {
     inputs
         .deserialize_inputs(inputs_frontend)
         .map_err(|err| format!("Failed to deserialize inputs - {:?}", err))?;
+    utils_instrument::validate_inputs(inputs_frontend)
+        .map_err(|err| format!("Invalid incoming order - {}", err))?;
 
     // default (96 bits of security)
     let proving_options = ProvingOptions::default();
@@ -65,6 +114,50 @@ pub fn verify_program(
     output: &[u64],
     overflow_addrs: &[u64],
 ) -> Result<(), JsValue> {
+    verify_execution(inputs_frontend, proof, output, overflow_addrs)?;
+
+    Ok(())
+}
+
+/// Verifies a proof bundle given as JSON, as the prover service answers it
+#[wasm_bindgen]
+pub fn verify_bundle(bundle: &str) -> Result<(), JsValue> {
+    let bundle: ProofBundle = serde_json::from_str(bundle)
+        .map_err(|err| format!("Failed to deserialize proof bundle - {:?}", err))?;
+    bundle.verify()?;
+
+    Ok(())
+}
+
+/// Runs the program with the given inputs without proving it, returning the stack outputs
+pub fn execute_program(inputs_frontend: &str) -> Result<Vec<u64>, String> {
+    let mut program = utils_program::MidenProgram::new(
+        &utils_masm_code::get_masm_code().to_string(),
+        utils_program::DEBUG_OFF,
+    );
+    program.compile_program().map_err(|err| format!("Failed to compile program - {:?}", err))?;
+
+    let mut inputs = utils_input::Inputs::new();
+    inputs
+        .deserialize_inputs(inputs_frontend)
+        .map_err(|err| format!("Failed to deserialize inputs - {:?}", err))?;
+    utils_instrument::validate_inputs(inputs_frontend)
+        .map_err(|err| format!("Invalid incoming order - {}", err))?;
+
+    let trace =
+        miden_vm::execute(&program.program.unwrap(), inputs.stack_inputs, inputs.advice_provider)
+            .map_err(|err| format!("Failed to execute program - {:?}", err))?;
+
+    Ok(trace.stack_outputs().stack().to_vec())
+}
+
+/// Verifies a proof of the program against the given inputs and outputs
+pub(crate) fn verify_execution(
+    inputs_frontend: &str,
+    proof: &[u8],
+    output: &[u64],
+    overflow_addrs: &[u64],
+) -> Result<(), String> {
     let mut program = utils_program::MidenProgram::new(
         &utils_masm_code::get_masm_code().to_string(),
         utils_program::DEBUG_OFF,
@@ -92,14 +185,105 @@ pub fn verify_program(
     Ok(())
 }
 
+/// Decodes the stack outputs into tree roots, fills and change log
+#[wasm_bindgen]
+pub fn decode_outputs(stack_output: &[u64]) -> Result<JsValue, JsValue> {
+    let outputs = utils_output::decode_outputs(stack_output)
+        .map_err(|err| format!("Failed to decode outputs - {:?}", err))?;
+
+    serde_wasm_bindgen::to_value(&outputs)
+        .map_err(|err| JsValue::from(format!("Failed to serialize outputs - {:?}", err)))
+}
+
+/// Verifies a proof bundle given as JSON and builds its best execution report, Markdown or JSON
+#[wasm_bindgen]
+pub fn best_execution_report(bundle: &str, markdown: bool) -> Result<String, JsValue> {
+    let bundle: ProofBundle = serde_json::from_str(bundle)
+        .map_err(|err| format!("Failed to deserialize proof bundle - {:?}", err))?;
+    let outputs = bundle.decode()?;
+    let report = BestExecutionReport::new(&bundle, &outputs.fills)?;
+
+    if markdown {
+        Ok(report.to_markdown())
+    } else {
+        Ok(report.to_json()?)
+    }
+}
+
 #[test]
 fn test_prove_program() {
-    let input_str: &str = r#"
-    {
-        "operand_stack": ["0"],
-        "advice_stack": ["0"]
-    }"#;
-
-    let prove_result = prove_program(input_str).unwrap();
-    assert_eq!(prove_result.stack_output, vec![3, 0, 0])
-}
\ No newline at end of file
+    let inputs = include_str!("../../market_bid_(order_matching)_small_tree.input");
+    let books = Sequencer::from_inputs(inputs).unwrap().books;
+
+    let bundle = ProofBundle::new(inputs, prove_execution(inputs).unwrap());
+    assert_eq!(bundle.decode().unwrap().old_root, books[&0].root());
+    bundle.verify().unwrap();
+}
+
+#[test]
+fn test_execute_fixtures() {
+    let fixtures = [
+        include_str!("../../call_auction_(uncross)_small_tree.input"),
+        include_str!("../../market_ask_(order_insertion).input"),
+        include_str!("../../market_ask_(order_insertion)_small_tree.input"),
+        include_str!("../../market_batch_(order_batching)_small_tree.input"),
+        include_str!("../../market_bid_(order_matching).input"),
+        include_str!("../../market_bid_(order_matching)_small_tree.input"),
+        include_str!("../../rbBST.input"),
+    ];
+
+    for inputs in fixtures {
+        let mut books = Sequencer::from_inputs(inputs).unwrap().books;
+        let outputs = utils_output::decode_outputs(&execute_program(inputs).unwrap()).unwrap();
+        let first_book = *books.keys().next().unwrap();
+        assert_eq!(outputs.old_root, books[&first_book].root());
+
+        apply_outputs(&mut books, &outputs).unwrap();
+        assert_eq!(outputs.new_root, books[&first_book].root());
+        for book in &outputs.books {
+            assert_eq!(book.new_root, books[&book.instrument_id].root());
+        }
+        // The fixtures settle no balances and keep no nullifiers
+        assert_eq!(outputs.new_balances_root, [0; 4]);
+        assert_eq!(outputs.new_nullifier_root, [0; 4]);
+    }
+}
+
+#[test]
+fn test_execute_fixture_with_fees_and_balances() {
+    let inputs = include_str!("../../market_bid_(order_matching)_small_tree.input");
+    let mut sequencer = Sequencer::from_inputs(inputs).unwrap();
+    sequencer.fees =
+        FeeSchedule { tiers: vec![FeeTier { min_notional: 0, maker_bps: 1, taker_bps: 2 }] };
+    let mut balances = Balances::default();
+    balances.accounts.insert(1, Balance { base: 1000, quote: 1_000_000 });
+    sequencer.balances = Some(balances);
+
+    let operand_stack = |inputs: &str| {
+        let input_file: utils_input::InputFile = serde_json::from_str(inputs).unwrap();
+        let operand_stack = input_file.operand_stack.unwrap_or_default();
+        operand_stack.iter().map(|v| v.parse::<u64>().unwrap()).collect::<Vec<_>>()
+    };
+    let order = IncomingOrder {
+        account_id: 1,
+        ..IncomingOrder::from_operand_stack(&operand_stack(inputs))
+    };
+    let advice = miden_vm::utils::collections::BTreeMap::new();
+    let transition = sequencer.sequence(&order, &advice).unwrap();
+    assert!(!transition.outputs.fills.is_empty());
+
+    let operand_stack = operand_stack(&transition.inputs);
+    let fee_commitment: [u64; 4] = core::array::from_fn(|i| {
+        utils_instrument::stack_element(&operand_stack, FEE_COMMITMENT_POSITION + i)
+    });
+    assert_eq!(fee_commitment, sequencer.fees.commitment());
+    for fill in &transition.outputs.fills {
+        assert_eq!(
+            sequencer.fees.fees(fill.quantity, fill.price),
+            Ok((fill.maker_fee, fill.taker_fee))
+        );
+    }
+    assert_eq!(transition.outputs.new_root, sequencer.books[&0].root());
+    let balances = sequencer.balances.unwrap();
+    assert_eq!(Ok(transition.outputs.new_balances_root), balances.root());
+}
@@ -0,0 +1,167 @@
+use crate::utils_instrument::stack_element;
+use serde::{Deserialize, Serialize};
+
+/// Operand stack position, counted from the top, of the allocation algorithm. It sits below the
+/// nullifier root, the priority share below it.
+pub const ALLOCATION_POSITION: usize = 32;
+
+/// Fills the orders of a price level in time priority.
+pub const FIFO_ALLOCATION: u64 = 0;
+
+/// Shares a price level among its orders in proportion to their displayed quantity.
+pub const PRO_RATA_ALLOCATION: u64 = 1;
+
+/// Fills a priority share of the quantity in time priority, then shares the rest pro rata.
+pub const HYBRID_ALLOCATION: u64 = 2;
+
+/// Largest incoming quantity pro rata and hybrid allocation take: `MulDiv` works on u32 values.
+pub const MAX_ALLOCATED_QUANTITY: u64 = u32::MAX as u64;
+
+/// Allocation of an incoming order among the orders of a price level it does not exhaust, see
+/// `AllocateLevel`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Allocation {
+    pub algorithm: u64,
+    /// Share of the quantity filled in time priority first under hybrid allocation, in 1/10000.
+    pub priority_share_bps: u64,
+}
+
+impl Allocation {
+    /// Reads the allocation from an operand stack listed bottom first, as in input files.
+    pub fn from_operand_stack(operand_stack: &[u64]) -> Self {
+        Self {
+            algorithm: stack_element(operand_stack, ALLOCATION_POSITION),
+            priority_share_bps: stack_element(operand_stack, ALLOCATION_POSITION + 1),
+        }
+    }
+
+    /// Returns the bottom of the operand stack holding the allocation, listed bottom first.
+    pub fn to_operand_stack(&self) -> Vec<u64> {
+        vec![self.priority_share_bps, self.algorithm]
+    }
+
+    /// Mirrors the asserts of `LoadAllocation`.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.algorithm > HYBRID_ALLOCATION {
+            return Err(format!("allocation algorithm {} is unknown", self.algorithm));
+        }
+        if self.priority_share_bps > 10000 {
+            return Err(format!(
+                "priority share of {} bps is above 10000 bps",
+                self.priority_share_bps
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks an incoming quantity stays within what the allocation can share, see `MulDiv`.
+    pub fn validate_quantity(&self, quantity: u64) -> Result<(), String> {
+        if self.algorithm != FIFO_ALLOCATION && quantity > MAX_ALLOCATED_QUANTITY {
+            return Err(format!(
+                "quantity {quantity} is above {MAX_ALLOCATED_QUANTITY}, the most pro rata and \
+                 hybrid allocation share"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Mirrors `AllocateLevel`: returns the fill of every eligible order of a level, given their
+    /// displayed quantities in time priority. Pro rata shares are rounded down to whole lots and
+    /// the remainder fills in time priority. A level the quantity exhausts fills in time priority
+    /// whatever the algorithm. Fails where `MulDiv` fails, on quantities beyond u32.
+    pub fn allocate(
+        &self,
+        quantity: u64,
+        lot_size: u64,
+        queue: &[u64],
+    ) -> Result<Vec<u64>, String> {
+        let mut fills = vec![0; queue.len()];
+        if self.algorithm == FIFO_ALLOCATION || quantity >= queue.iter().sum() {
+            fill_in_time_priority(&mut fills, queue, quantity);
+            return Ok(fills);
+        }
+
+        let share_bps =
+            if self.algorithm == HYBRID_ALLOCATION { self.priority_share_bps } else { 0 };
+        let slice = round_down_to_lot(mul_div(quantity, share_bps, 10000)?, lot_size);
+        fill_in_time_priority(&mut fills, queue, slice);
+
+        let rest = quantity - slice;
+        let left =
+            queue.iter().zip(&fills).map(|(displayed, fill)| displayed - fill).collect::<Vec<_>>();
+        let eligible_quantity = left.iter().sum::<u64>();
+        let mut remainder = rest;
+        if rest > 0 {
+            for (fill, left) in fills.iter_mut().zip(left) {
+                let share = round_down_to_lot(mul_div(left, rest, eligible_quantity)?, lot_size);
+                *fill += share;
+                remainder -= share;
+            }
+        }
+        fill_in_time_priority(&mut fills, queue, remainder);
+        Ok(fills)
+    }
+}
+
+/// Adds quantity to the fills of a queue from its first order on, none past its displayed quantity.
+fn fill_in_time_priority(fills: &mut [u64], queue: &[u64], mut quantity: u64) {
+    for (fill, displayed) in fills.iter_mut().zip(queue) {
+        let filled = quantity.min(displayed - *fill);
+        *fill += filled;
+        quantity -= filled;
+    }
+}
+
+/// Mirrors `MulDiv`: a * b / c rounded down, the product widened so it cannot overflow. All three
+/// and the quotient must be u32 values.
+fn mul_div(a: u64, b: u64, c: u64) -> Result<u64, String> {
+    let max = u32::MAX as u64;
+    if a > max || b > max || c > max {
+        return Err(format!("{a} * {b} / {c} takes a value beyond u32"));
+    }
+    let quotient = a * b / c;
+    if quotient > max {
+        return Err(format!("{a} * {b} / {c} is beyond u32"));
+    }
+    Ok(quotient)
+}
+
+/// Mirrors `RoundDownToLot`.
+fn round_down_to_lot(quantity: u64, lot_size: u64) -> u64 {
+    if lot_size == 0 {
+        quantity
+    } else {
+        quantity - quantity % lot_size
+    }
+}
+
+#[test]
+fn test_allocate() {
+    let queue = [30, 10, 60];
+    let fifo = Allocation::default();
+    assert_eq!(fifo.allocate(35, 5, &queue), Ok(vec![30, 5, 0]));
+
+    let pro_rata = Allocation { algorithm: PRO_RATA_ALLOCATION, priority_share_bps: 0 };
+    // Shares of 15, 5 and 30 rounded down to 15, 5 and 30, nothing left
+    assert_eq!(pro_rata.allocate(50, 5, &queue), Ok(vec![15, 5, 30]));
+    // Shares of 10.5, 3.5 and 21 rounded down to 10, 0 and 20, the remainder of 5 from the top
+    assert_eq!(pro_rata.allocate(35, 5, &queue), Ok(vec![15, 0, 20]));
+    // An exhausted level fills in time priority
+    assert_eq!(pro_rata.allocate(120, 5, &queue), Ok(vec![30, 10, 60]));
+
+    let hybrid = Allocation { algorithm: HYBRID_ALLOCATION, priority_share_bps: 4000 };
+    // Slice of 20 to the top order, then 30 shared among 10, 10 and 60 and the remainder of 10
+    // from the top
+    assert_eq!(hybrid.allocate(50, 5, &queue), Ok(vec![30, 0, 20]));
+    // `MulDiv` takes u32 values only, so larger quantities are rejected up front
+    let large = [MAX_ALLOCATED_QUANTITY, MAX_ALLOCATED_QUANTITY];
+    assert!(pro_rata.allocate(MAX_ALLOCATED_QUANTITY + 1, 1, &large).is_err());
+    assert!(pro_rata.validate_quantity(MAX_ALLOCATED_QUANTITY).is_ok());
+    assert!(pro_rata.validate_quantity(MAX_ALLOCATED_QUANTITY + 1).is_err());
+    assert!(fifo.validate_quantity(MAX_ALLOCATED_QUANTITY + 1).is_ok());
+
+    let mut operand_stack = hybrid.to_operand_stack();
+    operand_stack.extend([0; ALLOCATION_POSITION]);
+    assert_eq!(Allocation::from_operand_stack(&operand_stack), hybrid);
+    assert!(Allocation { algorithm: 3, priority_share_bps: 0 }.validate().is_err());
+}
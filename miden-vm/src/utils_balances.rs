@@ -0,0 +1,326 @@
+use crate::{
+    utils_fees::MAX_FEE_BPS,
+    utils_input::InputFile,
+    utils_order::IncomingOrder,
+    utils_orderbook::{stack_word, OrderBook, BUY_SIDE_ID, BUY_STOP_SIDE_ID},
+    utils_output::Fill,
+};
+use miden_vm::{crypto::SimpleSmt, math::StarkField, utils::collections::BTreeMap, Word};
+use serde::{Deserialize, Serialize};
+
+/// Operand stack position, counted from the top, of the balances root. The root sits below the
+/// instrument parameters and is '0' for no balances.
+pub const BALANCES_ROOT_POSITION: usize = 22;
+
+/// Base and quote balance of an account, the leaf [base, quote, 0, 0] read by `getBalance`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Balance {
+    pub base: u64,
+    pub quote: u64,
+}
+
+/// Off-circuit mirror of the account balances committed to by the balances root.
+///
+/// Balances are the leaves of a sparse Merkle tree of depth 64 indexed by account ID, the tree
+/// built from the `sparse_merkle_tree` merkle data of an input file. Missing accounts hold
+/// nothing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Balances {
+    pub accounts: BTreeMap<u64, Balance>,
+}
+
+impl Balances {
+    /// Returns the balances root the program reads below the instrument parameters, in stack
+    /// order.
+    pub fn root(&self) -> Result<[u64; 4], String> {
+        let leaves = self.leaves().collect::<Vec<_>>();
+        let smt = SimpleSmt::with_leaves(SimpleSmt::MAX_DEPTH, leaves)
+            .map_err(|e| format!("failed to build balances tree - {e}"))?;
+
+        let root = Word::from(smt.root());
+        Ok([root[3].as_int(), root[2].as_int(), root[1].as_int(), root[0].as_int()])
+    }
+
+    /// Reads the balances from the `sparse_merkle_tree` merkle data of an input file.
+    pub fn from_merkle_data(merkle_data: &[(u64, String)]) -> Result<Self, String> {
+        let mut accounts = BTreeMap::new();
+        for (account_id, leaf) in merkle_data {
+            let leaf = InputFile::parse_word(leaf)?;
+            let balance = Balance { base: leaf[3].as_int(), quote: leaf[2].as_int() };
+            accounts.insert(*account_id, balance);
+        }
+        Ok(Self { accounts })
+    }
+
+    /// Returns the `sparse_merkle_tree` merkle data holding the balances, leaves in hex.
+    pub fn to_merkle_data(&self) -> Vec<(u64, String)> {
+        self.leaves()
+            .map(|(account_id, leaf)| {
+                let hex =
+                    leaf.iter().map(|element| format!("{:016x}", element.as_int().swap_bytes()));
+                (account_id, hex.collect())
+            })
+            .collect()
+    }
+
+    /// Mirrors `getLockedFunds`: the funds an order locks for its unfilled quantity, the base
+    /// quantity of a sell order or the quote notional of a buy order plus its fee at
+    /// `MAX_FEE_BPS`, rounded down.
+    pub fn locked_funds(is_buy: bool, quantity: u64, price: u64) -> Result<u64, String> {
+        if !is_buy {
+            return Ok(quantity);
+        }
+        let overflow = || format!("funds locked by {quantity} at {price} overflow");
+        let notional = quantity.checked_mul(price).ok_or_else(overflow)?;
+        let fee = notional.checked_mul(MAX_FEE_BPS).ok_or_else(overflow)? / 10000;
+        notional.checked_add(fee).ok_or_else(overflow)
+    }
+
+    /// Mirrors `getReleasedFunds`: the funds an order with `unfilled_quantity` left releases once
+    /// `quantity` of it fills or leaves the book.
+    pub fn released_funds(
+        is_buy: bool,
+        quantity: u64,
+        unfilled_quantity: u64,
+        price: u64,
+    ) -> Result<u64, String> {
+        let remaining_quantity = unfilled_quantity
+            .checked_sub(quantity)
+            .ok_or_else(|| format!("{quantity} is more than the {unfilled_quantity} unfilled"))?;
+        Ok(Self::locked_funds(is_buy, unfilled_quantity, price)?
+            - Self::locked_funds(is_buy, remaining_quantity, price)?)
+    }
+
+    /// Mirrors `LockIncomingFunds`: debits the account with the funds an incoming order locks.
+    /// Fails, leaving the balances untouched, if the account runs short.
+    pub fn lock(
+        &mut self,
+        account_id: u64,
+        is_buy: bool,
+        quantity: u64,
+        price: u64,
+    ) -> Result<(), String> {
+        let funds = Self::locked_funds(is_buy, quantity, price)?;
+        if is_buy {
+            self.debit(account_id, 0, funds)
+        } else {
+            self.debit(account_id, funds, 0)
+        }
+    }
+
+    /// Mirrors `SettleFill`: the buyer gets the base quantity and the seller the quote notional
+    /// less its fee, both fees credited to account 0. The `buyer_funds` released by the buyer
+    /// order pay the notional and the buyer fee, and what is left goes back to the buyer. Fees
+    /// are `(buyer_bps, seller_bps)`, in whole quote units rounded down.
+    pub fn settle(
+        &mut self,
+        quantity: u64,
+        price: u64,
+        buyer_id: u64,
+        seller_id: u64,
+        buyer_funds: u64,
+        (buyer_bps, seller_bps): (u64, u64),
+    ) -> Result<(), String> {
+        let overflow = || format!("settlement of {quantity} at {price} overflows");
+        let notional = quantity.checked_mul(price).ok_or_else(overflow)?;
+        let fee = |bps: u64| notional.checked_mul(bps).map(|fee| fee / 10000).ok_or_else(overflow);
+        let (buyer_fee, seller_fee) = (fee(buyer_bps)?, fee(seller_bps)?);
+        let buyer_quote = notional
+            .checked_add(buyer_fee)
+            .and_then(|pays| buyer_funds.checked_sub(pays))
+            .ok_or_else(|| format!("funds of buyer {buyer_id} are not sufficient"))?;
+        let seller_quote = notional.checked_sub(seller_fee).ok_or_else(overflow)?;
+
+        self.credit(0, 0, buyer_fee + seller_fee);
+        self.credit(seller_id, 0, seller_quote);
+        self.credit(buyer_id, quantity, buyer_quote);
+        Ok(())
+    }
+
+    /// Moves the balances on by the transition of a single incoming order into the books.
+    ///
+    /// Locked funds are released as the orders holding them fill or leave, so every account ends
+    /// up with what it held, plus the funds its orders resting in `old_books` locked, less those
+    /// its orders resting in `new_books` lock, plus what its fills bought or sold. Fails if an
+    /// account runs short.
+    pub fn apply_transition(
+        &mut self,
+        order: &IncomingOrder,
+        old_books: &BTreeMap<u64, OrderBook>,
+        new_books: &BTreeMap<u64, OrderBook>,
+        fills: &[Fill],
+    ) -> Result<(), String> {
+        // Account and side of every order, resting or incoming
+        let mut orders = BTreeMap::new();
+        let mut changes = BTreeMap::<u64, (i128, i128)>::new();
+        for (books, sign) in [(old_books, 1), (new_books, -1)] {
+            for (side_id, entry) in books.values().flat_map(OrderBook::queued_orders) {
+                let is_buy = side_id == BUY_SIDE_ID || side_id == BUY_STOP_SIDE_ID;
+                orders.insert(entry.order_id, (entry.account_id, is_buy));
+                let funds =
+                    Self::locked_funds(is_buy, entry.quantity + entry.reserve, entry.price)?;
+                let change = changes.entry(entry.account_id).or_default();
+                if is_buy {
+                    change.1 += sign * funds as i128;
+                } else {
+                    change.0 += sign * funds as i128;
+                }
+            }
+        }
+        orders.insert(order.order_id, (order.account_id, order.buy_sell_flag == 1));
+
+        for fill in fills {
+            let account = |order_id: u64| {
+                orders.get(&order_id).copied().ok_or(format!("order {order_id} is unknown"))
+            };
+            let (taker_id, taker_is_buy) = account(fill.taker_id)?;
+            let (maker_id, _) = account(fill.maker_id)?;
+            let (buyer_id, seller_id, buyer_fee, seller_fee) = if taker_is_buy {
+                (taker_id, maker_id, fill.taker_fee, fill.maker_fee)
+            } else {
+                (maker_id, taker_id, fill.maker_fee, fill.taker_fee)
+            };
+            let notional = fill.quantity as i128 * fill.price as i128;
+            let (buyer_fee, seller_fee) = (buyer_fee as i128 / 10000, seller_fee as i128 / 10000);
+
+            let buyer = changes.entry(buyer_id).or_default();
+            buyer.0 += fill.quantity as i128;
+            buyer.1 -= notional + buyer_fee;
+            let seller = changes.entry(seller_id).or_default();
+            seller.0 -= fill.quantity as i128;
+            seller.1 += notional - seller_fee;
+            changes.entry(0).or_default().1 += buyer_fee + seller_fee;
+        }
+
+        let mut accounts = self.accounts.clone();
+        for (account_id, (base, quote)) in changes {
+            let balance = accounts.entry(account_id).or_default();
+            let short = || format!("balance of account {account_id} runs short");
+            balance.base = u64::try_from(balance.base as i128 + base).map_err(|_| short())?;
+            balance.quote = u64::try_from(balance.quote as i128 + quote).map_err(|_| short())?;
+        }
+        self.accounts = accounts;
+        Ok(())
+    }
+
+    /// Returns the balance of an account, zero for a missing account.
+    pub fn balance(&self, account_id: u64) -> Balance {
+        self.accounts.get(&account_id).copied().unwrap_or_default()
+    }
+
+    fn credit(&mut self, account_id: u64, base: u64, quote: u64) {
+        let balance = self.accounts.entry(account_id).or_default();
+        balance.base += base;
+        balance.quote += quote;
+    }
+
+    fn debit(&mut self, account_id: u64, base: u64, quote: u64) -> Result<(), String> {
+        let balance = self.accounts.entry(account_id).or_default();
+        if balance.base < base {
+            return Err(format!("base balance of account {account_id} is not sufficient"));
+        }
+        if balance.quote < quote {
+            return Err(format!("quote balance of account {account_id} is not sufficient"));
+        }
+        balance.base -= base;
+        balance.quote -= quote;
+        Ok(())
+    }
+
+    /// Returns the non-empty leaves of the balances tree, by account ID.
+    fn leaves(&self) -> impl Iterator<Item = (u64, Word)> + '_ {
+        self.accounts.iter().filter(|(_, balance)| **balance != Balance::default()).map(
+            |(account_id, balance)| (*account_id, stack_word([balance.base, balance.quote, 0, 0])),
+        )
+    }
+}
+
+#[test]
+fn test_settle() {
+    let mut balances = Balances::default();
+    balances.accounts.insert(1, Balance { base: 0, quote: 1000 });
+    balances.accounts.insert(2, Balance { base: 30, quote: 0 });
+
+    assert_eq!(Balances::locked_funds(true, 19, 50), Ok(959));
+    balances.lock(1, true, 19, 50).unwrap();
+    assert!(balances.lock(1, true, 1, 50).is_err());
+    assert_eq!(balances.balance(1), Balance { base: 0, quote: 41 });
+    balances.lock(2, false, 30, 48).unwrap();
+    assert!(Balances::locked_funds(true, u64::MAX, 2).is_err());
+
+    let buyer_funds = Balances::released_funds(true, 10, 19, 50).unwrap();
+    assert_eq!(buyer_funds + Balances::released_funds(true, 9, 9, 50).unwrap(), 959);
+    assert!(balances.settle(10, 48, 1, 2, 480, (100, 50)).is_err());
+    balances.settle(10, 48, 1, 2, buyer_funds, (100, 50)).unwrap();
+    assert_eq!(balances.balance(0), Balance { base: 0, quote: 6 });
+    assert_eq!(balances.balance(1), Balance { base: 10, quote: 62 });
+    assert_eq!(balances.balance(2), Balance { base: 0, quote: 478 });
+}
+
+#[test]
+fn test_apply_transition() {
+    use crate::utils_output::{ChangeLogEntry, QueueEntry};
+
+    // A sell order of account 2 rests at 50, 4 of its 10 filled by a buy order of account 1
+    let book = |quantity: u64| {
+        let mut book = OrderBook::default();
+        let init = |node_pointer: u64, order_id: u64| ChangeLogEntry {
+            node_pointer,
+            color: 2,
+            order_id,
+            ..Default::default()
+        };
+        book.nodes.insert(1, init(1, 0));
+        book.nodes.insert(4, init(4, 2));
+        let level = ChangeLogEntry {
+            node_pointer: 7,
+            parent_pointer: 4,
+            quantity,
+            price: 50,
+            time: 1,
+            order_id: 1,
+            ..Default::default()
+        };
+        book.nodes.insert(7, level);
+        let entry = QueueEntry {
+            slot: 1,
+            account_id: 2,
+            quantity,
+            price: 50,
+            time: 1,
+            order_id: 11,
+            ..Default::default()
+        };
+        book.slots.insert(1, entry);
+        [(0, book)].into_iter().collect::<BTreeMap<_, _>>()
+    };
+    let order = IncomingOrder {
+        quantity: 4,
+        price: 52,
+        order_id: 12,
+        buy_sell_flag: 1,
+        account_id: 1,
+        ..Default::default()
+    };
+    let fill = Fill {
+        quantity: 4,
+        price: 50,
+        maker_id: 11,
+        taker_id: 12,
+        maker_fee: 200 * 50,
+        taker_fee: 200 * 100,
+    };
+
+    let mut balances = Balances::default();
+    balances.accounts.insert(1, Balance { base: 0, quote: 1000 });
+    balances.apply_transition(&order, &book(10), &book(6), &[fill.clone()]).unwrap();
+    assert_eq!(balances.balance(0), Balance { base: 0, quote: 3 });
+    assert_eq!(balances.balance(1), Balance { base: 4, quote: 798 });
+    assert_eq!(balances.balance(2), Balance { base: 0, quote: 199 });
+    assert_eq!(Balances::from_merkle_data(&balances.to_merkle_data()), Ok(balances));
+
+    let mut balances = Balances::default();
+    balances.accounts.insert(1, Balance { base: 0, quote: 100 });
+    assert!(balances.apply_transition(&order, &book(10), &book(6), &[fill]).is_err());
+    assert_eq!(balances.balance(1), Balance { base: 0, quote: 100 });
+}
@@ -0,0 +1,331 @@
+use crate::{
+    utils_input::InputFile,
+    utils_orderbook::{apply_outputs, books_from_advice_map, OrderBook, SELL_SIDE_ID},
+    utils_output::{DecodedOutputs, Fill},
+    utils_proof::ProofBundle,
+};
+use miden_vm::utils::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Execution of one fill against the book committed to by the proof.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FillExecution {
+    pub instrument_id: u64,
+    pub maker_id: u64,
+    pub taker_id: u64,
+    /// Whether the taker bought, lifting asks, or sold, hitting bids.
+    pub is_buy: bool,
+    pub quantity: u64,
+    /// Price achieved by the taker.
+    pub price: u64,
+    /// Best opposite price resting in the committed book when the fill happened, after the
+    /// earlier fills of the proof.
+    pub best_price: u64,
+    /// Best opposite price resting in the committed book when the taker arrived.
+    pub arrival_price: u64,
+    /// Distance of the price achieved from the arrival price, positive when worse for the taker.
+    pub slippage: i64,
+    /// Slippage in basis points of the arrival price.
+    pub slippage_bps: i64,
+    /// Fee charged to the maker, in quote units as settled.
+    pub maker_fee: u64,
+    /// Fee charged to the taker, in quote units as settled.
+    pub taker_fee: u64,
+    /// Notional the taker paid or received net of its fee: plus the fee when buying, minus it
+    /// when selling.
+    pub taker_net_notional: u64,
+    /// Notional the maker paid or received net of its fee.
+    pub maker_net_notional: u64,
+    /// Distance of the taker net notional from the quantity at the arrival price in basis points
+    /// of the latter, positive when worse for the taker.
+    pub net_slippage_bps: i64,
+    /// No opposite order resting at a better price than the price achieved was left unfilled.
+    pub no_better_order_skipped: bool,
+}
+
+/// Human-readable attestation of the best execution of the fills of a proven transition.
+///
+/// Prices are checked against the books of the input file, whose roots the proof commits to as
+/// old roots. Orders resting in the course of a batch are not in those books, so a fill against
+/// one of them may be better than the best price, at a negative slippage. The report is only
+/// built for a bundle whose proof verifies, and carries its best execution flag next to its own
+/// checks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BestExecutionReport {
+    /// Hash of the proven program as hex, empty if the bundle has none.
+    pub program_hash: String,
+    pub old_root: [u64; 4],
+    pub new_root: [u64; 4],
+    /// Best execution flag output by the proof, see `DecodedOutputs::best_execution`.
+    pub proven_best_execution: Option<bool>,
+    pub fills: Vec<FillExecution>,
+}
+
+impl BestExecutionReport {
+    /// Builds the report of the decoded fills of a proof bundle, failing if its proof does not
+    /// verify.
+    pub fn new(bundle: &ProofBundle, fills: &[Fill]) -> Result<Self, String> {
+        bundle.verify().map_err(|e| format!("proof bundle does not verify - {e}"))?;
+        let outputs = bundle.decode()?;
+        if outputs.fills != fills {
+            return Err("fills do not match the outputs of the proof bundle".to_string());
+        }
+
+        let input_file: InputFile =
+            serde_json::from_str(&bundle.inputs).map_err(|e| e.to_string())?;
+        let books = books_from_advice_map(&input_file.advice_map.unwrap_or_default())?;
+        let program_hash = bundle
+            .outputs
+            .program_hash
+            .iter()
+            .flatten()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        Self::from_books(program_hash, &books, &outputs)
+    }
+
+    /// Builds the report of the fills of `outputs` against the books they were proven on.
+    fn from_books(
+        program_hash: String,
+        books: &BTreeMap<u64, OrderBook>,
+        outputs: &DecodedOutputs,
+    ) -> Result<Self, String> {
+        let first_book = *books.keys().next().ok_or("no book in the inputs")?;
+        let old_roots = outputs.books.iter().map(|book| (book.instrument_id, book.old_root));
+        for (instrument_id, old_root) in
+            [(first_book, outputs.old_root)].into_iter().chain(old_roots)
+        {
+            if books.get(&instrument_id).map(OrderBook::root) != Some(old_root) {
+                return Err(format!(
+                    "book of instrument {instrument_id} does not match its committed root"
+                ));
+            }
+        }
+        let mut books_after = books.clone();
+        apply_outputs(&mut books_after, outputs)?;
+
+        // Depth of every side, taken down by the fills as they happen
+        let mut depths = BTreeMap::new();
+        let mut arrival_prices = BTreeMap::new();
+        let mut executions = Vec::new();
+        for fill in &outputs.fills {
+            let (instrument_id, side_id) = [books, &books_after]
+                .into_iter()
+                .flat_map(|books| books.iter())
+                .find_map(|(instrument_id, book)| {
+                    book.order_side_id(fill.maker_id).map(|side_id| (*instrument_id, side_id))
+                })
+                .ok_or(format!("maker {} of a fill rests in no book", fill.maker_id))?;
+            let depth: &mut BTreeMap<u64, u64> = depths
+                .entry((instrument_id, side_id))
+                .or_insert_with(|| books[&instrument_id].depth(side_id));
+
+            // The taker lifts asks from the lowest one up and hits bids from the highest one down
+            let is_buy = side_id == SELL_SIDE_ID;
+            let best_price = if is_buy { depth.keys().next() } else { depth.keys().next_back() }
+                .copied()
+                .unwrap_or(fill.price);
+            let arrival_price = *arrival_prices.entry(fill.taker_id).or_insert(best_price);
+            let slippage = if is_buy {
+                fill.price as i64 - arrival_price as i64
+            } else {
+                arrival_price as i64 - fill.price as i64
+            };
+            let slippage_bps =
+                if arrival_price == 0 { 0 } else { slippage * 10000 / arrival_price as i64 };
+            let no_better_order_skipped =
+                if is_buy { best_price >= fill.price } else { best_price <= fill.price };
+
+            // Fees are settled in whole quote units, see `SettleFill`
+            let (maker_fee, taker_fee) = (fill.maker_fee / 10000, fill.taker_fee / 10000);
+            let notional = fill
+                .quantity
+                .checked_mul(fill.price)
+                .ok_or(format!("notional of a fill of maker {} overflows", fill.maker_id))?;
+            let (buyer_fee, seller_fee) =
+                if is_buy { (taker_fee, maker_fee) } else { (maker_fee, taker_fee) };
+            let buyer_notional = notional.checked_add(buyer_fee).ok_or("buyer fee overflows")?;
+            let seller_notional = notional.saturating_sub(seller_fee);
+            let (taker_net_notional, maker_net_notional) = if is_buy {
+                (buyer_notional, seller_notional)
+            } else {
+                (seller_notional, buyer_notional)
+            };
+            let arrival_notional = fill.quantity as i128 * arrival_price as i128;
+            let net_slippage = if is_buy {
+                taker_net_notional as i128 - arrival_notional
+            } else {
+                arrival_notional - taker_net_notional as i128
+            };
+            let net_slippage_bps = if arrival_notional == 0 {
+                0
+            } else {
+                (net_slippage * 10000 / arrival_notional) as i64
+            };
+
+            if let Some(quantity) = depth.get_mut(&fill.price) {
+                *quantity = quantity.saturating_sub(fill.quantity);
+                if *quantity == 0 {
+                    depth.remove(&fill.price);
+                }
+            }
+
+            executions.push(FillExecution {
+                instrument_id,
+                maker_id: fill.maker_id,
+                taker_id: fill.taker_id,
+                is_buy,
+                quantity: fill.quantity,
+                price: fill.price,
+                best_price,
+                arrival_price,
+                slippage,
+                slippage_bps,
+                maker_fee,
+                taker_fee,
+                taker_net_notional,
+                maker_net_notional,
+                net_slippage_bps,
+                no_better_order_skipped,
+            });
+        }
+
+        Ok(Self {
+            program_hash,
+            old_root: outputs.old_root,
+            new_root: outputs.new_root,
+            proven_best_execution: outputs.best_execution,
+            fills: executions,
+        })
+    }
+
+    /// Whether no fill of the report skipped a better priced resting order.
+    pub fn is_best_execution(&self) -> bool {
+        self.fills.iter().all(|fill| fill.no_better_order_skipped)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("failed to serialize report - {e}"))
+    }
+
+    /// Returns the report as a Markdown document, one table row per fill.
+    pub fn to_markdown(&self) -> String {
+        let root = |root: &[u64; 4]| {
+            root.iter().map(|element| format!("{element:016x}")).collect::<Vec<_>>().join(" ")
+        };
+        let mut markdown = String::from("# Best execution report\n\n");
+        if !self.program_hash.is_empty() {
+            writeln!(markdown, "- Program hash: `{}`", self.program_hash).unwrap();
+        }
+        writeln!(markdown, "- Committed book root: `{}`", root(&self.old_root)).unwrap();
+        writeln!(markdown, "- New book root: `{}`", root(&self.new_root)).unwrap();
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+        let proven = self.proven_best_execution.map_or("not applicable", yes_no);
+        writeln!(markdown, "- Best execution proven: {proven}").unwrap();
+        writeln!(markdown, "- Best execution: {}\n", yes_no(self.is_best_execution())).unwrap();
+
+        if self.fills.is_empty() {
+            markdown.push_str("No fills.\n");
+            return markdown;
+        }
+        markdown.push_str(
+            "| Instrument | Taker | Maker | Side | Quantity | Price | Best price | Arrival price \
+             | Slippage | Slippage (bps) | Taker fee | Maker fee | Taker net notional \
+             | Maker net notional | Net slippage (bps) | No better order skipped |\n",
+        );
+        markdown.push_str("|---|---|---|---|---|---|---|---|---|---|---|---|---|---|---|---|\n");
+        for fill in &self.fills {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                fill.instrument_id,
+                fill.taker_id,
+                fill.maker_id,
+                if fill.is_buy { "buy" } else { "sell" },
+                fill.quantity,
+                fill.price,
+                fill.best_price,
+                fill.arrival_price,
+                fill.slippage,
+                fill.slippage_bps,
+                fill.taker_fee,
+                fill.maker_fee,
+                fill.taker_net_notional,
+                fill.maker_net_notional,
+                fill.net_slippage_bps,
+                yes_no(fill.no_better_order_skipped),
+            )
+            .unwrap();
+        }
+        markdown
+    }
+}
+
+#[test]
+fn test_best_execution_report() {
+    use crate::utils_output::{ChangeLogEntry, QueueEntry};
+
+    // Every level queues a single order, its head and tail slot
+    let level = |node_pointer: u64, quantity: u64, price: u64, slot: u64| ChangeLogEntry {
+        node_pointer,
+        parent_pointer: 4,
+        quantity,
+        price,
+        time: slot,
+        order_id: slot,
+        ..Default::default()
+    };
+    let queued = |slot: u64, quantity: u64, price: u64, order_id: u64| QueueEntry {
+        slot,
+        quantity,
+        price,
+        time: 1,
+        order_id,
+        ..Default::default()
+    };
+    let mut book = OrderBook::default();
+    let init = |node_pointer: u64, order_id: u64| ChangeLogEntry {
+        node_pointer,
+        color: 2,
+        order_id,
+        ..Default::default()
+    };
+    book.nodes.insert(1, init(1, 1));
+    book.nodes.insert(4, init(4, 2));
+    book.nodes.insert(7, level(7, 10, 50, 1));
+    book.nodes.insert(10, level(10, 10, 51, 2));
+    book.creation_counter = 3;
+    book.slots.insert(1, queued(1, 10, 50, 11));
+    book.slots.insert(2, queued(2, 10, 51, 12));
+    book.slot_counter = 2;
+    let books = [(0, book.clone())].into_iter().collect();
+
+    let fill = |quantity: u64, price: u64, maker_id: u64| Fill {
+        quantity,
+        price,
+        maker_id,
+        taker_id: 9,
+        ..Default::default()
+    };
+    let outputs = DecodedOutputs {
+        old_root: book.root(),
+        fills: vec![fill(10, 50, 11), Fill { taker_fee: 255 * 100, ..fill(5, 51, 12) }],
+        ..Default::default()
+    };
+    let report = BestExecutionReport::from_books(String::new(), &books, &outputs).unwrap();
+    assert!(report.is_best_execution());
+    assert_eq!(report.fills[1].best_price, 51);
+    assert_eq!(report.fills[1].slippage, 1);
+    assert_eq!(report.fills[1].slippage_bps, 200);
+    assert_eq!(report.fills[1].taker_net_notional, 257);
+    assert_eq!(report.fills[1].maker_net_notional, 255);
+    assert_eq!(report.fills[1].net_slippage_bps, 280);
+    let row = "| 0 | 9 | 12 | buy | 5 | 51 | 51 | 50 | 1 | 200 | 2 | 0 | 257 | 255 | 280 | yes |";
+    assert!(report.to_markdown().contains(row));
+
+    let outputs = DecodedOutputs { fills: vec![fill(5, 51, 12)], ..outputs };
+    let report = BestExecutionReport::from_books(String::new(), &books, &outputs).unwrap();
+    assert!(!report.is_best_execution());
+}
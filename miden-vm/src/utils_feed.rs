@@ -0,0 +1,150 @@
+use crate::{
+    utils_orderbook::{OrderBook, BUY_SIDE_ID, SELL_SIDE_ID},
+    utils_output::DecodedOutputs,
+};
+use miden_vm::utils::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// New quantity resting at a price level, 0 once the level is gone.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LevelDelta {
+    pub instrument_id: u64,
+    pub is_buy: bool,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+/// A fill of the incoming order against a resting order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Trade {
+    pub instrument_id: u64,
+    pub quantity: u64,
+    pub price: u64,
+    pub maker_id: u64,
+    pub taker_id: u64,
+}
+
+/// Market data of one proven transition: the L2 depth deltas and the trades it led to, and the
+/// new tree root of every book it changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeedUpdate {
+    /// Reference of the proof attesting to the transition.
+    pub proof_id: u64,
+    pub roots: BTreeMap<u64, [u64; 4]>,
+    pub levels: Vec<LevelDelta>,
+    pub trades: Vec<Trade>,
+}
+
+impl FeedUpdate {
+    /// Derives the update from the books before and after a transition by an order of
+    /// `instrument_id`, the books after being moved on by the decoded outputs.
+    pub fn new(
+        proof_id: u64,
+        instrument_id: u64,
+        books_before: &BTreeMap<u64, OrderBook>,
+        books_after: &BTreeMap<u64, OrderBook>,
+        outputs: &DecodedOutputs,
+    ) -> Self {
+        let mut roots = BTreeMap::new();
+        if let Some(first_book) = books_after.keys().next() {
+            roots.insert(*first_book, outputs.new_root);
+        }
+        roots.extend(outputs.books.iter().map(|book| (book.instrument_id, book.new_root)));
+        roots.retain(|instrument_id, root| {
+            books_before.get(instrument_id).map(OrderBook::root) != Some(*root)
+        });
+
+        let mut levels = Vec::new();
+        for instrument_id in roots.keys() {
+            let empty = OrderBook::default();
+            let before = books_before.get(instrument_id).unwrap_or(&empty);
+            let after = books_after.get(instrument_id).unwrap_or(&empty);
+            for (side_id, is_buy) in [(BUY_SIDE_ID, true), (SELL_SIDE_ID, false)] {
+                for (price, quantity) in depth_deltas(&before.depth(side_id), &after.depth(side_id))
+                {
+                    levels.push(LevelDelta {
+                        instrument_id: *instrument_id,
+                        is_buy,
+                        price,
+                        quantity,
+                    });
+                }
+            }
+        }
+
+        let trades = outputs
+            .fills
+            .iter()
+            .map(|fill| Trade {
+                instrument_id,
+                quantity: fill.quantity,
+                price: fill.price,
+                maker_id: fill.maker_id,
+                taker_id: fill.taker_id,
+            })
+            .collect();
+
+        Self { proof_id, roots, levels, trades }
+    }
+}
+
+/// Returns the price levels whose quantity differs between two depths, 0 for a level gone.
+fn depth_deltas(before: &BTreeMap<u64, u64>, after: &BTreeMap<u64, u64>) -> Vec<(u64, u64)> {
+    let gone = before.keys().filter(|price| !after.contains_key(price)).map(|price| (*price, 0));
+    let changed = after
+        .iter()
+        .filter(|(price, quantity)| before.get(price) != Some(quantity))
+        .map(|(price, quantity)| (*price, *quantity));
+    gone.chain(changed).collect()
+}
+
+#[test]
+fn test_feed_update() {
+    use crate::utils_output::{ChangeLogEntry, Fill};
+
+    let level = |node_pointer: u64, quantity: u64, price: u64| ChangeLogEntry {
+        node_pointer,
+        parent_pointer: 1,
+        quantity,
+        price,
+        time: 1,
+        order_id: 1,
+        ..Default::default()
+    };
+    let mut before = OrderBook::default();
+    before.nodes.insert(1, ChangeLogEntry { node_pointer: 1, color: 2, ..Default::default() });
+    before.nodes.insert(4, level(4, 30, 48));
+    before.nodes.insert(7, level(7, 10, 47));
+    let mut after = before.clone();
+    after.nodes.insert(4, level(4, 20, 48));
+    after.nodes.insert(7, ChangeLogEntry { node_pointer: 7, ..Default::default() });
+
+    let outputs = DecodedOutputs {
+        new_root: after.root(),
+        fills: vec![Fill {
+            quantity: 10,
+            price: 48,
+            maker_id: 1,
+            taker_id: 9,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let update = FeedUpdate::new(
+        3,
+        5,
+        &[(5, before)].into_iter().collect(),
+        &[(5, after)].into_iter().collect(),
+        &outputs,
+    );
+
+    assert_eq!(update.roots.len(), 1);
+    assert_eq!(
+        update.levels,
+        vec![
+            LevelDelta { instrument_id: 5, is_buy: true, price: 47, quantity: 0 },
+            LevelDelta { instrument_id: 5, is_buy: true, price: 48, quantity: 20 },
+        ]
+    );
+    assert_eq!(update.trades[0].taker_id, 9);
+}
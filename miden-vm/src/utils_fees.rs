@@ -0,0 +1,145 @@
+use crate::utils_orderbook::{advice_map_key, stack_word, FEE_NAMESPACE};
+use miden_vm::{
+    crypto::{Rpo256, RpoDigest},
+    math::StarkField,
+    utils::collections::BTreeMap,
+    Word,
+};
+use serde::{Deserialize, Serialize};
+
+/// Highest maker or taker rate of a fee tier, in basis points. Buy orders lock their fee at it.
+pub const MAX_FEE_BPS: u64 = 100;
+
+/// Operand stack position, counted from the top, of the fee schedule commitment, below the 12
+/// incoming order inputs.
+pub const FEE_COMMITMENT_POSITION: usize = 12;
+
+/// A fee tier loaded by `LoadFeeSchedule`, [min_notional, maker_bps, taker_bps, 0] in memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeeTier {
+    /// Smallest fill notional, quantity times price, the tier applies to.
+    pub min_notional: u64,
+    pub maker_bps: u64,
+    pub taker_bps: u64,
+}
+
+/// Maker and taker fees in basis points per tier, committed to by the deepest word of the
+/// operand stack.
+///
+/// Tiers are sorted by `min_notional`. A fill pays the rates of the last tier at or below its
+/// notional, and no fees below the first tier.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeeSchedule {
+    pub tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    /// Returns the commitment asserted by `LoadFeeSchedule`, in stack order. An empty schedule
+    /// commits to zero, which the program reads as no fees.
+    pub fn commitment(&self) -> [u64; 4] {
+        let mut commitment = RpoDigest::default();
+        for tier in &self.tiers {
+            let tier_word = stack_word([tier.min_notional, tier.maker_bps, tier.taker_bps, 0]);
+            commitment = Rpo256::merge(&[commitment, tier_word.into()]);
+        }
+
+        let commitment = Word::from(commitment);
+        [
+            commitment[3].as_int(),
+            commitment[2].as_int(),
+            commitment[1].as_int(),
+            commitment[0].as_int(),
+        ]
+    }
+
+    /// Returns the advice map entries `LoadFeeSchedule` expects for this schedule.
+    pub fn to_advice_map(&self) -> BTreeMap<String, Vec<u64>> {
+        let mut advice_map = BTreeMap::new();
+        advice_map.insert(advice_map_key(FEE_NAMESPACE, 0), vec![0, 0, 0, self.tiers.len() as u64]);
+        for (index, tier) in self.tiers.iter().enumerate() {
+            advice_map.insert(
+                advice_map_key(FEE_NAMESPACE, index as u64 + 1),
+                vec![tier.min_notional, tier.maker_bps, tier.taker_bps, 0],
+            );
+        }
+        advice_map
+    }
+
+    /// Reads the schedule from the advice map entries `LoadFeeSchedule` reads, none for no fees.
+    pub fn from_advice_map(advice_map: &BTreeMap<String, Vec<u64>>) -> Result<Self, String> {
+        let number_of_tiers =
+            advice_map.get(&advice_map_key(FEE_NAMESPACE, 0)).and_then(|value| value.get(3));
+        let tiers = (1..=number_of_tiers.copied().unwrap_or(0))
+            .map(|index| {
+                let key = advice_map_key(FEE_NAMESPACE, index);
+                match advice_map.get(&key).map(Vec::as_slice) {
+                    Some([min_notional, maker_bps, taker_bps, 0]) => Ok(FeeTier {
+                        min_notional: *min_notional,
+                        maker_bps: *maker_bps,
+                        taker_bps: *taker_bps,
+                    }),
+                    _ => Err(format!("advice map has no fee tier `{key}`")),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tiers })
+    }
+
+    /// Mirrors the asserts of `LoadFeeSchedule`: tiers are strictly sorted by `min_notional` and
+    /// charge at most `MAX_FEE_BPS`.
+    pub fn validate(&self) -> Result<(), String> {
+        for tier in &self.tiers {
+            if tier.maker_bps > MAX_FEE_BPS || tier.taker_bps > MAX_FEE_BPS {
+                return Err(format!(
+                    "fee tier at {} charges more than {MAX_FEE_BPS} bps",
+                    tier.min_notional
+                ));
+            }
+        }
+        for tiers in self.tiers.windows(2) {
+            if tiers[0].min_notional >= tiers[1].min_notional {
+                return Err(format!(
+                    "fee tier at {} is not above the tier at {}",
+                    tiers[1].min_notional, tiers[0].min_notional
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the maker and taker fees `PrintFills` charges a fill, in 1/10000 of the quote unit.
+    pub fn fees(&self, quantity: u64, price: u64) -> Result<(u64, u64), String> {
+        let overflow = || format!("fees of {quantity} at {price} overflow");
+        let notional = quantity.checked_mul(price).ok_or_else(overflow)?;
+        match self.tiers.iter().filter(|tier| tier.min_notional <= notional).last() {
+            Some(tier) => Ok((
+                notional.checked_mul(tier.maker_bps).ok_or_else(overflow)?,
+                notional.checked_mul(tier.taker_bps).ok_or_else(overflow)?,
+            )),
+            None => Ok((0, 0)),
+        }
+    }
+}
+
+#[test]
+fn test_fees() {
+    let schedule = FeeSchedule {
+        tiers: vec![
+            FeeTier { min_notional: 100, maker_bps: 2, taker_bps: 4 },
+            FeeTier { min_notional: 1000, maker_bps: 1, taker_bps: 3 },
+        ],
+    };
+
+    assert_eq!(schedule.fees(1, 50), Ok((0, 0)));
+    assert_eq!(schedule.fees(10, 49), Ok((980, 1960)));
+    assert_eq!(schedule.fees(50, 48), Ok((2400, 7200)));
+    assert!(schedule.fees(u64::MAX, 2).is_err());
+    assert_eq!(FeeSchedule::default().commitment(), [0; 4]);
+    assert_eq!(FeeSchedule::from_advice_map(&schedule.to_advice_map()), Ok(schedule.clone()));
+
+    assert!(schedule.validate().is_ok());
+    let expensive = FeeTier { taker_bps: MAX_FEE_BPS + 1, ..schedule.tiers[0].clone() };
+    assert!(FeeSchedule { tiers: vec![expensive] }.validate().is_err());
+    let unsorted = FeeSchedule { tiers: schedule.tiers.into_iter().rev().collect() };
+    assert!(unsorted.validate().is_err());
+}
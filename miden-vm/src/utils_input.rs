@@ -1,8 +1,9 @@
+use crate::utils_signatures::SignatureAdviceProvider;
 use miden_vm::{
     crypto::{MerkleStore, MerkleTree, SimpleSmt},
     math::{Felt, FieldElement},
     utils::collections::BTreeMap,
-    AdviceInputs, MemAdviceProvider, StackInputs, StackOutputs, Word,
+    AdviceInputs, StackInputs, StackOutputs, Word,
 };
 
 /// The Outputs struct is used to serialize the output of the program.
@@ -61,7 +62,7 @@ pub struct InputFile {
 /// Helper methods to interact with the input file
 impl InputFile {
     /// Parse advice provider data from the input file.
-    pub fn parse_advice_provider(&self) -> Result<MemAdviceProvider, String> {
+    pub fn parse_advice_provider(&self) -> Result<SignatureAdviceProvider, String> {
         let mut advice_inputs = AdviceInputs::default();
 
         let stack = self
@@ -85,7 +86,7 @@ impl InputFile {
             advice_inputs = advice_inputs.with_merkle_store(merkle_store);
         }
 
-        Ok(MemAdviceProvider::from(advice_inputs))
+        Ok(SignatureAdviceProvider::from(advice_inputs))
     }
 
     /// Parse advice stack data from the input file.
@@ -217,7 +218,7 @@ impl InputFile {
 /// Miden Inputs plus Outputs that are used as inputs for the verifier.
 pub struct Inputs {
     pub stack_inputs: StackInputs,
-    pub advice_provider: MemAdviceProvider,
+    pub advice_provider: SignatureAdviceProvider,
     pub stack_outputs: StackOutputs,
 }
 
@@ -226,7 +227,7 @@ impl Inputs {
     pub fn new() -> Self {
         Self {
             stack_inputs: StackInputs::new(vec![Felt::ZERO]),
-            advice_provider: MemAdviceProvider::default(),
+            advice_provider: SignatureAdviceProvider::default(),
             stack_outputs: StackOutputs::new(vec![], vec![]).unwrap(),
         }
     }
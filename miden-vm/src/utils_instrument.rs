@@ -0,0 +1,290 @@
+use crate::{
+    utils_allocation::Allocation,
+    utils_input::InputFile,
+    utils_order::{batch_commitment, IncomingOrder, BATCH_COMMITMENT_POSITION, MARKET_ORDER},
+    utils_orderbook::{advice_map_key, instrument_ids, BATCH_NAMESPACE},
+    utils_query::{QUANTITY_UP_TO_QUERY, QUERY_MODE},
+};
+use serde::{Deserialize, Serialize};
+
+/// Operand stack flag selecting batch mode, see `ProcessBatch`.
+const BATCH_MODE: u64 = 2;
+
+/// Operand stack flag selecting call auction mode, see `Uncross`.
+const AUCTION_MODE: u64 = 3;
+
+/// Operand stack position, counted from the top, of the first instrument parameter. Parameters
+/// sit below the 12 incoming order inputs and the fee schedule commitment.
+pub const INSTRUMENT_PARAMS_POSITION: usize = 16;
+
+/// Operand stack position, counted from the top, of the instrument ID of the incoming order,
+/// below the instrument parameters and the balances root. It selects the book the order goes to.
+pub const INSTRUMENT_ID_POSITION: usize = 26;
+
+/// Instrument parameters every incoming order is asserted against by `ValidateOrder`.
+///
+/// A 0 parameter is not checked. Quantity and price must always be positive.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InstrumentParams {
+    pub tick_size: u64,
+    pub lot_size: u64,
+    pub min_quantity: u64,
+    pub max_quantity: u64,
+    pub reference_price: u64,
+    /// Largest distance of a price to `reference_price`.
+    pub price_band: u64,
+}
+
+impl InstrumentParams {
+    /// Reads the parameters from an operand stack listed bottom first, as in input files.
+    pub fn from_operand_stack(operand_stack: &[u64]) -> Self {
+        let parameter =
+            |index: usize| stack_element(operand_stack, INSTRUMENT_PARAMS_POSITION + index);
+        Self {
+            tick_size: parameter(0),
+            lot_size: parameter(1),
+            min_quantity: parameter(2),
+            max_quantity: parameter(3),
+            reference_price: parameter(4),
+            price_band: parameter(5),
+        }
+    }
+
+    /// Returns the bottom of the operand stack holding the parameters, listed bottom first.
+    pub fn to_operand_stack(&self) -> Vec<u64> {
+        vec![
+            self.price_band,
+            self.reference_price,
+            self.max_quantity,
+            self.min_quantity,
+            self.lot_size,
+            self.tick_size,
+        ]
+    }
+
+    /// Mirrors `ValidateOrder`. Price is checked unless the order is a stop market order, the
+    /// trigger price is checked for a stop. A market order is no stop and its price is a slippage
+    /// cap of at most 10000 bps.
+    pub fn validate_order(
+        &self,
+        quantity: u64,
+        price: u64,
+        time_in_force: u64,
+        trigger_price: u64,
+    ) -> Result<(), String> {
+        self.validate_quantity(quantity)?;
+        if time_in_force == MARKET_ORDER {
+            if trigger_price != 0 {
+                return Err("market order is a stop".to_string());
+            }
+            if price > 10000 {
+                return Err(format!("slippage cap of {price} bps is above 10000 bps"));
+            }
+            return Ok(());
+        }
+        if trigger_price == 0 || price != 0 {
+            self.validate_price(price)?;
+        }
+        if trigger_price != 0 {
+            self.validate_price(trigger_price)?;
+        }
+        Ok(())
+    }
+
+    /// Mirrors the asserts of `ValidateQuantity`.
+    fn validate_quantity(&self, quantity: u64) -> Result<(), String> {
+        if quantity == 0 {
+            return Err("quantity is not positive".to_string());
+        }
+        if self.lot_size != 0 && !is_multiple(quantity, self.lot_size) {
+            return Err(format!(
+                "quantity {quantity} is not a multiple of lot size {}",
+                self.lot_size
+            ));
+        }
+        if self.min_quantity != 0 && quantity < self.min_quantity {
+            return Err(format!("quantity {quantity} is below min quantity {}", self.min_quantity));
+        }
+        if self.max_quantity != 0 && quantity > self.max_quantity {
+            return Err(format!("quantity {quantity} is above max quantity {}", self.max_quantity));
+        }
+        Ok(())
+    }
+
+    /// Mirrors the asserts of `ValidatePrice`.
+    fn validate_price(&self, price: u64) -> Result<(), String> {
+        if price == 0 {
+            return Err("price is not positive".to_string());
+        }
+        if self.tick_size != 0 && !is_multiple(price, self.tick_size) {
+            return Err(format!("price {price} is not a multiple of tick size {}", self.tick_size));
+        }
+        if self.price_band != 0 && price.abs_diff(self.reference_price) > self.price_band {
+            return Err(format!(
+                "price {price} is outside the price band {} around reference price {}",
+                self.price_band, self.reference_price
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the incoming orders of an input file against its instrument parameters before
+/// proving, with the messages of the asserts the program would fail on.
+pub fn validate_inputs(inputs: &str) -> Result<(), String> {
+    if inputs.trim().is_empty() {
+        return Ok(());
+    }
+    let input_file: InputFile = serde_json::from_str(inputs).map_err(|e| e.to_string())?;
+    let operand_stack = input_file
+        .operand_stack
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .map(|v| v.parse::<u64>().map_err(|e| format!("failed to parse operand `{v}` - {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let params = InstrumentParams::from_operand_stack(&operand_stack);
+    let allocation = Allocation::from_operand_stack(&operand_stack);
+    allocation.validate()?;
+    let advice_map = input_file.advice_map.unwrap_or_default();
+    let instrument_ids = instrument_ids(&advice_map)?;
+    let has_book = |instrument_id: u64| {
+        if instrument_ids.contains(&instrument_id) {
+            Ok(())
+        } else {
+            Err(format!("instrument {instrument_id} has no book"))
+        }
+    };
+
+    match stack_element(&operand_stack, 0) {
+        BATCH_MODE => {
+            let number_of_orders = advice_map
+                .get(&advice_map_key(BATCH_NAMESPACE, 0))
+                .and_then(|value| value.get(3).copied())
+                .ok_or("advice map has no number of incoming orders")?;
+            let mut orders = Vec::new();
+            for index in 1..=number_of_orders {
+                let key = advice_map_key(BATCH_NAMESPACE, index);
+                let order = advice_map
+                    .get(&key)
+                    .ok_or(format!("advice map has no incoming order `{key}`"))
+                    .and_then(|value| IncomingOrder::from_batch_advice_value(value))?;
+                has_book(order.instrument_id)
+                    .and_then(|_| {
+                        params.validate_order(
+                            order.quantity,
+                            order.price,
+                            order.time_in_force,
+                            order.trigger_price,
+                        )
+                    })
+                    .and_then(|_| allocation.validate_quantity(order.quantity))
+                    .map_err(|e| format!("order {}: {e}", order.order_id))?;
+                orders.push(order);
+            }
+
+            let commitment: [u64; 4] = core::array::from_fn(|i| {
+                stack_element(&operand_stack, BATCH_COMMITMENT_POSITION + i)
+            });
+            if batch_commitment(&orders) != commitment {
+                return Err("batch does not match its commitment".to_string());
+            }
+            Ok(())
+        }
+        AUCTION_MODE => has_book(stack_element(&operand_stack, INSTRUMENT_ID_POSITION)),
+        QUERY_MODE => {
+            has_book(stack_element(&operand_stack, INSTRUMENT_ID_POSITION))?;
+            let query = stack_element(&operand_stack, 1);
+            if query > QUANTITY_UP_TO_QUERY {
+                return Err(format!("query {query} is unknown"));
+            }
+            Ok(())
+        }
+        _ => {
+            let order = |position: usize| stack_element(&operand_stack, position);
+            has_book(order(INSTRUMENT_ID_POSITION))
+                .and_then(|_| params.validate_order(order(1), order(2), order(5), order(9)))
+                .and_then(|_| allocation.validate_quantity(order(1)))
+                .map_err(|e| format!("order {}: {e}", order(4)))
+        }
+    }
+}
+
+/// Returns the element at a position counted from the top of an operand stack listed bottom
+/// first. Elements past the listed ones are 0.
+pub(crate) fn stack_element(operand_stack: &[u64], position: usize) -> u64 {
+    operand_stack.len().checked_sub(position + 1).map_or(0, |index| operand_stack[index])
+}
+
+/// Mirrors `u32checked_mod`, which fails on values beyond 32 bits.
+fn is_multiple(value: u64, step: u64) -> bool {
+    value <= u32::MAX as u64 && step <= u32::MAX as u64 && value % step == 0
+}
+
+#[test]
+fn test_validate_order() {
+    let params = InstrumentParams {
+        tick_size: 2,
+        lot_size: 5,
+        min_quantity: 10,
+        max_quantity: 100,
+        reference_price: 50,
+        price_band: 10,
+    };
+
+    assert_eq!(params.validate_order(20, 48, 0, 0), Ok(()));
+    assert_eq!(params.validate_order(0, 48, 0, 0), Err("quantity is not positive".to_string()));
+    assert_eq!(
+        params.validate_order(21, 48, 0, 0),
+        Err("quantity 21 is not a multiple of lot size 5".to_string())
+    );
+    assert_eq!(
+        params.validate_order(20, 49, 0, 0),
+        Err("price 49 is not a multiple of tick size 2".to_string())
+    );
+    assert_eq!(
+        params.validate_order(20, 62, 0, 0),
+        Err("price 62 is outside the price band 10 around reference price 50".to_string())
+    );
+    // Stop market orders only check their trigger price
+    assert_eq!(params.validate_order(20, 0, 0, 44), Ok(()));
+    // Market orders carry a slippage cap in place of a price
+    assert_eq!(params.validate_order(20, 300, MARKET_ORDER, 0), Ok(()));
+    assert!(params.validate_order(20, 300, MARKET_ORDER, 44).is_err());
+
+    let mut operand_stack = params.to_operand_stack();
+    operand_stack.extend([0; INSTRUMENT_PARAMS_POSITION]);
+    assert_eq!(InstrumentParams::from_operand_stack(&operand_stack), params);
+
+    // Pro rata and hybrid allocation take u32 quantities only
+    use crate::{
+        utils_allocation::{ALLOCATION_POSITION, MAX_ALLOCATED_QUANTITY},
+        utils_orderbook::{books_to_advice_map, OrderBook},
+    };
+    let books = miden_vm::utils::collections::BTreeMap::from([(0, OrderBook::default())]);
+    let inputs = |algorithm: u64| {
+        let order = IncomingOrder {
+            buy_sell_flag: 1,
+            quantity: MAX_ALLOCATED_QUANTITY + 1,
+            price: 50,
+            order_id: 1,
+            ..Default::default()
+        };
+        let mut operand_stack = Allocation { algorithm, priority_share_bps: 0 }.to_operand_stack();
+        operand_stack.extend(vec![0; ALLOCATION_POSITION - order.to_operand_stack().len()]);
+        operand_stack.extend(order.to_operand_stack());
+        serde_json::json!({
+            "operand_stack": operand_stack.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "advice_map": books_to_advice_map(&books),
+        })
+        .to_string()
+    };
+    assert!(validate_inputs(&inputs(0)).is_ok());
+    assert!(validate_inputs(&inputs(1)).unwrap_err().contains("pro rata and hybrid allocation"));
+
+    // A batch whose orders were changed no longer matches its commitment
+    let batch = include_str!("../../market_batch_(order_batching)_small_tree.input");
+    assert_eq!(validate_inputs(batch), Ok(()));
+    let changed = batch.replace("60, 48, 1688476601", "65, 48, 1688476601");
+    assert_eq!(validate_inputs(&changed), Err("batch does not match its commitment".to_string()));
+}
@@ -0,0 +1,194 @@
+use crate::{
+    utils_feed::FeedUpdate,
+    utils_order::IncomingOrder,
+    utils_proof::ProofBundle,
+    utils_sequencer::{Sequencer, Transition},
+};
+use miden_vm::utils::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// A record of the journal, one JSON line each.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalRecord {
+    /// An incoming order accepted by the sequencer, with its signature advice and the tree root
+    /// of every book before and after it.
+    Order {
+        sequence: u64,
+        order: IncomingOrder,
+        #[serde(default)]
+        signature_advice: BTreeMap<String, Vec<u64>>,
+        old_roots: BTreeMap<u64, [u64; 4]>,
+        new_roots: BTreeMap<u64, [u64; 4]>,
+    },
+    /// The proof bundle of the order with the same sequence number.
+    Proof { sequence: u64, bundle: ProofBundle },
+    /// The proof of the order with the same sequence number failed. The books go back to the
+    /// state before it, dropping it and every order after it.
+    Rollback { sequence: u64 },
+}
+
+/// Durable append-only log of the orders accepted by a sequencer and of their proofs.
+///
+/// Every record is synced to disk before `append` returns. A crash while appending leaves at
+/// most a partial last line, which `read` skips.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Opens the journal at `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("failed to open journal {} - {e}", path.display()))?;
+        Ok(Self { path: path.to_path_buf(), file })
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), String> {
+        let mut line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("failed to append to journal {} - {e}", self.path.display()))
+    }
+
+    /// Reads every complete record of the journal at `path`, none if it does not exist.
+    pub fn read(path: &Path) -> Result<Vec<JournalRecord>, String> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let journal = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read journal {} - {e}", path.display()))?;
+
+        // Only the last line can be partial, cut by a crash before its newline was written
+        let complete = journal.rfind('\n').map_or("", |end| &journal[..end]);
+        complete
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("journal record {} is corrupt - {e}", index + 1))
+            })
+            .collect()
+    }
+}
+
+/// An order of the journal without a proof, rebuilt by `recover`.
+pub struct UnprovenOrder {
+    pub order: IncomingOrder,
+    pub transition: Transition,
+    pub update: FeedUpdate,
+}
+
+/// State rebuilt from a journal by `recover`.
+pub struct Recovery {
+    pub sequencer: Sequencer,
+    /// Sequence number of the last accepted order, 0 for none.
+    pub last_sequence: u64,
+    /// Orders without a proof in the journal, by sequence number.
+    pub unproven: BTreeMap<u64, UnprovenOrder>,
+    /// Orders dropped by a rollback, by sequence number.
+    pub rolled_back: Vec<u64>,
+}
+
+/// Rebuilds the books of `sequencer` by executing again every order of the journal, in order.
+///
+/// The tree roots of every book are checked against the journal before and after each order.
+/// A proof bundle in the journal is verified, and must prove the inputs the order is executed
+/// with again and the same tree roots. A rollback rebuilds the sequencer from the inputs of the
+/// order rolled back, and a proof of an order rolled back is ignored.
+pub fn recover(mut sequencer: Sequencer, records: &[JournalRecord]) -> Result<Recovery, String> {
+    let mut last_sequence = 0;
+    let mut unproven = BTreeMap::new();
+    let mut rolled_back = Vec::new();
+    for record in records {
+        match record {
+            JournalRecord::Order { sequence, order, signature_advice, old_roots, new_roots } => {
+                if *sequence != last_sequence + 1 {
+                    return Err(format!("journal skips from order {last_sequence} to {sequence}"));
+                }
+                if sequencer.roots() != *old_roots {
+                    return Err(format!("books do not match the old roots of order {sequence}"));
+                }
+                let books_before = sequencer.books.clone();
+                let transition = sequencer.sequence(order, signature_advice)?;
+                if sequencer.roots() != *new_roots {
+                    return Err(format!("books do not match the new roots of order {sequence}"));
+                }
+                let update = FeedUpdate::new(
+                    *sequence,
+                    order.instrument_id,
+                    &books_before,
+                    &sequencer.books,
+                    &transition.outputs,
+                );
+                unproven
+                    .insert(*sequence, UnprovenOrder { order: order.clone(), transition, update });
+                last_sequence = *sequence;
+            }
+            JournalRecord::Proof { sequence, .. } if rolled_back.contains(sequence) => {}
+            JournalRecord::Proof { sequence, bundle } => {
+                let UnprovenOrder { transition, .. } = unproven
+                    .remove(sequence)
+                    .ok_or(format!("proof of order {sequence} has no unproven order"))?;
+                if bundle.inputs != transition.inputs {
+                    return Err(format!("proof of order {sequence} does not match its inputs"));
+                }
+                bundle
+                    .verify()
+                    .map_err(|e| format!("proof of order {sequence} is invalid - {e}"))?;
+                let outputs = bundle.decode()?;
+                if (outputs.old_root, outputs.new_root)
+                    != (transition.outputs.old_root, transition.outputs.new_root)
+                {
+                    return Err(format!("proof of order {sequence} does not match its roots"));
+                }
+            }
+            JournalRecord::Rollback { sequence } => {
+                let failed = unproven
+                    .get(sequence)
+                    .ok_or(format!("rollback of order {sequence} has no unproven order"))?;
+                sequencer = Sequencer::from_inputs(&failed.transition.inputs)?;
+                unproven.split_off(sequence);
+                rolled_back.extend(*sequence..=last_sequence);
+            }
+        }
+    }
+
+    Ok(Recovery { sequencer, last_sequence, unproven, rolled_back })
+}
+
+#[test]
+fn test_read_skips_partial_record() {
+    let path = std::env::temp_dir().join(format!("journal-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut journal = Journal::open(&path).unwrap();
+    for sequence in 1..=2 {
+        journal
+            .append(&JournalRecord::Order {
+                sequence,
+                order: IncomingOrder { order_id: sequence, ..Default::default() },
+                signature_advice: BTreeMap::new(),
+                old_roots: BTreeMap::new(),
+                new_roots: BTreeMap::new(),
+            })
+            .unwrap();
+    }
+    journal.file.write_all(b"{\"order\":{\"seq").unwrap();
+
+    let records = Journal::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(matches!(records[1], JournalRecord::Order { sequence: 2, .. }));
+}
@@ -6,18 +6,403 @@ pub fn get_masm_code() -> String {{
 # Reference: https://en.wikipedia.org/wiki/Red%E2%80%93black_tree
 #
 
+use.std::crypto::dsa::rpo_falcon512
+use.std::math::u64
+
+proc.getBookNumber
+  # Inputs:  [...]
+  # Outputs: [book_number, ...]
+  #
+  # Comments: Book selected by SelectBook, kept at address 2147483636. '0' is the first book
+  #
+  mem_load.2147483636
+end
+
+proc.getBookBase
+  # Inputs:  [...]
+  # Outputs: [book_base, ...]
+  # book_base = book_number * 8388608
+  #
+  # Comments: Every book owns 8388608 node addresses from book_base and as many slot addresses
+  #           from 536870912 + book_base, so 64 books at most. The first book sits at address 0
+  #
+  exec.getBookNumber push.8388608 mul
+end
+
+proc.getCreationCounter
+  # Inputs:  [...]
+  # Outputs: [creation_counter, ...]
+  #
+  # Comments: Creation number of the selected book, kept at its book_base
+  #
+  exec.getBookBase mem_load
+end
+
+proc.SaveCreationCounter
+  # Inputs:  [creation_counter, ...]
+  # Outputs: [...]
+  #
+  exec.getBookBase mem_store
+end
+
 proc.getNodePointer
   # Inputs:  [creation_counter, ...]
   # Outputs: [node_pointer, ...]
-  # node_pointer = creation_counter * 3 + 1
+  # node_pointer = creation_counter * 3 + 1 + book_base
   #
   # Comments: node_pointer >= 1.  Three items in memory: coordinate_word, order_word, and change flag
   #
   push.3 mul push.1 add  # Generate node_pointer
+  exec.getBookBase add   # Move into selected book
+end
+
+proc.ToMemoryPointer
+  # Inputs:  [book_pointer, ...]
+  # Outputs: [node_pointer, ...]
+  #
+  # Comments: Advice map and change log pointers count from the book_base, NIL stays NIL
+  #
+  dup push.0 neq
+  if.true
+    exec.getBookBase add
+  end
+end
+
+proc.ToBookPointer
+  # Inputs:  [node_pointer, ...]
+  # Outputs: [book_pointer, ...]
+  #
+  dup push.0 neq
+  if.true
+    exec.getBookBase sub
+  end
+end
+
+proc.ToMemoryCoordinates
+  # Inputs:  [color, parent_pointer, L_child_pointer, R_child_pointer, ...]
+  # Outputs: [color, parent_pointer, L_child_pointer, R_child_pointer, ...]
+  #
+  swap exec.ToMemoryPointer swap
+  movup.2 exec.ToMemoryPointer movdn.2
+  movup.3 exec.ToMemoryPointer movdn.3
+end
+
+proc.ToBookCoordinates
+  # Inputs:  [color, parent_pointer, L_child_pointer, R_child_pointer, ...]
+  # Outputs: [color, parent_pointer, L_child_pointer, R_child_pointer, ...]
+  #
+  swap exec.ToBookPointer swap
+  movup.2 exec.ToBookPointer movdn.2
+  movup.3 exec.ToBookPointer movdn.3
 end
 
 proc.getTotalNodes
-  mem_load.0 push.2 sub 
+  exec.getCreationCounter push.2 sub 
+end
+
+proc.getFillPointer
+  # Inputs:  [fill_number, ...]
+  # Outputs: [fill_pointer, ...]
+  # fill_pointer = fill_number + 2147483648
+  #
+  # Comments: fill_pointer(0) holds the fill counter. Fill words live far above the node pointers
+  #
+  push.2147483648 add    # Generate fill_pointer
+end
+
+proc.getFillFundsPointer
+  # Inputs:  [fill_number, ...]
+  # Outputs: [funds_pointer, ...]
+  # funds_pointer = fill_number + 2684354560
+  #
+  # Comments: Funds the buyer of the fill releases to pay for it, spent by SettleFill
+  #
+  push.2684354560 add    # Generate funds_pointer
+end
+
+proc.getReportPointer
+  # Inputs:  [report_number, ...]
+  # Outputs: [report_pointer, ...]
+  # report_pointer = report_number + 1610612736
+  #
+  # Comments: report_pointer(0) holds the report counter. One report word per incoming order
+  #
+  push.1610612736 add    # Generate report_pointer
+end
+
+proc.getQuotePointer
+  # Inputs:  [quote_number, ...]
+  # Outputs: [quote_pointer, ...]
+  # quote_pointer = quote_number + 1744830464
+  #
+  # Comments: quote_pointer(0) holds the quote counter. Quote words answer a query mode query
+  #
+  push.1744830464 add    # Generate quote_pointer
+end
+
+proc.getFeeTierPointer
+  # Inputs:  [tier_number, ...]
+  # Outputs: [tier_pointer, ...]
+  # tier_pointer = tier_number + 1879048192
+  #
+  # Comments: tier_pointer(0) holds the number of fee tiers. One tier word per fee tier:
+  #           [min_notional, maker_bps, taker_bps, 0]
+  #
+  push.1879048192 add    # Generate tier_pointer
+end
+
+proc.getIncomingOrder
+  # Inputs:  [...]
+  # Outputs: [quantity, price, time, ID, ...]
+  #
+  # Comments: Incoming order is kept at address 2147483646 while it is processed
+  #
+  push.0.0.0.0 mem_loadw.2147483646
+end
+
+proc.getIncomingFlags
+  # Inputs:  [...]
+  # Outputs: [buy-sell_flag, time_in_force, expiry, post_only, ...]
+  #
+  # Comments: Flags of the incoming order are kept at address 2147483643 while it is processed
+  #
+  push.0.0.0.0 mem_loadw.2147483643
+end
+
+proc.getIncomingAccount
+  # Inputs:  [...]
+  # Outputs: [account_ID, stp_policy, 0, 0, ...]
+  #
+  # Comments: Account of the incoming order is kept at address 2147483640 while it is processed
+  #
+  push.0.0.0.0 mem_loadw.2147483640
+end
+
+proc.getInstrumentParams
+  # Inputs:  [...]
+  # Outputs: [tick_size, lot_size, min_quantity, max_quantity, reference_price, price_band, ...]
+  #
+  # Comments: Instrument parameters are kept at addresses 2147483638 and 2147483637.
+  #           A '0' parameter is not checked
+  #
+  push.0.0.0.0 mem_loadw.2147483637 movup.2 drop movup.2 drop  # Load price band   [reference_price, price_band, ...]
+  push.0.0.0.0 mem_loadw.2147483638                            # Load tick and lot
+end
+
+proc.getAllocation
+  # Inputs:  [...]
+  # Outputs: [allocation_algorithm, priority_share_bps, ...]
+  #
+  # Comments: Allocation is kept at address 2147483627. Allocation_algorithm '0' fills the orders of
+  #           a level in time priority, '1' pro rata and '2' hybrid, priority_share_bps of the
+  #           quantity in time priority first
+  #
+  push.0.0.0.0 mem_loadw.2147483627 movup.2 drop movup.2 drop
+end
+
+proc.isIncomingBuy
+  # Inputs:  [...]
+  # Outputs: [isBuy_bool, ...]
+  #
+  exec.getIncomingFlags movdn.3 drop drop drop
+end
+
+proc.getTimeInForce
+  # Inputs:  [...]
+  # Outputs: [time_in_force, ...]
+  #
+  exec.getIncomingFlags drop movdn.2 drop drop
+end
+
+proc.getPostOnlyFlag
+  # Inputs:  [...]
+  # Outputs: [post_only, ...]
+  #
+  exec.getIncomingFlags drop drop drop
+end
+
+proc.getSlotPointer
+  # Inputs:  [slot, ...]
+  # Outputs: [slot_pointer, ...]
+  # slot_pointer = slot * 4 + 536870912 + book_base
+  #
+  # Comments: slot_pointer(0) holds the slot counter. Four items in memory per queued order:
+  #           order_word, link_word [next_slot, expiry, reserve, peak], change flag and
+  #           account_word [account_ID, stp_policy, 0, 0]
+  #
+  push.4 mul push.536870912 add  # Generate slot_pointer
+  exec.getBookBase add           # Move into selected book
+end
+
+proc.getSlotOrder
+  # Inputs:  [slot, ...]
+  # Outputs: [quantity, price, time, orderID, ...]
+  #
+  exec.getSlotPointer push.0.0.0.0 movup.4 mem_loadw  # Load queued order from memory
+end
+
+proc.getSlotAccount
+  # Inputs:  [slot, ...]
+  # Outputs: [account_ID, stp_policy, 0, 0, ...]
+  #
+  exec.getSlotPointer push.3 add push.0.0.0.0 movup.4 mem_loadw  # Load account word
+end
+
+proc.getNextSlot
+  # Inputs:  [slot, ...]
+  # Outputs: [next_slot, ...]
+  #
+  exec.getSlotPointer push.1 add push.0.0.0.0 movup.4 mem_loadw  # Load link word  [next_slot, expiry, reserve, peak, ...]
+  movdn.3 drop drop drop                                         # Isolate next slot
+end
+
+proc.getSlotReserve
+  # Inputs:  [slot, ...]
+  # Outputs: [reserve, ...]
+  #
+  # Comments: Hidden quantity of an iceberg order, 0 for an order displayed in full
+  #
+  exec.getSlotPointer push.1 add push.0.0.0.0 movup.4 mem_loadw  # Load link word  [next_slot, expiry, reserve, peak, ...]
+  drop drop swap drop                                            # Isolate reserve
+end
+
+proc.getSlotExpiry
+  # Inputs:  [slot, ...]
+  # Outputs: [expiry, ...]
+  #
+  exec.getSlotPointer push.1 add push.0.0.0.0 movup.4 mem_loadw  # Load link word  [next_slot, expiry, reserve, peak, ...]
+  drop movdn.2 drop drop                                         # Isolate expiry
+end
+
+proc.isSlotExpired
+  # Inputs:  [slot, ...]
+  # Outputs: [isExpired_bool, ...]
+  #
+  # Comments: A queued order is expired once the time of the incoming order, kept at
+  #           address 2147483646, passes its expiry. Expiry 0 never expires
+  #
+  exec.getSlotExpiry                   # Load expiry            [expiry, ...]
+  dup push.0 neq swap                  # Does order expire?     [expiry, doesExpire_bool, ...]
+  exec.getIncomingOrder                # Load incoming order    [quantity, price, time, ID, expiry, doesExpire_bool, ...]
+  drop drop swap drop                  # Isolate current time   [time, expiry, doesExpire_bool, ...]
+  lt and                               # Is expiry < time?      [isExpired_bool, ...]
+end
+
+proc.getSlotChangeFlag
+  # Inputs:  [slot, ...]
+  # Outputs: [change_flag, ...]
+  #
+  exec.getSlotPointer push.2 add mem_load
+end
+
+proc.SaveSlotChangeFlag
+  # Inputs:  [slot, ...]
+  # Outputs: [...]
+  #
+  push.1 swap                        # Set change flag      [slot, 1, ...]
+  exec.getSlotPointer push.2 add     # Set memory address   [slot_pointer+2, 1, ...]
+  mem_store                          # Save in RAM          [...]
+end
+
+proc.UpdateSlotOrder
+  # Inputs:  [slot, order_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: saves order word of queued order. Change flag is updated
+  #
+  dup exec.SaveSlotChangeFlag
+  exec.getSlotPointer mem_storew dropw
+end
+
+proc.SaveSlotLink
+  # Inputs:  [slot, link_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: saves link word of queued order. Change flag is updated
+  #
+  dup exec.SaveSlotChangeFlag
+  exec.getSlotPointer push.1 add       # Set memory address     [slot_pointer+1, link_word, ...]
+  mem_storew dropw                     # Save in RAM            [...]
+end
+
+proc.SaveSlotAccount
+  # Inputs:  [slot, account_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: saves account word of queued order. Change flag is updated
+  #
+  dup exec.SaveSlotChangeFlag
+  exec.getSlotPointer push.3 add       # Set memory address     [slot_pointer+3, account_word, ...]
+  mem_storew dropw                     # Save in RAM            [...]
+end
+
+proc.SetNextSlot
+  # Inputs:  [slot, next_slot, ...]
+  # Outputs: [...]
+  #
+  # Comments: links next_slot behind slot in its queue, keeping its expiry. Change flag is updated
+  #
+  dup exec.getSlotPointer push.1 add
+  push.0.0.0.0 movup.4 mem_loadw       # Load link word         [old_next_slot, expiry, reserve, peak, slot, next_slot, ...]
+  drop movup.4 movup.4                 # Build link word        [slot, next_slot, expiry, reserve, peak, ...]
+  exec.SaveSlotLink                    # Save in RAM            [...]
+end
+
+proc.ClearSlotReserve
+  # Inputs:  [slot, ...]
+  # Outputs: [...]
+  #
+  # Comments: Drops the hidden quantity of an iceberg order. Change flag is updated
+  #
+  dup exec.getSlotPointer push.1 add
+  push.0.0.0.0 movup.4 mem_loadw       # Load link word         [next_slot, expiry, reserve, peak, slot, ...]
+  movup.2 drop push.0 movdn.2          # Zero out reserve       [next_slot, expiry, 0, peak, slot, ...]
+  movup.4 exec.SaveSlotLink            # Save in RAM            [...]
+end
+
+proc.ZeroSlot
+  # Inputs:  [slot, ...]
+  # Outputs: [...]
+  #
+  # Comments: Zeros out queued order, link and account. Change flag is updated
+  #
+  push.0.0.0.0 dup.4 exec.SaveSlotAccount  # Zero out account   [slot, ...]
+  push.0.0.0.0 dup.4 exec.SaveSlotLink # Zero out link          [slot, ...]
+  push.0.0.0.0 movup.4                 # Zero order word        [slot, 0, 0, 0, 0, ...]
+  exec.UpdateSlotOrder
+end
+
+proc.NewSlot
+  # Inputs:  [...]
+  # Outputs: [slot, ...]
+  #
+  # Comments: increments slot counter and returns fresh slot
+  #
+  push.0 exec.getSlotPointer mem_load  # Load slot counter      [slot_counter, ...]
+  push.1 add dup                       # Increment counter      [slot_counter++, slot_counter++, ...]
+  push.0 exec.getSlotPointer mem_store # Save new counter       [slot_counter++, ...]
+end
+
+proc.LoadSlot
+  # Inputs:  [slot, ...]
+  # Outputs: [link_word, order_word, ...]
+  #
+  dup exec.getSlotOrder                # Load order             [order_word, slot, ...]
+  movup.4 exec.getSlotPointer push.1 add
+  push.0.0.0.0 movup.4 mem_loadw       # Load link              [link_word, order_word, ...]
+end
+
+proc.isSlotNIL
+  # Inputs:  [slot, ...]
+  # Outputs: [isNIL_bool, ...]
+  #
+  # Comments: Checks if queued order was zeroed out
+  #
+  exec.getSlotOrder                    # Load Order                       [order_quantity, order_price, order_time, order_index, ...]
+  push.0 eq                            # Is quantity zero                 [isQuantityZero_bool, order_price, order_time, order_index, ...]
+  swap push.0 eq and                   # Are price&quantity zero          [isPriceQuantityZero_bool, order_time, order_index, ...]
+  swap push.0 eq and                   # Are price&quantity&time zero     [isTimePriceQuantityZero_bool, order_index, ...]
+  swap push.0 eq and                   # Are price&quantity&time&idx zero [isIdxTimePriceQuantityZero_bool, ...]
 end
 
 proc.SaveCoordinate
@@ -156,7 +541,7 @@ proc.PrintAllNodesEssential
   # Output: [N, coordinate_word_N, price_N, ..., 2, coordinate_word_2, price_2, 1, coordinate_word_3, price_3, ... ]
   #
   push.0 dup                            # [0,0,....]
-  mem_load.0 lt                         # [isLessThanCreationNumber_bool, 0, ...]
+  exec.getCreationCounter lt            # [isLessThanCreationNumber_bool, 0, ...]
   while.true
      push.1 add dup                     # [j++, j++, ...]
      exec.getNodePointer                # [node_pointer, j++, ...]
@@ -165,7 +550,7 @@ proc.PrintAllNodesEssential
      swap exec.getCoordinates           # [coordinate_word, order_price, j++, ...]
      movup.5 dup                        # [j++, j++, coordinate_word, order_price, j++, ...]
      exec.getNodePointer swap           # [j++, node_pointer, coordinate_word, order_price, j++, ...]
-     dup mem_load.0 lt                  # [moreNodes_bool, j++, node_pointer, coordinate_word, order_price, j++, ...]
+     dup exec.getCreationCounter lt     # [moreNodes_bool, j++, node_pointer, coordinate_word, order_price, j++, ...]
   end
   drop
 end
@@ -181,35 +566,169 @@ proc.PrintAllNodes
      drop movup.8 dup                   # [j, j, node_coordinate_word, node_order_word, ...]
      exec.getNodePointer swap           # [j, node_pointer, node_coordinate_word, node_order_word, ...]
      push.1 add                         # [j++, node_pointer, node_coordinate_word, node_order_word, ...]
-     dup mem_load.0 lt                  # [moreNodes_bool, j++, node_pointer, node_coordinate_word, node_order_word, ...]
+     dup exec.getCreationCounter lt     # [moreNodes_bool, j++, node_pointer, node_coordinate_word, node_order_word, ...]
   end
   drop
 end
 
 proc.PrintChangeLog
   # Inputs: [...]
-  # Output: [N, coordinate_word_N, order_word_N, ..., 2, coordinate_word_2, order_word_2, 1, coordinate_word_3, order_word_3, ... ]
+  # Output: [node_count, N, coordinate_word_N, order_word_N, ..., 1, coordinate_word_1, order_word_1, ... ]
   #
-  # Comments: Prints all node data only for orders whose change flag has changed
+  # Comments: Prints all node data only for nodes whose change flag has changed, with pointers
+  #           counted from the book_base of the selected book
   #
-  push.0 dup                           # [0,0,....]
-  mem_load.0 lt                        # [isLessThanCreationNumber_bool, 0, ...]
+  push.0 push.0                        # Initialize counter and node count      [j=0, node_count=0, ...]
+  dup exec.getCreationCounter lt       # [isLessThanCreationNumber_bool, j, node_count, ...]
   while.true
-    push.1 add dup                     # [j++, j++, ...]
-    exec.getNodePointer                # [node_pointer, j++, ...] 
-    dup exec.getChangeFlag             # [node_change_flag, node_pointer, j++, ...]
+    push.1 add                         # [j++, node_count, ...]
+    dup exec.getNodePointer            # [node_pointer, j++, node_count, ...]
+    dup exec.getChangeFlag             # [node_change_flag, node_pointer, j++, node_count, ...]
     if.true 
-      exec.LoadNode drop               # [node_coordinate_word, node_order_word, j++, ...]
-      movup.8 dup                      # [j++, j++, node_coordinate_word, node_order_word, ...]
-      exec.getNodePointer swap         # [j++, node_pointer, node_coordinate_word, node_order_word, ...]
+      exec.LoadNode drop               # [node_coordinate_word, node_order_word, j++, node_count, ...]
+      exec.ToBookCoordinates           # [node_coordinate_word, node_order_word, j++, node_count, ...]
+      movup.9 push.1 add movup.9       # [j++, node_count++, node_coordinate_word, node_order_word, ...]
+      dup push.3 mul push.1 add movdn.2  # [j++, node_count++, node_pointer, node_coordinate_word, node_order_word, ...]
     else 
-      drop
+      drop                             # [j++, node_count, ...]
     end                
-    dup mem_load.0 lt                  # [moreNodes_bool, j++, node_pointer, node_coordinate_word, node_order_word, ...]
+    dup exec.getCreationCounter lt     # [moreNodes_bool, j++, node_count, ...]
+  end
+  drop
+end
+
+proc.PrintSlotChangeLog
+  # Inputs: [...]
+  # Output: [slot_count, N, account_word_N, link_word_N, order_word_N, ..., 1, account_word_1, link_word_1, order_word_1, ... ]
+  #
+  # Comments: Prints all queued order data only for slots whose change flag has changed
+  #
+  push.0 push.0                        # Initialize counter and slot count      [j=0, slot_count=0, ...]
+  dup push.0 exec.getSlotPointer mem_load lt
+  while.true
+    push.1 add                         # [j++, slot_count, ...]
+    dup exec.getSlotChangeFlag         # [slot_change_flag, j++, slot_count, ...]
+    if.true
+      dup exec.LoadSlot                # [link_word, order_word, j++, slot_count, ...]
+      dup.8 exec.getSlotAccount        # [account_word, link_word, order_word, j++, slot_count, ...]
+      movup.13 push.1 add movup.13     # [j++, slot_count++, account_word, link_word, order_word, ...]
+      dup movdn.2                      # [j++, slot_count++, slot, account_word, link_word, order_word, ...]
+    end
+    dup push.0 exec.getSlotPointer mem_load lt  # [moreSlots_bool, j++, slot_count, ...]
+  end
+  drop
+end
+
+proc.SaveOrderReport
+  # Inputs:  [report_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves report word [ID, status, price, resting_quantity] at a fresh report pointer.
+  #
+  push.0 exec.getReportPointer mem_load push.1 add  # Increment report counter   [report_number++, report_word, ...]
+  dup push.0 exec.getReportPointer mem_store        # Save new report counter    [report_number++, report_word, ...]
+  exec.getReportPointer mem_storew dropw            # Save in RAM                [...]
+end
+
+proc.PrintOrderReports
+  # Inputs:  [...]
+  # Output:  [report_count, report_word_1, ..., report_word_N, ...]
+  #
+  # Comments: Prints the report of every incoming order, first order closest to the top.
+  #           Report Word Format: [ID, status, price, resting_quantity]
+  #
+  push.0 exec.getReportPointer mem_load dup  # Load report counter          [j=report_count, report_count, ...]
+  dup push.0 neq                       # Are there any reports?             [hasReports_bool, j, report_count, ...]
+  while.true
+    dup exec.getReportPointer          # Derive report pointer              [report_pointer, j, report_count, ...]
+    push.0.0.0.0 movup.4 mem_loadw     # Load report word                   [report_word_j, j, report_count, ...]
+    movup.4 movup.5 swap               # Counters back on top               [j, report_count, report_word_j, ...]
+    push.1 sub                         # Decrement counter                  [j--, report_count, report_word_j, ...]
+    dup push.0 neq                     # Are there more reports?            [moreReports_bool, j--, report_count, ...]
+  end
+  drop
+end
+
+proc.SaveQuote
+  # Inputs:  [quote_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves quote word at a fresh quote pointer.
+  #
+  push.0 exec.getQuotePointer mem_load push.1 add  # Increment quote counter     [quote_number++, quote_word, ...]
+  dup push.0 exec.getQuotePointer mem_store        # Save new quote counter      [quote_number++, quote_word, ...]
+  exec.getQuotePointer mem_storew dropw            # Save in RAM                 [...]
+end
+
+proc.PrintQuotes
+  # Inputs:  [...]
+  # Output:  [quote_count, quote_word_1, ..., quote_word_N, ...]
+  #
+  # Comments: Prints the answer of a query, first quote closest to the top. None outside query mode
+  #
+  push.0 exec.getQuotePointer mem_load dup  # Load quote counter             [j=quote_count, quote_count, ...]
+  dup push.0 neq                       # Are there any quotes?              [hasQuotes_bool, j, quote_count, ...]
+  while.true
+    dup exec.getQuotePointer           # Derive quote pointer               [quote_pointer, j, quote_count, ...]
+    push.0.0.0.0 movup.4 mem_loadw     # Load quote word                    [quote_word_j, j, quote_count, ...]
+    movup.4 movup.5 swap               # Counters back on top               [j, quote_count, quote_word_j, ...]
+    push.1 sub                         # Decrement counter                  [j--, quote_count, quote_word_j, ...]
+    dup push.0 neq                     # Are there more quotes?             [moreQuotes_bool, j--, quote_count, ...]
   end
   drop
 end
 
+proc.getFeeRates
+  # Inputs:  [notional, ...]
+  # Outputs: [maker_bps, taker_bps, ...]
+  #
+  # Comments: Rates of the last fee tier whose min_notional is at or below notional.
+  #           No fees below the first tier
+  #
+  push.0.0 push.0                      # No fees, first tier                [i=0, maker_bps, taker_bps, notional, ...]
+  dup push.0 exec.getFeeTierPointer mem_load lt
+  while.true
+    push.1 add                         # Next tier                          [i++, maker_bps, taker_bps, notional, ...]
+    dup exec.getFeeTierPointer push.0.0.0.0 movup.4 mem_loadw
+    dup dup.8 lte                      # Is min_notional <= notional?       [isReached_bool, min_notional, tier_maker_bps, tier_taker_bps, 0, i++, ...]
+    if.true
+      drop movup.4 drop movup.4 drop   # Take tier rates                    [tier_maker_bps, tier_taker_bps, 0, i++, notional, ...]
+      movup.2 drop movup.2             #                                    [i++, maker_bps, taker_bps, notional, ...]
+    else
+      dropw                            #                                    [i++, maker_bps, taker_bps, notional, ...]
+    end
+    dup push.0 exec.getFeeTierPointer mem_load lt
+  end
+  drop movup.2 drop
+end
+
+proc.PrintFills
+  # Inputs:  [...]
+  # Output:  [maker_fee_total, taker_fee_total, fill_count, fill_1, ..., fill_N, ...]
+  #
+  # Comments: Prints every fill recorded by RecordFill with its fees, first fill closest to the top.
+  #           Fill Format: [quantity, price, maker_ID, taker_ID, maker_fee, taker_fee]
+  #           Fees are notional * bps of the fee tier of the fill, in 1/10000 of the quote unit
+  #
+  push.0.0                             # Initialize fee totals              [maker_fee_total, taker_fee_total, ...]
+  mem_load.2147483648 dup              # Load fill counter                  [j=fill_count, fill_count, maker_fee_total, taker_fee_total, ...]
+  dup push.0 neq                       # Are there any fills?               [hasFills_bool, j, fill_count, ...]
+  while.true
+    dup exec.getFillPointer            # Derive fill pointer                [fill_pointer, j, fill_count, ...]
+    push.0.0.0.0 movup.4 mem_loadw     # Load fill word                     [fill_word_j, j, fill_count, maker_fee_total, taker_fee_total, ...]
+    dup.1 dup.1 mul                    # Fill notional                      [notional, fill_word_j, ...]
+    dup exec.getFeeRates               # Fee tier of fill                   [maker_bps, taker_bps, notional, fill_word_j, ...]
+    dup.2 mul swap movup.2 mul         # Fees                               [taker_fee, maker_fee, fill_word_j, j, fill_count, maker_fee_total, taker_fee_total, ...]
+    dup movup.10 add movdn.9           # Add taker fee to total             [taker_fee, maker_fee, fill_word_j, j, fill_count, maker_fee_total, taker_fee_total', ...]
+    dup.1 movup.9 add movdn.8          # Add maker fee to total             [taker_fee, maker_fee, fill_word_j, j, fill_count, maker_fee_total', taker_fee_total', ...]
+    movdn.5 movdn.4                    # Build fill                         [fill_j, j, fill_count, maker_fee_total, taker_fee_total, ...]
+    movup.9 movup.9 movup.9 movup.9    # Counters back on top               [j, fill_count, maker_fee_total, taker_fee_total, fill_j, ...]
+    push.1 sub                         # Decrement counter                  [j--, fill_count, ...]
+    dup push.0 neq                     # Are there more fills?              [moreFills_bool, j--, fill_count, ...]
+  end
+  drop movdn.2                         #                                    [maker_fee_total, taker_fee_total, fill_count, ...]
+end
+
 proc.CompareOrders
   # Input:   [Order_word_A, Order_Word_B, ...]
   # Outputs: [compare_bool, ...]
@@ -321,8 +840,18 @@ proc.HasChildren
   end
 end
 
+proc.getInitPointer
+  # Inputs:  [...]
+  # Outputs: [init_pointer, ...]
+  #
+  # Comments: Pointer to the init node of the side selected by SelectBids or SelectAsks,
+  #           kept at address 2147483644. Tree procedures work on the selected side
+  #
+  mem_load.2147483644
+end
+
 proc.getRootPointer 
-  push.0 exec.getNodePointer exec.getCoordinates exec.getChildrenFromCoordinates
+  exec.getInitPointer exec.getCoordinates exec.getChildrenFromCoordinates
   dup push.0 eq 
   if.true 
     drop 
@@ -1158,7 +1687,7 @@ proc.DeleteNode
   and                          # Node is Root and No Children            [isNodeRootandNoChildren_bool, node_pointer, ...]
   if.true
     dup exec.setToNIL dup      # Zero out fields                         [node_pointer, node_pointer, ...]
-    push.0 exec.getNodePointer # Get InitTree pointer                    [initTree_pointer, node_pointer, node_pointer, ...]
+    exec.getInitPointer        # Get InitTree pointer                    [initTree_pointer, node_pointer, node_pointer, ...]
     exec.UpdateChildInParent   # Update child reference in initTree node [node_pointer, ...]
     push.0 swap                # Set recursion flag                      [node_pointer, 0, ...]
     push.0                     # Skip next cases                         [0, node_pointer, 0, ...]
@@ -1389,7 +1918,7 @@ proc.NewCreation
   #          Comments: increments creation number and return fresh node pointer
   #
   #### CHECK IF TREE NEEDS TO BE INITIALIZED  ####
-  mem_load.0           # Load current creation number state                  [creation_number ...]
+  exec.getCreationCounter  # Load current creation number state              [creation_number ...]
   dup push.0 eq        # Check if this is the first node of the tree         [isFirstNode_bool, creation_number ...]
 
   if.true
@@ -1398,30 +1927,109 @@ proc.NewCreation
 
   #### GET CREATION NUMBER FOR NEW NODE ####
   push.1 add               # Increment creation number                       [creation_number++, ...]
-  dup mem_store.0          # Save New Creation number                        [creation_number++, ...]
+  dup exec.SaveCreationCounter  # Save New Creation number                   [creation_number++, ...]
   dup exec.getNodePointer  # Derive node pointer                             [newNode_pointer, creation_number++, ...]
 end
 
-proc.CreateNode
-  # Inputs:  [order_wordA, ...]
+proc.SelectBids
+  # Inputs:  [...]
   # Outputs: [...]
-  #            Coordinates> {{color, Parent_pointer, L_child_pointer, R_child_pointer}} saved as word 
-  #            at fresh node_pointer. Node_pointer computed by using getNodePointer.
-  #            Order Info>  {{order_word}} saved at node_pointer+1
-  #            [creation_number] -> [creation_number+1] in mem_store.0
   #
+  # Comments: Points tree procedures to the BUY side, whose init node is node 0
+  #
+  push.0 exec.getNodePointer mem_store.2147483644
+end
 
-  exec.NewCreation                     # Fresh pointer and creation number       [node_pointer, creation_number++, order_word, ...]
-  loc_store.1                          # Save node_pointer in registry           [creation_number++, order_word, ...]
+proc.SelectBook
+  # Inputs:  [book_number, ...]
+  # Outputs: [...]
+  #
+  # Comments: Points node, slot and creation number procedures to the book book_number.
+  #           Select a side afterwards
+  #
+  mem_store.2147483636
+end
 
-  ### GET PARENT ###
-  push.1 eq                            # Is this the first node of the tree      [isFirstNode_bool, order_word, ...]
-  if.true
-    push.0 exec.getNodePointer         # Get tree init addres                    [tree_init_pointer, order_word]
-  else
-    exec.FindParent                    # Find suitable parent in tree            [parent_pointer, order_word]
-  end
-  dup loc_store.2                      # Save Parent_pointer in registry         [parent_pointer, order_word, ...]
+proc.SelectInstrument
+  # Inputs:  [instrument_ID, ...]
+  # Outputs: [...]
+  #
+  # Comments: Selects the book of instrument_ID, listed by LoadBooks, and its BUY side.
+  #           Fails for an instrument without book
+  #
+  push.0                               # Start from first book                   [book_number=0, instrument_ID, ...]
+  dup push.2113929216 add mem_load     # Load instrument of book                 [book_instrument_ID, book_number, instrument_ID, ...]
+  dup.2 neq
+  while.true
+    push.1 add                         # Next book                               [book_number++, instrument_ID, ...]
+    dup mem_load.2147483635 lt assert  # Instrument must have a book             [book_number++, instrument_ID, ...]
+    dup push.2113929216 add mem_load
+    dup.2 neq                          # Is it another instrument?               [isOther_bool, book_number++, instrument_ID, ...]
+  end
+  exec.SelectBook drop                 #                                         [...]
+  exec.SelectBids
+end
+
+proc.getInitCachePointer
+  # Inputs:  [side_ID, ...]
+  # Outputs: [init_cache_pointer, ...]
+  # init_cache_pointer = book_number * 8 + side_ID + 2013265920
+  #
+  # Comments: Every book caches the init pointers of its own sides
+  #
+  push.2013265920 add exec.getBookNumber push.8 mul add
+end
+
+proc.SelectSide
+  # Inputs:  [side_ID, ...]
+  # Outputs: [...]
+  #
+  # Comments: Points tree procedures to the tree whose init node has ID side_ID, creating the
+  #           init node on first use. Init nodes have color 2 like node 0 and are told apart by ID:
+  #           '2' SELL side, '3' buy stops, '4' sell stops.
+  #           Init pointer of side_ID is kept at getInitCachePointer once loaded
+  #
+  dup exec.getInitCachePointer mem_load  # Load init pointer                     [init_pointer, side_ID, ...]
+  dup push.0 eq
+  if.true
+    drop dup exec.NewCreation swap drop  # Fresh pointer                         [init_pointer, side_ID, side_ID, ...]
+    swap push.0.0.0                    # Init order                              [0, 0, 0, side_ID, init_pointer, side_ID, ...]
+    push.0.0.0.2                       # Init coordinates                        [2, 0, 0, 0, 0, 0, 0, side_ID, init_pointer, side_ID, ...]
+    dup.8 exec.UpdateNode              # Save new init node                      [init_pointer, side_ID, ...]
+    dup dup.2 exec.getInitCachePointer mem_store  # Save init pointer            [init_pointer, side_ID, ...]
+  end
+  mem_store.2147483644 drop
+end
+
+proc.SelectAsks
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Points tree procedures to the SELL side
+  #
+  push.2 exec.SelectSide
+end
+
+proc.CreateNode
+  # Inputs:  [order_wordA, ...]
+  # Outputs: [...]
+  #            Coordinates> {{color, Parent_pointer, L_child_pointer, R_child_pointer}} saved as word 
+  #            at fresh node_pointer. Node_pointer computed by using getNodePointer.
+  #            Order Info>  {{order_word}} saved at node_pointer+1
+  #            [creation_number] -> [creation_number+1] at the book_base
+  #
+
+  exec.NewCreation                     # Fresh pointer and creation number       [node_pointer, creation_number++, order_word, ...]
+  loc_store.1                          # Save node_pointer in registry           [creation_number++, order_word, ...]
+
+  ### GET PARENT ###
+  drop exec.getRootPointer push.0 eq   # Is the tree empty                       [isTreeEmpty_bool, order_word, ...]
+  if.true
+    exec.getInitPointer                # Get tree init addres                    [tree_init_pointer, order_word]
+  else
+    exec.FindParent                    # Find suitable parent in tree            [parent_pointer, order_word]
+  end
+  dup loc_store.2                      # Save Parent_pointer in registry         [parent_pointer, order_word, ...]
 
   #### BUILD NODE COORDINATES ####
   push.0.0                             # Initialize Left and Right child pointers to NIL value      [0, 0, parent_pointer, order_word, ...]
@@ -1475,158 +2083,2530 @@ proc.getLeftMinimumSubtreeElement
   drop
 end
 
-proc.FillOrder
-  # Inputs:  [ask_pointer, bid_quantity, bid_price...]
-  # Outputs: [continue_bool, ask_pointer, remaining_bid_quantity, bid_price...]
+proc.getInOrderPredecessor
+  # Inputs:  [node_pointer, ...]
+  # Outputs: [predecessor_pointer, ...]
   #
-  # Comments: Fills order at order pointer.           
+  # Comments: Returns the next lower order in the tree, or 0 if node holds the minimum order
   #
-  dup exec.getOrder      # Load order                      [ask_quantity, ask_price, _____, ____, ask_pointer, bid_quantity, bid_price...]
-  dup.1 dup.7 gt         # Is ask price > bid price?       [isAskGreaterThanBid_bool, ask_quantity, ask_price, _____, ____, ask_pointer, bid_quantity, bid_price, ...]
-  if.true  
-    dup dup.6            # Duplicate quantities on top     [bid_quantity, ask_quantity, ask_quantity, ask_price, _____, ____, ask_pointer, bid_quantity, bid_price, ...]
-    dup.1 dup.1 lte      # Is ask quantity < bid quantity? [isAskLessThanBid, bid_quantity, ask_quantity, ask_quantity, ask_price, _____, ____, ask_pointer, bid_quantity, bid_price, ...] 
-    if.true 
-      #
-      # Ask is less than the bid. Execute entire ask order and continue
-      #
-      swap sub           # How much has been filled      [(bid_quantity-ask_quantity), ask_quantity, ask_price, _____, ____, ask_pointer, bid_quantity, bid_price, ...]
-      swap.6 drop        # Update how much left to fill  [ask_quantity, ask_price, _____, ____, ask_pointer, (bid_quantity-ask_quantity), bid_price, ...]
-      dropw              # Drop order word               [ask_pointer, (bid_quantity-ask_quantity), bid_price, ...]
-      push.1             # Continue filling              [1, ask_pointer, (bid_quantity-ask_quantity), bid_price, ...]       
-    else 
-      #
-      # Ask is greater than the bid. Execute at this price and finish
-      #
-      sub swap drop              # Update to ask quantity        [(ask_quantity-bid_quantity), ask_price, _____, ____, ask_pointer, bid_quantity, bid_price, ...]
-      dup.4 exec.UpdateOrder  # Update order word             [ask_pointer, bid_quantity, bid_price, ...]
-      push.0 swap.2 drop      # Update remaining amount       [ask_pointer, 0, bid_price, ...]
-      push.0                  # Stop filling                  [0, ask_pointer, 0, bid_price, ...]
+  dup exec.hasLeftChild              # Is there a left child?                 [hasLeftChild_bool, Lchild_pointer, node_pointer, ...]
+  if.true
+    swap drop                        # Predecessor is max of left subtree     [Lchild_pointer, ...]
+    exec.getRightMaximumSubtreeElement
+  else
+    drop                             # Climb while node is a left child       [node_pointer, ...]
+    dup exec.isLeftChild
+    while.true
+      exec.getParentPointer
+      dup exec.isLeftChild
+    end
+    dup exec.isRoot                  # Root reached from the left: no pred.   [isRoot_bool, node_pointer, ...]
+    if.true
+      drop push.0
+    else
+      exec.getParentPointer          # Predecessor is parent of right child   [parent_pointer, ...]
     end
-  else 
-     dropw push.0            # No order to fill              [0, ask_pointer, bid_quantity, bid_price, ...]
   end
 end
 
-proc.MatchOrder
-  # Inputs:  [order_word, ...]
-  # Outputs: [...]
+proc.getBestBidPointer
+  # Inputs:  [...]
+  # Outputs: [max_pointer, ...]
   #
-  # Comments: Matches order. Order Flag must be 0 for sell order, 1 for buy order            
+  # Comments: Returns pointer to the max order in the tree, or 0 if tree is empty
   #
+  exec.getRootPointer
+  dup push.0 neq
+  if.true
+    exec.getRightMaximumSubtreeElement
+  end
+end
 
-  ### SAVE Order Temporarily at proper pointer ####
-  dupw                                                      # Duplicate order word                [order_word, order_word, ...]
-  exec.NewCreation                                          # Fresh pointer and creation number   [node_pointer, creation_number++, order_word, order_word, ...]
-  swap mem_store.0                                          # Save creation number                [node_pointer, order_word, order_word, ...]
-  exec.SaveOrder                                            # Save new order                      [order_word, ...]
+proc.isBidAtOrAbovePrice
+  # Inputs:  [bid_pointer, price, ...]
+  # Outputs: [isBidAtOrAbovePrice_bool, ...]
+  #
+  # Comments: False for a NIL bid pointer
+  #
+  dup push.0 neq                     # Is bid not NIL?                        [isNotNIL_bool, bid_pointer, price, ...]
+  swap exec.getOrder drop            # Load bid                               [bid_price, bid_time, bid_ID, isNotNIL_bool, price, ...]
+  movdn.2 drop drop                  # Isolate bid price                      [bid_price, isNotNIL_bool, price, ...]
+  movup.2 gte and                    # Compare to price                       [isBidAtOrAbovePrice_bool, ...]
+end
 
-  loc_store.0 loc_store.1 drop drop                          # Save quantity (loc0) and price (loc1)  [...]
-  loc_load.1 loc_load.0                                      # Quantity and Price on stack [quantity, price, ...] 
-  exec.getRootPointer exec.getRightMaximumSubtreeElement     # Load pointer to max price   [maxRight_pointer, quantity, price, ...]
-  push.1                                                     # Enter loop                  [1, maxRight_pointer, quantity, price, ...]
-  while.true 
-    exec.FillOrder                                           # Fill order                  [continuation_bool, maxRight_pointer, remaining_quantity, price, ...]
-    if.true 
-      dup exec.getCoordinates                                # Get ask order coordiantes   [___, ask_parent_pointer, ask_Lchild_pointer, ___, maxRight_pointer, quantity, price, ...]
-      drop swap dup push.0 eq                                # Is Left child NIL           [isLeftChildNIL_bool, ask_Lchild_pointer, ask_parent_pointer, ___, maxRight_pointer, quantity, price, ...]
-      if.true 
-        drop swap drop swap                                  # Isolate parent              [maxRight_pointer, ask_parent_pointer, quantity, price, ...]  
-      else 
-        swap drop swap drop swap                             # Isolate Left child          [maxRight_pointer, ask_Lchild_pointer, quantity, price, ...]  
-      end
-      exec.DestroyNode                                       # Destroy Filled order        [next_ask_pointer, quantity, price, ...]
-      push.1                                                 # Continue filling            [1, next_ask_pointer, quantity, price, ...]
-    else 
-     drop drop drop push.0                                   # Finished filling            [0, ...]
-     mem_load.0 exec.getNodePointer exec.ZeroOut         # Destroy Bid Order           [0, ...]
-     push.0
+proc.getInOrderSuccessor
+  # Inputs:  [node_pointer, ...]
+  # Outputs: [successor_pointer, ...]
+  #
+  # Comments: Returns the next higher order in the tree, or 0 if node holds the maximum order
+  #
+  dup exec.getCoordinates            # Is there a right child?                [node_coordinate_word, node_pointer, ...]
+  exec.getChildrenFromCoordinates swap drop
+  dup push.0 neq                     #                                        [hasRightChild_bool, Rchild_pointer, node_pointer, ...]
+  if.true
+    swap drop                        # Successor is min of right subtree      [Rchild_pointer, ...]
+    exec.getLeftMinimumSubtreeElement
+  else
+    drop                             # Climb while node is a right child      [node_pointer, ...]
+    dup exec.isLeftChild not dup.1 exec.isRoot not and
+    while.true
+      exec.getParentPointer
+      dup exec.isLeftChild not dup.1 exec.isRoot not and
+    end
+    dup exec.isRoot                  # Root reached from the right: no succ.  [isRoot_bool, node_pointer, ...]
+    if.true
+      drop push.0
+    else
+      exec.getParentPointer          # Successor is parent of left child      [parent_pointer, ...]
     end
   end
 end
 
-proc.TempLoadOrders
+proc.getBestAskPointer
   # Inputs:  [...]
-  # Outputs: [...]
+  # Outputs: [min_pointer, ...]
   #
-  #    Comments: Loads orders and creates rbBST 
+  # Comments: Returns pointer to the min order in the tree, or 0 if tree is empty
   #
-  push.0.0.0.0
-  adv.push_mapval adv_loadw           # Load Total number of orders in advice stack    [number_of_orders, ...]
-  loc_store.0                         # Save number of orders in registry position 0   [...]
-  push.0 dup                          # Initialize counter i                           [0, 0, ...]
-  loc_load.1 loc_load.0 lt 
-  while.true
-    push.1 add                        # Increment counter and duplicate              [order_counter++, ...]
-    push.0.0.0 dup.3                  # Add buffer for loading next word             [0, 0, 0, 0, order_counter++, ...]
-    adv.push_mapval adv_loadw         # Load new order                               [ID, time, price, quantity, order_counter++, ...]
-    movdn.3 movdn.2 swap              # Put into correct format                      [quantity, price, time, ID, order_counter++, ...]
-    exec.CreateNode                   # Create Node                                  [order_counter++, ...]
-    dup loc_load.0 lt                 # Aare there more nodes?                       [more_nodes_bool, order_counter++, ...]
+  exec.getRootPointer
+  dup push.0 neq
+  if.true
+    exec.getLeftMinimumSubtreeElement
   end
-  drop
 end
 
-proc.LoadTree
+proc.isLevelAtOrBelowPrice
+  # Inputs:  [level_pointer, price, ...]
+  # Outputs: [isLevelAtOrBelowPrice_bool, ...]
+  #
+  # Comments: False for a NIL level pointer
+  #
+  dup push.0 neq                     # Is level not NIL?                      [isNotNIL_bool, level_pointer, price, ...]
+  swap exec.getOrder drop            # Load level                             [level_price, head_slot, tail_slot, isNotNIL_bool, price, ...]
+  movdn.2 drop drop                  # Isolate level price                    [level_price, isNotNIL_bool, price, ...]
+  movup.2 lte and                    # Compare to price                       [isLevelAtOrBelowPrice_bool, ...]
+end
+
+proc.getBestOppositePointer
   # Inputs:  [...]
-  # Outputs: [...]
+  # Outputs: [level_pointer, ...]
   #
-  #    Comments: Loads pre-structured tree nodes from advice stack            
-  #              Coordinate Word Format: {{color, Parent_pointer, L_child_pointer, R_child_pointer}} 
-  #              Order Word Format:      {{quantity, price, time, ID}} 
+  # Comments: Best level the incoming order can match against: min ask for a buy order,
+  #           max bid for a sell order. The opposite side must be selected
   #
-  push.0.0.0.0
-  adv.push_mapval adv_loadw            # Load Total number of orders in advice stack    [number_of_orders, ...]
-  mem_store.0 drop drop drop           # Save number of orders as final creation number [...]
-  exec.InitTree                       # Initialize Tree                                [...]
-  push.0 dup                          # Initialize counter in registry position 1      [0, 0, ...]
-  loc_load.1 mem_load.0 lt 
+  exec.isIncomingBuy
+  if.true
+    exec.getBestAskPointer
+  else
+    exec.getBestBidPointer
+  end
+end
+
+proc.getNextOppositeLevel
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [next_level_pointer, ...]
+  #
+  # Comments: Next worse level for the incoming order, or 0 once the side is exhausted
+  #
+  exec.isIncomingBuy
+  if.true
+    exec.getInOrderSuccessor
+  else
+    exec.getInOrderPredecessor
+  end
+end
+
+proc.isLevelCrossingPrice
+  # Inputs:  [level_pointer, price, ...]
+  # Outputs: [isCrossing_bool, ...]
+  #
+  # Comments: A buy order at price crosses asks at or below price,
+  #           a sell order at price crosses bids at or above price
+  #
+  exec.isIncomingBuy
+  if.true
+    exec.isLevelAtOrBelowPrice
+  else
+    exec.isBidAtOrAbovePrice
+  end
+end
+
+proc.FindLevel
+  # Inputs:  [price, ...]
+  # Outputs: [level_pointer, ...]
+  #
+  # Comments: Returns pointer to the price level at price, or 0 if there is none
+  #
+  exec.getRootPointer                # Start from tree root                   [node_pointer, price, ...]
+  dup push.0 neq                     # Is tree not empty?                     [continue_bool, node_pointer, price, ...]
   while.true
-    push.1 add                        # Increment counter                            [order_counter++, ...] 
-    push.0.0.0 dup.3                  # Add buffer for loading next word             [0, 0, 0, 0, order_counter++, ...]
-    adv.push_mapval adv_loadw         # Load Node pointer                            [node_pointer, 0, 0, 0, order_counter++, ...]
-    push.0.0.0.0 adv_loadw            # Load Node coordinates                        [Rchild_pointer, Lchild_pointer, Parent_pointer, color, node_pointer, 0, 0, 0, order_counter++, ...]
-    movdn.3 movdn.2 swap              # Put into correct format                      [node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
-    push.0.0.0.0 adv_loadw            # Load Node order                              [ID, time, price, quantity, node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
-    movdn.3 movdn.2 swap              # Put into correct format                      [node_order_word, node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
-    swapw                             # Coordinates in front                         [node_coordinate_word, node_order_word, node_pointer, 0, 0, 0, order_counter++, ...]
-    movupw.2 movdn.7 drop drop drop   # Isolate node pointer                         [node_coordinate_word, node_pointer, node_order_word, order_counter++, ...]
-    dup.4                             # Copy node pointer top of stack               [node_pointer, node_coordinate_word, node_pointer, node_order_word, order_counter++, ...]
-    exec.SaveCoordinate               # Save Node coordiante word                    [node_pointer, node_order_word, order_counter++, ...]
-    exec.SaveOrder                    # Save Node order word                         [order_counter++, ...]
-    dup mem_load.0 lt                 # Aare there more nodes?                      [more_nodes_bool, order_counter++, ...]            
+    dup exec.getOrder drop           # Load level                             [level_price, head_slot, tail_slot, node_pointer, price, ...]
+    movdn.2 drop drop                # Isolate level price                    [level_price, node_pointer, price, ...]
+    dup dup.3 eq                     # Is level at price?                     [isLevelFound_bool, level_price, node_pointer, price, ...]
+    if.true
+      drop push.0                    # Level found. Stop searching            [0, node_pointer, price, ...]
+    else
+      dup.2 gt                       # Go left if level price > price         [isLeft_bool, node_pointer, price, ...]
+      swap exec.getCoordinates exec.getChildrenFromCoordinates
+      movup.2                        # [isLeft_bool, Lchild_pointer, Rchild_pointer, price, ...]
+      if.true
+        swap drop                    # Isolate Left child                     [Lchild_pointer, price, ...]
+      else
+        drop                         # Isolate Right child                    [Rchild_pointer, price, ...]
+      end
+      dup push.0 neq                 # Does child exist?                      [continue_bool, child_pointer, price, ...]
+    end
   end
-  drop drop
+  swap drop
 end
 
-begin
-  # Operand Stack format: [orderID, time, price, quantity, buy-sell flag]
-  # Advice stack format: 
-  #            0000000000000000000000000000000000000000000000000000000000000000 : [0,0,0, number_of_orders_in_book],
-  #            0000000000000000000000000000000000000000000000000100000000000000 : [0, 0, 0, node_pointer,  color,  parent_pointer, Lchild_pointer, Rchild_pointer, quantity, price, time, ID], 
+proc.InsertOrderAtLevel
+  # Inputs:  [level_price, expiry, reserve, peak, order_word, ...]
+  # Outputs: [...]
   #
-  # Comments: Buy-sell_flag should be '0' for a sell order, '1' for buy order. Orderbook is assumed to be the BUY side. 
-  #           All buy orders will be immediately inserted.
+  # Comments: Queues order at the tail of the price level at level_price, creating the level
+  #           Level Word Format: [level_quantity, price, head_slot, tail_slot]
+  #           Expiry 0 rests the order until it is filled. Order quantity is the displayed
+  #           quantity, reserve the hidden quantity refilled by slices of peak.
+  #           Queued order belongs to the account of the incoming order
   #
-  # ORDER INSERTION EXAMPLE
+  movdn.7                              # Level price behind order                [expiry, reserve, peak, order_word, level_price, ...]
+  exec.NewSlot dup movdn.8             # Fresh slot                              [slot, expiry, reserve, peak, order_word, slot, level_price, ...]
+  push.0 swap                          # Build link word                         [slot, 0, expiry, reserve, peak, order_word, slot, level_price, ...]
+  exec.SaveSlotLink                    # Save expiry and reserve                 [order_word, slot, level_price, ...]
+  dup.4 exec.UpdateSlotOrder           # Save queued order                       [slot, level_price, ...]
+  exec.getIncomingAccount dup.4 exec.SaveSlotAccount  # Save account             [slot, level_price, ...]
+  dup exec.getSlotOrder                # Reload order                            [quantity, price, time, ID, slot, level_price, ...]
+  movdn.3 drop drop drop               # Isolate quantity                        [quantity, slot, level_price, ...]
+  movup.2 swap                         #                                         [quantity, level_price, slot, ...]
+  dup.1 exec.FindLevel                 # Find price level                        [level_pointer, quantity, price, slot, ...]
+  dup push.0 neq
+  if.true
+    #
+    # Level exists. Append order to its queue
+    #
+    dup exec.getOrder                  # Load level                              [level_quantity, price, head_slot, tail_slot, level_pointer, quantity, price, slot, ...]
+    movup.3 dup.7 swap                 # Link slot behind tail                   [tail_slot, slot, level_quantity, price, head_slot, level_pointer, quantity, price, slot, ...]
+    exec.SetNextSlot                   #                                         [level_quantity, price, head_slot, level_pointer, quantity, price, slot, ...]
+    movup.4 add                        # Add quantity to level                   [level_quantity', price, head_slot, level_pointer, price, slot, ...]
+    movup.5 movdn.3                    # Slot is new tail                        [level_word', level_pointer, price, ...]
+    movup.4 exec.UpdateOrder drop      # Save level                              [...]
+  else
+    #
+    # New price level holding a single order
+    #
+    drop dup.2 movdn.2                 # Build level word                        [quantity, price, slot, slot, ...]
+    exec.CreateNode                    # Insert level in tree                    [...]
+  end
+end
+
+proc.InsertOrder
+  # Inputs:  [expiry, reserve, peak, order_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: Queues order at the tail of the price level at its price
+  #
+  dup.4 exec.InsertOrderAtLevel
+end
+
+proc.getLevelHeadSlot
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [head_slot, ...]
+  #
+  exec.getOrder drop drop swap drop    # Isolate head slot                      [head_slot, ...]
+end
+
+proc.getLevelHeadOrder
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [quantity, price, time, orderID, ...]
+  #
+  # Comments: Loads the first order queued at the price level
   #
-  # Load pre-structured tree from advice_stack
-  exec.LoadTree
+  exec.getLevelHeadSlot exec.getSlotOrder
+end
 
-  #### Check buy-sell flag
+proc.RefillSlot
+  # Inputs:  [slot, ...]
+  # Outputs: [displayed_quantity, ...]
+  #
+  # Comments: Displays the next slice of an iceberg order from its reserve, with the time of
+  #           the incoming order as new time priority. Slot is unlinked from its queue
+  #
+  dup exec.getSlotPointer push.1 add
+  push.0.0.0.0 movup.4 mem_loadw       # Load link word                         [next_slot, expiry, reserve, peak, slot, ...]
+  drop dup.2 dup.2 lt                  # Is peak < reserve?                     [isPeakSmaller_bool, expiry, reserve, peak, slot, ...]
   if.true
-    # Order is but order
-    # Create new node in tree by inserting new order from operand stack 
-    exec.CreateNode  
-  else 
-    # Order is a sell order
-    # Search tree for matching orders
-    # and execute 
-    exec.MatchOrder
+    dup.2                              # Display a full peak                    [displayed_quantity, expiry, reserve, peak, slot, ...]
+  else
+    dup.1                              # Display what is left                   [displayed_quantity, expiry, reserve, peak, slot, ...]
   end
+  movup.2 dup.1 sub                    # Update reserve                         [reserve', displayed_quantity, expiry, peak, slot, ...]
+  swap movdn.4 swap push.0             # Build link word                        [0, expiry, reserve', peak, slot, displayed_quantity, ...]
+  dup.4 exec.SaveSlotLink              # Save link                              [slot, displayed_quantity, ...]
+  dup exec.getSlotOrder drop           # Load order                             [price, time, ID, slot, displayed_quantity, ...]
+  swap drop                            #                                        [price, ID, slot, displayed_quantity, ...]
+  exec.getIncomingOrder drop drop swap drop
+  swap dup.4                           # New slice                              [displayed_quantity, price, time', ID, slot, displayed_quantity, ...]
+  movup.4 exec.UpdateSlotOrder         # Save order                             [displayed_quantity, ...]
+end
 
-  # Output all the nodes that have to be updated as a result of the new order inserted
-  exec.PrintChangeLog
+proc.FillLevelHead
+  # Inputs:  [fill_quantity, level_pointer, ...]
+  # Outputs: [level_exhausted_bool, ...]
+  #
+  # Comments: Fills the first order queued at the price level, dequeuing it once filled.
+  #           A filled iceberg order with reserve left is requeued at the tail with a new slice.
+  #           Price level is destroyed once its quantity is filled.
+  #
+  dup.1 exec.getOrder                  # Load level                             [level_quantity, price, head_slot, tail_slot, fill_quantity, level_pointer, ...]
+  dup.4 sub                            # Update level quantity                  [level_quantity', price, head_slot, tail_slot, fill_quantity, level_pointer, ...]
+  dup.2 exec.getSlotOrder              # Load head order                        [head_order_word, level_word', fill_quantity, level_pointer, ...]
+  movup.8 sub                          # Update head quantity                   [head_order_word', level_word', level_pointer, ...]
+  dup push.0 eq
+  if.true
+    #
+    # Head order filled. Dequeue it
+    #
+    dropw                              #                                        [level_quantity', price, head_slot, tail_slot, level_pointer, ...]
+    dup.2 exec.getNextSlot             # Next order is new head                 [next_slot, level_quantity', price, head_slot, tail_slot, level_pointer, ...]
+    movup.3 dup exec.getSlotReserve push.0 gt
+    if.true
+      #
+      # Iceberg order. Refill it at the tail
+      #
+      dup exec.RefillSlot              # Display next slice                     [displayed_quantity, head_slot, next_slot, level_quantity', price, tail_slot, level_pointer, ...]
+      movup.3 add movdn.2              # Update level quantity                  [head_slot, next_slot, level_quantity'', price, tail_slot, level_pointer, ...]
+      dup.1 push.0 eq
+      if.true
+        swap drop movup.3 drop         # Order is alone in queue                [head_slot, level_quantity'', price, level_pointer, ...]
+        dup movdn.3 movdn.2            #                                        [level_quantity'', price, head_slot, head_slot, level_pointer, ...]
+      else
+        dup movup.5 exec.SetNextSlot   # Link behind tail                       [head_slot, next_slot, level_quantity'', price, level_pointer, ...]
+        movdn.3 movdn.2                #                                        [level_quantity'', price, next_slot, head_slot, level_pointer, ...]
+      end
+    else
+      exec.ZeroSlot                    # Zero out filled order                  [next_slot, level_quantity', price, tail_slot, level_pointer, ...]
+      dup push.0 eq
+      if.true
+        movup.3 drop push.0 movdn.3    # Empty queue has no tail                [next_slot, level_quantity', price, 0, level_pointer, ...]
+      end
+      movdn.2                          #                                        [level_word', level_pointer, ...]
+    end
+  else
+    dup.6 exec.UpdateSlotOrder         # Save head order                        [level_word', level_pointer, ...]
+  end
+  dup push.0 eq                        # Is level exhausted?                    [level_exhausted_bool, level_word', level_pointer, ...]
+  if.true
+    dropw exec.DestroyNode push.1      # Remove level from tree                 [1, ...]
+  else
+    movup.4 exec.UpdateOrder push.0    # Save level                             [0, ...]
+  end
+end
+
+proc.getBalancesRoot
+  # Inputs:  [...]
+  # Outputs: [balances_root, ...]
+  #
+  # Comments: Root of the account balances is kept at address 2147483634, '0' for no balances
+  #
+  push.0.0.0.0 mem_loadw.2147483634
+end
+
+proc.isSettling
+  # Inputs:  [...]
+  # Outputs: [isSettling_bool, ...]
+  #
+  exec.getBalancesRoot padw eqw movdn.8 dropw dropw not
+end
+
+proc.getBalance
+  # Inputs:  [account_ID, ...]
+  # Outputs: [base_balance, quote_balance, ...]
+  #
+  # Comments: Balances are the leaves [base_balance, quote_balance, 0, 0] of a sparse Merkle tree
+  #           of depth 64 indexed by account ID, provided by the merkle store
+  #
+  exec.getBalancesRoot movup.4 push.64 # Leaf of account                    [64, account_ID, balances_root, ...]
+  mtree_get swapw dropw                # Load leaf                          [base_balance, quote_balance, 0, 0, ...]
+  movup.2 drop movup.2 drop            #                                    [base_balance, quote_balance, ...]
+end
+
+proc.SaveBalance
+  # Inputs:  [account_ID, base_balance, quote_balance, ...]
+  # Outputs: [...]
+  #
+  movdn.2 push.0.0 movup.3 movup.3     # Build leaf                         [base_balance, quote_balance, 0, 0, account_ID, ...]
+  exec.getBalancesRoot movup.8 push.64 # Leaf of account                    [64, account_ID, balances_root, leaf, ...]
+  mtree_set dropw                      # Update tree                        [balances_root', ...]
+  mem_storew.2147483634 dropw          # Save new root                      [...]
+end
+
+proc.CreditBalance
+  # Inputs:  [account_ID, base_amount, quote_amount, ...]
+  # Outputs: [...]
+  #
+  dup exec.getBalance                  # Load balance                       [base_balance, quote_balance, account_ID, base_amount, quote_amount, ...]
+  movup.3 add                          # Credit base                        [base_balance', quote_balance, account_ID, quote_amount, ...]
+  swap movup.3 add                     # Credit quote                       [quote_balance', base_balance', account_ID, ...]
+  swap movup.2 exec.SaveBalance        #                                    [...]
+end
+
+proc.DebitBalance
+  # Inputs:  [account_ID, base_amount, quote_amount, ...]
+  # Outputs: [...]
+  #
+  dup exec.getBalance                  # Load balance                       [base_balance, quote_balance, account_ID, base_amount, quote_amount, ...]
+  dup dup.4 gte assert                 # Base balance is not sufficient     [base_balance, quote_balance, account_ID, base_amount, quote_amount, ...]
+  movup.3 sub                          # Debit base                         [base_balance', quote_balance, account_ID, quote_amount, ...]
+  swap dup dup.4 gte assert            # Quote balance is not sufficient    [quote_balance, base_balance', account_ID, quote_amount, ...]
+  movup.3 sub                          # Debit quote                        [quote_balance', base_balance', account_ID, ...]
+  swap movup.2 exec.SaveBalance        #                                    [...]
+end
+
+proc.ToAmount
+  # Inputs:  [amount_hi, amount_lo, ...]
+  # Outputs: [amount, ...]
+  #
+  # Comments: Amount of a u64 value as a field element, asserted below the field modulus
+  #
+  dup push.4294967295 lt assert        # Amount fits a field element        [amount_hi, amount_lo, ...]
+  push.4294967296 mul add              #                                    [amount, ...]
+end
+
+proc.getNotional
+  # Inputs:  [quantity, price, ...]
+  # Outputs: [notional, ...]
+  #
+  # Comments: Quantity times price, both u32 values, widened rather than reduced modulo p
+  #
+  u32assert2 u32overflowing_mul        # Notional as u64                    [notional_hi, notional_lo, ...]
+  exec.ToAmount                        #                                    [notional, ...]
+end
+
+proc.getFee
+  # Inputs:  [notional, bps, ...]
+  # Outputs: [fee, ...]
+  #
+  # Comments: Fee of notional at bps, in whole quote units rounded down
+  #
+  u32split movup.2 push.0              # Bps as u64                         [0, bps, notional_hi, notional_lo, ...]
+  exec.u64::checked_mul                # Fee in bps                         [fee_hi, fee_lo, ...]
+  push.10000.0 exec.u64::checked_div   # Fee                                [fee_hi, fee_lo, ...]
+  exec.ToAmount                        #                                    [fee, ...]
+end
+
+proc.getLockedFunds
+  # Inputs:  [quantity, price, isBuy_bool, ...]
+  # Outputs: [locked_funds, ...]
+  #
+  # Comments: Funds an order locks for its unfilled quantity: the base quantity of a sell order, the
+  #           quote notional of a buy order plus its fee at the max rate of 100 bps, rounded down.
+  #           Quantity and price of a buy order must be u32 values
+  #
+  movup.2
+  if.true
+    u32assert2 u32overflowing_mul      # Notional as u64                    [notional_hi, notional_lo, ...]
+    dup.1 dup.1 push.100.0             # Max fee rate as u64                [0, 100, notional_hi, notional_lo, notional_hi, notional_lo, ...]
+    exec.u64::checked_mul              # Max fee in bps                     [fee_hi, fee_lo, notional_hi, notional_lo, ...]
+    push.10000.0 exec.u64::checked_div # Max fee                            [fee_hi, fee_lo, notional_hi, notional_lo, ...]
+    exec.u64::checked_add              # Notional and fee                   [locked_hi, locked_lo, ...]
+    exec.ToAmount                      #                                    [locked_funds, ...]
+  else
+    swap drop                          # Base quantity                      [quantity, ...]
+  end
+end
+
+proc.getReleasedFunds
+  # Inputs:  [quantity, unfilled_quantity, price, isBuy_bool, ...]
+  # Outputs: [released_funds, ...]
+  #
+  # Comments: Funds an order with unfilled_quantity left releases once quantity of it fills or leaves
+  #           the book, the difference of its locked funds before and after. The funds released by
+  #           an order add up to those it locked
+  #
+  movdn.3                              #                                    [unfilled_quantity, price, isBuy_bool, quantity, ...]
+  dup.2 dup.2 dup.2 exec.getLockedFunds  # Locked before                    [locked_funds, unfilled_quantity, price, isBuy_bool, quantity, ...]
+  movdn.4 movup.3 sub                  # Unfilled after                     [unfilled_quantity', price, isBuy_bool, locked_funds, ...]
+  exec.getLockedFunds sub              # Locked before less after           [released_funds, ...]
+end
+
+proc.CreditFunds
+  # Inputs:  [account_ID, funds, isBuy_bool, ...]
+  # Outputs: [...]
+  #
+  # Comments: Credits funds released by an order to its account, quote for a buy order and base for
+  #           a sell order
+  #
+  movup.2
+  if.true
+    push.0 swap                        # Quote funds                        [account_ID, 0, funds, ...]
+  else
+    push.0 movdn.2                     # Base funds                         [account_ID, funds, 0, ...]
+  end
+  exec.CreditBalance
+end
+
+proc.DebitFunds
+  # Inputs:  [account_ID, funds, isBuy_bool, ...]
+  # Outputs: [...]
+  #
+  # Comments: Debits funds locked by an order from its account, quote for a buy order and base for a
+  #           sell order
+  #
+  movup.2
+  if.true
+    push.0 swap                        # Quote funds                        [account_ID, 0, funds, ...]
+  else
+    push.0 movdn.2                     # Base funds                         [account_ID, funds, 0, ...]
+  end
+  exec.DebitBalance
+end
+
+proc.LockIncomingFunds
+  # Inputs:  [quantity, price, ...]
+  # Outputs: [...]
+  #
+  # Comments: Debits the account of the incoming order with the funds quantity of it locks at price,
+  #           which fails the proof if the account runs short. Nothing is locked without balances
+  #
+  exec.isSettling
+  if.true
+    exec.isIncomingBuy movdn.2 exec.getLockedFunds  # Funds to lock         [locked_funds, ...]
+    exec.getIncomingAccount movdn.3 drop drop drop  # Account               [account_ID, locked_funds, ...]
+    exec.isIncomingBuy movdn.2 exec.DebitFunds
+  else
+    drop drop
+  end
+end
+
+proc.ReleaseIncoming
+  # Inputs:  [quantity, ...]
+  # Outputs: [...]
+  #
+  # Comments: Takes quantity off the unfilled quantity of the incoming order, kept at address
+  #           2147483623, without a fill and credits its account with the funds released.
+  #           Nothing is credited without balances
+  #
+  mem_load.2147483623                  # Load unfilled quantity             [unfilled_quantity, quantity, ...]
+  dup dup.2 sub mem_store.2147483623   # Update unfilled quantity           [unfilled_quantity, quantity, ...]
+  exec.isSettling
+  if.true
+    exec.getIncomingOrder drop movdn.2 drop drop  # Incoming price          [price, unfilled_quantity, quantity, ...]
+    exec.isIncomingBuy movdn.3 swap movup.2       #                         [quantity, unfilled_quantity, price, isBuy_bool, ...]
+    exec.getReleasedFunds              # Funds released                     [released_funds, ...]
+    exec.getIncomingAccount movdn.3 drop drop drop
+    exec.isIncomingBuy movdn.2 exec.CreditFunds   #                         [...]
+  else
+    drop drop
+  end
+end
+
+proc.ReleaseSlotFunds
+  # Inputs:  [quantity, slot, isBuy_bool, ...]
+  # Outputs: [...]
+  #
+  # Comments: Credits the account of the queued order with the funds quantity of it releases when it
+  #           leaves the book without a fill. Nothing is credited without balances
+  #
+  exec.isSettling
+  if.true
+    dup.1 exec.getSlotReserve          # Load reserve                       [reserve, quantity, slot, isBuy_bool, ...]
+    dup.2 exec.getSlotOrder            # Load queued order                  [slot_quantity, price, time, ID, reserve, quantity, slot, isBuy_bool, ...]
+    movup.2 drop movup.2 drop movup.2 add  # Reserve included               [unfilled_quantity, price, quantity, slot, isBuy_bool, ...]
+    dup.4 movdn.2 movup.3              #                                    [quantity, unfilled_quantity, price, isBuy_bool, slot, isBuy_bool, ...]
+    exec.getReleasedFunds              # Funds released                     [released_funds, slot, isBuy_bool, ...]
+    swap exec.getSlotAccount movdn.3 drop drop drop
+    exec.CreditFunds                   #                                    [...]
+  else
+    drop drop drop
+  end
+end
+
+proc.ReleaseLevelHead
+  # Inputs:  [quantity, level_pointer, ...]
+  # Outputs: [...]
+  #
+  # Comments: Releases the funds of quantity of the first order queued at the price level, which
+  #           leaves the book without a fill. The opposite side must be selected
+  #
+  swap exec.getLevelHeadSlot swap      # Head slot                          [quantity, slot, ...]
+  exec.isIncomingBuy not movdn.2       # Maker side                         [quantity, slot, isBuy_bool, ...]
+  exec.ReleaseSlotFunds
+end
+
+proc.getBuyerFunds
+  # Inputs:  [fill_quantity, fill_price, maker_unfilled_quantity, ...]
+  # Outputs: [buyer_funds, ...]
+  #
+  # Comments: Funds the buyer of a fill against the incoming order releases to pay for it: the
+  #           incoming order at its price, or else the maker at the fill price. '0' without balances
+  #
+  exec.isSettling
+  if.true
+    exec.isIncomingBuy
+    if.true
+      movup.2 drop swap drop           # Incoming order buys                [fill_quantity, ...]
+      exec.getIncomingOrder drop movdn.2 drop drop
+      mem_load.2147483623 movup.2      #                                    [fill_quantity, unfilled_quantity, price, ...]
+    else
+      movup.2 swap                     # Maker buys                         [fill_quantity, unfilled_quantity, price, ...]
+    end
+    push.1 movdn.3 exec.getReleasedFunds  #                                 [buyer_funds, ...]
+  else
+    drop drop drop push.0
+  end
+end
+
+proc.getLastTradePrice
+  # Inputs:  [...]
+  # Outputs: [last_trade_price, ...]
+  #
+  # Comments: Last trade price is kept as the quantity of node 0, '0' before the first trade
+  #
+  push.0 exec.getNodePointer exec.getOrder movdn.3 drop drop drop
+end
+
+proc.SetLastTradePrice
+  # Inputs:  [last_trade_price, ...]
+  # Outputs: [...]
+  #
+  push.0 exec.getNodePointer           # Node 0                             [node_pointer, last_trade_price, ...]
+  dup exec.getOrder drop               # Drop old price                     [0, 0, 1, node_pointer, last_trade_price, ...]
+  movup.4 movup.4 exec.UpdateOrder     # Save node 0                        [...]
+end
+
+proc.NewFill
+  # Inputs:  [...]
+  # Outputs: [fill_pointer, ...]
+  #
+  # Comments: increments fill counter and returns fresh fill pointer
+  #
+  mem_load.2147483648 push.1 add       # Increment fill counter              [fill_number++, ...]
+  dup mem_store.2147483648             # Save new fill counter               [fill_number++, ...]
+  exec.getFillPointer                  # Derive fill pointer                 [fill_pointer, ...]
+end
+
+proc.SaveFill
+  # Inputs:  [fill_word, account_word, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves fill word [quantity, price, maker_ID, taker_ID] at a fresh fill pointer and
+  #           account word [maker_account_ID, taker_account_ID, isTakerBuy_bool, maker_time] 1073741824
+  #           above it. Auction fills carry no maker time
+  #
+  exec.NewFill                         # Fresh fill pointer                 [fill_pointer, fill_word, account_word, ...]
+  dup movdn.5 mem_storew dropw         # Save in RAM                        [fill_pointer, account_word, ...]
+  push.1073741824 add mem_storew dropw # Save accounts                      [...]
+end
+
+proc.RecordFill
+  # Inputs:  [fill_quantity, fill_price, maker_ID, maker_account_ID, maker_time, maker_unfilled_quantity, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves the fill of maker against the incoming order and makes its price the
+  #           last trade price. The incoming order is kept at address 2147483646 while matching,
+  #           its unfilled quantity at address 2147483623. Maker_unfilled_quantity is what was left
+  #           of the maker order before the fill, reserve included. The funds the buyer releases
+  #           for the fill are saved for settlement
+  #
+  movup.5 dup.2 dup.2 exec.getBuyerFunds movdn.5  # Funds of buyer        [fill_quantity, fill_price, maker_ID, maker_account_ID, maker_time, buyer_funds, ...]
+  mem_load.2147483623 dup.1 sub        # Fill incoming order                [unfilled_quantity', fill_quantity, ...]
+  mem_store.2147483623
+  dup.1 exec.SetLastTradePrice         # Last trade price                   [fill_quantity, fill_price, maker_ID, maker_account_ID, maker_time, ...]
+  movup.3 movup.4 exec.isIncomingBuy   # Taker side                         [isTakerBuy_bool, maker_time, maker_account_ID, fill_quantity, fill_price, maker_ID, ...]
+  exec.getIncomingAccount movdn.3 drop drop drop
+  movup.3                              # Build account word                 [account_word, fill_quantity, fill_price, maker_ID, ...]
+  movup.6 movup.6 movup.6              #                                    [fill_quantity, fill_price, maker_ID, account_word, ...]
+  exec.getIncomingOrder                # Load incoming order                [quantity, price, time, taker_ID, fill_quantity, fill_price, maker_ID, ...]
+  drop drop drop movdn.3               # Build fill word                    [fill_quantity, fill_price, maker_ID, taker_ID, ...]
+  exec.SaveFill                        # Save fill                          [buyer_funds, ...]
+  mem_load.2147483648 exec.getFillFundsPointer mem_store
+end
+
+proc.CancelLevelHead
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [level_exhausted_bool, level_pointer, ...]
+  #
+  # Comments: Removes the first order queued at the price level, reserve included, without a fill.
+  #           Its funds are released
+  #
+  dup exec.getLevelHeadOrder movdn.3 drop drop drop
+  dup.1 exec.getLevelHeadSlot exec.getSlotReserve add  # Reserve included  [unfilled_quantity, level_pointer, ...]
+  dup.1 swap exec.ReleaseLevelHead     # Release funds                   [level_pointer, ...]
+  dup exec.getLevelHeadSlot exec.ClearSlotReserve
+  dup exec.getLevelHeadOrder           # Load head order                 [head_quantity, head_price, head_time, head_ID, level_pointer, ...]
+  movdn.3 drop drop drop               # Isolate head quantity           [head_quantity, level_pointer, ...]
+  dup.1 swap                           #                                 [head_quantity, level_pointer, level_pointer, ...]
+  exec.FillLevelHead                   # Dequeue head order              [level_exhausted_bool, level_pointer, ...]
+end
+
+proc.isSelfTrade
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [isSelfTrade_bool, ...]
+  #
+  # Comments: Checks if the first order queued at the price level belongs to the account of
+  #           the incoming order. Account '0' never self trades
+  #
+  exec.getLevelHeadSlot exec.getSlotAccount
+  movdn.3 drop drop drop               # Isolate head account            [head_account_ID, ...]
+  exec.getIncomingAccount
+  movdn.3 drop drop drop               # Isolate incoming account        [account_ID, head_account_ID, ...]
+  dup push.0 neq movdn.2 eq and        # Same non-zero account?          [isSelfTrade_bool, ...]
+end
+
+proc.PreventSelfTrade
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+  #
+  # Comments: Applies the STP policy of the incoming order against the first order queued at
+  #           the price level, without a fill. Stp_policy should be '0' to cancel the incoming
+  #           order, '1' to cancel the resting order, '2' to decrement both by the smaller quantity.
+  #           A cancelled incoming order is marked at address 2147483639. Decrements release funds
+  #
+  exec.getIncomingAccount
+  drop movdn.2 drop drop               # Isolate STP policy              [stp_policy, level_pointer, quantity, price, ...]
+  dup push.0 eq
+  if.true
+    # Cancel newest
+    drop push.1 mem_store.2147483639   # Mark incoming order cancelled   [level_pointer, quantity, price, ...]
+    swap drop push.0 swap push.0       # Nothing left to match           [0, level_pointer, 0, price, ...]
+  else
+    push.1 eq
+    if.true
+      # Cancel oldest
+      exec.CancelLevelHead             # Dequeue resting order           [level_exhausted_bool, level_pointer, quantity, price, ...]
+    else
+      # Decrement both
+      dup exec.getLevelHeadOrder       # Load head order                 [head_quantity, head_price, head_time, head_ID, level_pointer, quantity, price, ...]
+      movdn.3 drop drop drop           # Isolate head quantity           [head_quantity, level_pointer, quantity, price, ...]
+      dup.2 dup.1 dup.1 lt             # Is head quantity < quantity?    [isHeadSmaller_bool, quantity, head_quantity, level_pointer, quantity, price, ...]
+      if.true
+        drop                           # Decrement by head               [decrement, level_pointer, quantity, price, ...]
+      else
+        swap drop                      # Decrement by incoming order     [decrement, level_pointer, quantity, price, ...]
+      end
+      dup exec.ReleaseIncoming         # Release funds of incoming order [decrement, level_pointer, quantity, price, ...]
+      dup.1 dup.1 exec.ReleaseLevelHead  # Release funds of head order   [decrement, level_pointer, quantity, price, ...]
+      dup movup.3 swap sub movdn.2     # Decrement incoming order        [decrement, level_pointer, remaining_quantity, price, ...]
+      dup.1 swap                       #                                 [decrement, level_pointer, level_pointer, remaining_quantity, price, ...]
+      exec.FillLevelHead               # Decrement head order            [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    end
+  end
+end
+
+proc.FillOrder
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+  #
+  # Comments: Fills incoming order against the first order queued at the price level.
+  #           An expired head order is removed from the level without a fill.
+  #           A head order of the incoming account goes through self-trade prevention instead
+  #
+  dup exec.getLevelHeadSlot exec.isSlotExpired
+  if.true
+    exec.CancelLevelHead               # Dequeue expired order           [level_exhausted_bool, level_pointer, quantity, price, ...]
+  else
+    dup exec.isSelfTrade
+    if.true
+      exec.PreventSelfTrade            # Self-trade prevention           [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    else
+      dup exec.getLevelHeadOrder       # Load head order                 [head_quantity, head_price, head_time, head_ID, level_pointer, quantity, price, ...]
+      dup dup.6                        # Duplicate quantities on top     [quantity, head_quantity, head_quantity, head_price, head_time, head_ID, level_pointer, quantity, price, ...]
+      dup.1 dup.1 lt                   # Is head quantity < quantity?    [isHeadSmaller_bool, quantity, head_quantity, ...]
+      if.true
+        drop                           # Fill entire head                [fill_quantity, head_quantity, head_price, head_time, head_ID, level_pointer, quantity, price, ...]
+      else
+        swap drop                      # Fill entire incoming order      [fill_quantity, head_quantity, head_price, head_time, head_ID, level_pointer, quantity, price, ...]
+      end
+      dup.3 dup.5 dup.4 dup.3          # Fill at level price             [fill_quantity, head_price, head_ID, head_time, fill_quantity, head_order_word, level_pointer, quantity, price, ...]
+      dup.9 exec.getLevelHeadSlot      # Maker account                   [head_slot, fill_quantity, head_price, head_ID, head_time, ...]
+      exec.getSlotAccount movdn.3 drop drop drop movdn.3
+      dup.10 exec.getLevelHeadSlot exec.getSlotReserve dup.7 add movdn.5  # Head reserve included
+      exec.RecordFill                  # Record fill                     [fill_quantity, head_order_word, level_pointer, quantity, price, ...]
+      movdn.4 dropw                    #                                 [fill_quantity, level_pointer, quantity, price, ...]
+      dup movup.3 swap sub movdn.2     # Update how much left to fill    [fill_quantity, level_pointer, remaining_quantity, price, ...]
+      dup.1 swap                       #                                 [fill_quantity, level_pointer, level_pointer, remaining_quantity, price, ...]
+      exec.FillLevelHead               # Fill head order                 [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    end
+  end
+end
+
+proc.isLevelCrossing
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [isCrossing_bool, level_pointer, quantity, price, ...]
+  #
+  # Comments: Checks that quantity is left and level crosses price
+  #
+  dup.2 dup.1                          # [level_pointer, price, level_pointer, quantity, price, ...]
+  exec.isLevelCrossingPrice            # [isLevelCrossingPrice_bool, level_pointer, quantity, price, ...]
+  dup.2 push.0 gt and                  # [isCrossing_bool, level_pointer, quantity, price, ...]
+end
+
+proc.getFillableQuantity
+  # Inputs:  [price, ...]
+  # Outputs: [fillable_quantity, ...]
+  #
+  # Comments: Sums the quantity of unexpired orders queued at the opposite levels crossing price
+  #
+  push.0 swap                          # Initialize fillable quantity    [price, 0, ...]
+  exec.getBestOppositePointer          # Start from best level           [level_pointer, price, fillable_quantity, ...]
+  dup.1 dup.1 exec.isLevelCrossingPrice
+  while.true
+    dup exec.getLevelHeadSlot          # Walk queue from head            [slot, level_pointer, price, fillable_quantity, ...]
+    dup push.0 neq
+    while.true
+      dup exec.isSlotExpired not
+      if.true
+        dup exec.getSlotOrder          # Load queued order               [quantity, price, time, ID, slot, ...]
+        movdn.3 drop drop drop         # Isolate quantity                [quantity, slot, level_pointer, price, fillable_quantity, ...]
+        dup.1 exec.getSlotReserve add  # Add hidden quantity             [quantity, slot, level_pointer, price, fillable_quantity, ...]
+        movup.4 add movdn.3            # Add to fillable quantity        [slot, level_pointer, price, fillable_quantity', ...]
+      end
+      exec.getNextSlot                 # Next order in queue             [next_slot, level_pointer, price, fillable_quantity, ...]
+      dup push.0 neq
+    end
+    drop exec.getNextOppositeLevel     # Next worse level                [level_pointer, price, fillable_quantity, ...]
+    dup.1 dup.1 exec.isLevelCrossingPrice
+  end
+  drop drop
+end
+
+proc.isSlotEligible
+  # Inputs:  [slot, ...]
+  # Outputs: [isEligible_bool, ...]
+  #
+  # Comments: A queued order takes part in an allocation unless expired or of the incoming account
+  #
+  dup exec.isSlotExpired not swap      # Not expired                     [notExpired_bool, slot, ...]
+  exec.getSlotAccount
+  movdn.3 drop drop drop               # Isolate account                 [account_ID, notExpired_bool, ...]
+  exec.getIncomingAccount
+  movdn.3 drop drop drop               # Isolate incoming account        [incoming_account_ID, account_ID, notExpired_bool, ...]
+  dup push.0 neq movdn.2 eq and        # Same non-zero account?          [isSelfTrade_bool, notExpired_bool, ...]
+  not and
+end
+
+proc.getLevelEligibleQuantity
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [eligible_quantity, order_count, ...]
+  #
+  # Comments: Sums the displayed quantity of the eligible orders queued at the price level and
+  #           counts all of them
+  #
+  push.0.0 movup.2                     # Initialize sum and count        [level_pointer, eligible_quantity, order_count, ...]
+  exec.getLevelHeadSlot                # Walk queue from head            [slot, eligible_quantity, order_count, ...]
+  dup push.0 neq
+  while.true
+    movup.2 push.1 add movdn.2         # Count order                     [slot, eligible_quantity, order_count', ...]
+    dup exec.isSlotEligible
+    if.true
+      dup exec.getSlotOrder            # Load queued order               [quantity, price, time, ID, slot, ...]
+      movdn.3 drop drop drop           # Isolate quantity                [quantity, slot, eligible_quantity, order_count, ...]
+      movup.2 add swap                 # Add to eligible quantity        [slot, eligible_quantity', order_count, ...]
+    end
+    exec.getNextSlot                   # Next order in queue             [next_slot, eligible_quantity, order_count, ...]
+    dup push.0 neq
+  end
+  drop
+end
+
+proc.MulDiv
+  # Inputs:  [a, b, c, ...]
+  # Outputs: [quotient, ...]
+  #
+  # Comments: Quotient of a * b by c, rounded down. All three must be u32 values, the product is
+  #           widened to a u64 rather than reduced modulo p, and the quotient must fit a u32
+  #
+  u32assert2 u32overflowing_mul        # Product as u64                  [product_hi, product_lo, c, ...]
+  movup.2 u32assert u32split           # Divisor as u64                  [c_hi, c_lo, product_hi, product_lo, ...]
+  exec.u64::checked_div                # Divide                          [quotient_hi, quotient_lo, ...]
+  push.0 assert_eq                     # Quotient fits u32               [quotient, ...]
+end
+
+proc.RoundDownToLot
+  # Inputs:  [quantity, ...]
+  # Outputs: [rounded_quantity, ...]
+  #
+  # Comments: Rounds quantity down to a multiple of lot_size, unless lot_size is '0'
+  #
+  exec.getInstrumentParams
+  drop movdn.4 dropw                   # Isolate lot size                [lot_size, quantity, ...]
+  dup push.0 neq
+  if.true
+    dup.1 swap u32checked_mod sub      # Drop odd lot                    [rounded_quantity, ...]
+  else
+    drop
+  end
+end
+
+proc.RotateLevelHead
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [...]
+  #
+  # Comments: Moves the first order queued at the price level behind the last one
+  #
+  dup exec.getOrder                    # Load level                      [level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+  dup.3 dup.3 neq                      # More than one order?            [isQueue_bool, level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+  if.true
+    dup.2 dup.4 exec.SetNextSlot       # Link head behind tail           [level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+    dup.2 exec.getNextSlot             # Next order becomes head         [next_slot, level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+    push.0 dup.4 exec.SetNextSlot      # Nothing behind new tail         [next_slot, level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+    movup.4 drop movdn.2               # Build level word                [level_quantity, price, next_slot, head_slot, level_pointer, ...]
+    movup.4 exec.UpdateOrder           # Save level                      [...]
+  else
+    dropw drop
+  end
+end
+
+proc.ShareProRata
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [level_pointer, remaining_quantity, price, ...]
+  #
+  # Comments: Fills every eligible order queued at the price level with its share of quantity, in
+  #           proportion to its displayed quantity and rounded down to whole lots. Quantity must be
+  #           below the eligible quantity of the level, kept at address 2147483625 while quantity is
+  #           kept at address 2147483626. Every order visited goes behind the last one, so the queue
+  #           ends up in its time priority. The rounding remainder is left to fill
+  #
+  dup exec.getLevelEligibleQuantity    # Shares of eligible quantity     [eligible_quantity, order_count, level_pointer, quantity, price, ...]
+  mem_store.2147483625
+  dup.2 mem_store.2147483626           # Quantity to share               [order_count, level_pointer, quantity, price, ...]
+  dup push.0 gt
+  while.true
+    dup.1 dup exec.getLevelHeadSlot exec.isSlotEligible
+    if.true
+      dup exec.getLevelHeadOrder       # Load head order                 [head_quantity, head_price, head_time, head_ID, level_pointer, order_count, ...]
+      movdn.3 drop drop drop           # Isolate head quantity           [head_quantity, level_pointer, order_count, level_pointer, quantity, price, ...]
+      mem_load.2147483625 mem_load.2147483626 movup.2
+      exec.MulDiv exec.RoundDownToLot  # Share of head                   [share, level_pointer, order_count, level_pointer, quantity, price, ...]
+      dup push.0 gt
+      if.true
+        dup movup.5 swap sub movdn.4   # Update how much left to fill    [share, level_pointer, order_count, level_pointer, remaining_quantity, price, ...]
+        push.0 movdn.2 swap            #                                 [level_pointer, share, 0, order_count, ...]
+        exec.FillOrder dropw           # Fill share, below head quantity [order_count, level_pointer, remaining_quantity, price, ...]
+      else
+        drop drop
+      end
+    else
+      drop
+    end
+    dup.1 exec.RotateLevelHead         # Next order                      [order_count, level_pointer, remaining_quantity, price, ...]
+    push.1 sub dup push.0 gt
+  end
+  drop
+end
+
+proc.FillLevel
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+  #
+  # Comments: Fills quantity against the orders queued at the price level in time priority, until
+  #           either runs out
+  #
+  push.0 dup.2 push.0 gt               # Quantity left?                  [isLeft_bool, 0, level_pointer, quantity, price, ...]
+  while.true
+    drop exec.FillOrder                # Fill head of level              [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    dup not dup.3 push.0 gt and
+  end
+end
+
+proc.isAllocating
+  # Inputs:  [level_pointer, quantity, ...]
+  # Outputs: [isAllocating_bool, level_pointer, quantity, ...]
+  #
+  # Comments: Pro rata and hybrid allocation only share a level that quantity does not exhaust
+  #
+  exec.getAllocation swap drop push.0 neq
+  if.true
+    dup exec.getLevelEligibleQuantity  # Eligible quantity of level      [eligible_quantity, order_count, level_pointer, quantity, ...]
+    swap drop dup.2 gt                 # Is quantity below it?           [isAllocating_bool, level_pointer, quantity, ...]
+  else
+    push.0
+  end
+end
+
+proc.AllocateLevel
+  # Inputs:  [level_pointer, quantity, price, ...]
+  # Outputs: [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+  #
+  # Comments: Shares quantity among the orders queued at the price level, which must hold more
+  #           eligible quantity. Hybrid allocation first fills priority_share_bps of quantity,
+  #           rounded down to whole lots, in time priority. The rest is shared pro rata and the
+  #           rounding remainder fills in time priority
+  #
+  exec.getAllocation push.2 eq mul     # Hybrid share only               [share_bps, level_pointer, quantity, price, ...]
+  push.10000 swap dup.3                #                                 [quantity, share_bps, 10000, level_pointer, quantity, price, ...]
+  exec.MulDiv exec.RoundDownToLot      # Priority slice                  [slice, level_pointer, quantity, price, ...]
+  movup.2 dup.1 sub movdn.3 swap       # Set rest aside                  [level_pointer, slice, price, rest, ...]
+  exec.FillLevel                       # Fill slice in time priority     [level_exhausted_bool, level_pointer, slice_left, price, rest, ...]
+  drop swap movup.3 add                # Fold slice left into rest       [rest, level_pointer, price, ...]
+  mem_load.2147483639
+  if.true
+    drop push.0                        # Incoming order cancelled        [0, level_pointer, price, ...]
+  end
+  swap dup.1 push.0 gt
+  if.true
+    exec.ShareProRata                  # Share rest pro rata             [level_pointer, remainder, price, ...]
+  end
+  exec.FillLevel                       # Remainder in time priority      [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+end
+
+proc.MatchOrder
+  # Inputs:  [quantity, price, ...]
+  # Outputs: [remaining_quantity, price, ...]
+  #
+  # Comments: Matches incoming order against the opposite price levels in price-time priority, or
+  #           shares a level it does not exhaust under pro rata and hybrid allocation.
+  #           The opposite side must be selected
+  #
+  exec.getBestOppositePointer          # Load pointer to best price      [level_pointer, quantity, price, ...]
+  exec.isLevelCrossing
+  while.true 
+    exec.isAllocating
+    if.true
+      exec.AllocateLevel               # Share level among its orders    [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    else
+      exec.FillOrder                   # Fill head of level              [level_exhausted_bool, level_pointer, remaining_quantity, price, ...]
+    end
+    if.true 
+      drop exec.getBestOppositePointer # Move to next level              [level_pointer, remaining_quantity, price, ...]
+    end
+    exec.isLevelCrossing
+  end
+  drop
+end
+
+proc.getLevelMinTime
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [min_time, ...]
+  #
+  # Comments: Earliest time of the unexpired orders queued at the price level
+  #
+  push.18446744069414584320 swap       # Initialize min time             [level_pointer, min_time, ...]
+  exec.getLevelHeadSlot                # Walk queue from head            [slot, min_time, ...]
+  dup push.0 neq
+  while.true
+    dup exec.isSlotExpired not
+    if.true
+      dup exec.getSlotOrder            # Load queued order               [quantity, price, time, ID, slot, min_time, ...]
+      drop drop swap drop              # Isolate time                    [time, slot, min_time, ...]
+      dup dup.3 lt                     # Is time earlier?                [isEarlier_bool, time, slot, min_time, ...]
+      if.true
+        movup.2 drop swap              # Keep time                       [slot, min_time', ...]
+      else
+        drop                           #                                 [slot, min_time, ...]
+      end
+    end
+    exec.getNextSlot                   # Next order in queue             [next_slot, min_time, ...]
+    dup push.0 neq
+  end
+  drop
+end
+
+proc.CheckBestExecution
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Checks every fill of the incoming order, those past the fill counter saved at address
+  #           2147483629, against the best opposite level left once matched. No fill may be at a
+  #           worse price than the level, and no fill at its price may be of a maker later than its
+  #           earliest unexpired order, unless allocation is pro rata or hybrid. A failed check clears
+  #           the best execution flag kept at address 2147483628. The opposite side must be selected
+  #
+  exec.getBestOppositePointer          # Best level left                 [level_pointer, ...]
+  dup push.0 neq
+  if.true
+    dup exec.getLevelMinTime swap      # Earliest order of level         [level_pointer, min_time, ...]
+    exec.getOrder drop movdn.2 drop drop
+    mem_load.2147483629 push.1 add     # First fill of incoming order    [j, level_price, min_time, ...]
+    dup mem_load.2147483648 lte        # Any fill left?                  [moreFills_bool, j, level_price, min_time, ...]
+    while.true
+      dup exec.getFillPointer          # Derive fill pointer             [fill_pointer, j, level_price, min_time, ...]
+      push.0.0.0.0 dup.4 mem_loadw     # Load fill word                  [quantity, price, maker_ID, taker_ID, fill_pointer, j, level_price, min_time, ...]
+      drop movdn.2 drop drop           # Isolate price                   [price, fill_pointer, j, level_price, min_time, ...]
+      push.0.0.0.0 movup.5 push.1073741824 add mem_loadw
+      drop drop                        # Load account word               [isTakerBuy_bool, maker_time, price, j, level_price, min_time, ...]
+      if.true
+        dup.1 dup.4 lte                # Not above best ask left         [isPriceBest_bool, maker_time, price, j, level_price, min_time, ...]
+      else
+        dup.1 dup.4 gte                # Not below best bid left         [isPriceBest_bool, maker_time, price, j, level_price, min_time, ...]
+      end
+      swap dup.5 lte                   # Maker not later than level      [isTimeBest_bool, isPriceBest_bool, price, j, level_price, min_time, ...]
+      movup.2 dup.4 neq or             # Time only counts at level price [isTimeBest_bool, isPriceBest_bool, j, level_price, min_time, ...]
+      exec.getAllocation swap drop     # Nor under pro rata allocation   [allocation_algorithm, isTimeBest_bool, isPriceBest_bool, j, ...]
+      push.0 neq or and                #                                 [isBest_bool, j, level_price, min_time, ...]
+      mem_load.2147483628 and          # Update flag                     [isBestExecution_bool, j, level_price, min_time, ...]
+      mem_store.2147483628
+      push.1 add                       # Next fill                       [j++, level_price, min_time, ...]
+      dup mem_load.2147483648 lte      # More fills?                     [moreFills_bool, j++, level_price, min_time, ...]
+    end
+    drop drop drop
+  else
+    drop
+  end
+end
+
+proc.TempLoadOrders
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  #    Comments: Loads orders and creates rbBST 
+  #
+  push.0.0.0.0
+  adv.push_mapval adv_loadw           # Load Total number of orders in advice stack    [number_of_orders, ...]
+  loc_store.0                         # Save number of orders in registry position 0   [...]
+  push.0 dup                          # Initialize counter i                           [0, 0, ...]
+  loc_load.1 loc_load.0 lt 
+  while.true
+    push.1 add                        # Increment counter and duplicate              [order_counter++, ...]
+    push.0.0.0 dup.3                  # Add buffer for loading next word             [0, 0, 0, 0, order_counter++, ...]
+    adv.push_mapval adv_loadw         # Load new order                               [ID, time, price, quantity, order_counter++, ...]
+    movdn.3 movdn.2 swap              # Put into correct format                      [quantity, price, time, ID, order_counter++, ...]
+    push.0.0.0 exec.InsertOrder       # Queue order                                  [order_counter++, ...]
+    dup loc_load.0 lt                 # Aare there more nodes?                       [more_nodes_bool, order_counter++, ...]
+  end
+  drop
+end
+
+proc.LoadTree
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  #    Comments: Loads pre-structured tree nodes from advice stack            
+  #              Coordinate Word Format: {{color, Parent_pointer, L_child_pointer, R_child_pointer}} 
+  #              Order Word Format:      {{quantity, price, time, ID}} 
+  #
+  push.0 exec.getBookNumber push.0.0
+  adv.push_mapval adv_loadw            # Load Total number of orders in advice stack    [number_of_orders, ...]
+  exec.SaveCreationCounter drop drop drop  # Save number of orders as final creation number [...]
+  exec.InitTree                       # Initialize Tree                                [...]
+  push.0 dup                          # Initialize counter in registry position 1      [0, 0, ...]
+  loc_load.1 exec.getCreationCounter lt 
+  while.true
+    push.1 add                        # Increment counter                            [order_counter++, ...] 
+    push.0 exec.getBookNumber push.0 dup.3  # Build node key                         [order_counter++, 0, book_number, 0, order_counter++, ...]
+    adv.push_mapval adv_loadw         # Load Node pointer                            [node_pointer, 0, 0, 0, order_counter++, ...]
+    exec.ToMemoryPointer              # Move into selected book                      [node_pointer, 0, 0, 0, order_counter++, ...]
+    push.0.0.0.0 adv_loadw            # Load Node coordinates                        [Rchild_pointer, Lchild_pointer, Parent_pointer, color, node_pointer, 0, 0, 0, order_counter++, ...]
+    movdn.3 movdn.2 swap              # Put into correct format                      [node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
+    exec.ToMemoryCoordinates          # Move into selected book                      [node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
+    push.0.0.0.0 adv_loadw            # Load Node order                              [ID, time, price, quantity, node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
+    movdn.3 movdn.2 swap              # Put into correct format                      [node_order_word, node_coordinate_word, node_pointer, 0, 0, 0, order_counter++, ...]
+    swapw                             # Coordinates in front                         [node_coordinate_word, node_order_word, node_pointer, 0, 0, 0, order_counter++, ...]
+    movupw.2 movdn.7 drop drop drop   # Isolate node pointer                         [node_coordinate_word, node_pointer, node_order_word, order_counter++, ...]
+    dup push.2 eq                     # Is init node?                                [isInit_bool, node_coordinate_word, node_pointer, node_order_word, ...]
+    if.true
+      dup.4 dup.9 exec.getInitCachePointer mem_store  # Save init pointer by side ID [node_coordinate_word, node_pointer, node_order_word, order_counter++, ...]
+    end
+    dup.4                             # Copy node pointer top of stack               [node_pointer, node_coordinate_word, node_pointer, node_order_word, order_counter++, ...]
+    exec.SaveCoordinate               # Save Node coordiante word                    [node_pointer, node_order_word, order_counter++, ...]
+    exec.SaveOrder                    # Save Node order word                         [order_counter++, ...]
+    dup exec.getCreationCounter lt    # Aare there more nodes?                      [more_nodes_bool, order_counter++, ...]            
+  end
+  drop drop
+end
+
+proc.LoadSlots
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  #    Comments: Loads queued orders of the price levels from advice stack
+  #              Account Word Format: [account_ID, stp_policy, 0, 0]
+  #              Link Word Format:    [next_slot, expiry, reserve, peak]
+  #              Order Word Format:   [quantity, price, time, ID]
+  #
+  push.0 exec.getBookNumber push.3.0
+  adv.push_mapval adv_loadw           # Load number of queued orders                 [number_of_slots, 0, 0, 0, ...]
+  movdn.3 drop drop drop              # Isolate number of queued orders              [number_of_slots, ...]
+  dup push.0 exec.getSlotPointer mem_store
+  push.0                              # Initialize counter                           [0, number_of_slots, ...]
+  dup.1 dup.1 gt
+  while.true
+    push.1 add                        # Increment counter                            [slot_counter++, number_of_slots, ...]
+    push.0 exec.getBookNumber push.3 dup.3  # Build slot key                         [slot_counter++, 3, book_number, 0, slot_counter++, ...]
+    adv.push_mapval adv_loadw         # Load slot                                    [slot, 0, 0, 0, slot_counter++, ...]
+    push.0.0.0.0 adv_loadw            # Load account                                 [account_word, slot, 0, 0, 0, slot_counter++, ...]
+    push.0.0.0.0 adv_loadw            # Load link                                    [link_word, account_word, slot, 0, 0, 0, slot_counter++, ...]
+    push.0.0.0.0 adv_loadw            # Load queued order                            [ID, time, price, quantity, link_word, account_word, slot, ...]
+    movdn.3 movdn.2 swap              # Put into correct format                      [order_word, link_word, account_word, slot, 0, 0, 0, slot_counter++, ...]
+    dup.12 exec.getSlotPointer        # Derive slot pointer                          [slot_pointer, order_word, link_word, account_word, slot, ...]
+    mem_storew dropw                  # Save queued order                            [link_word, account_word, slot, 0, 0, 0, slot_counter++, ...]
+    dup.8 exec.getSlotPointer push.1 add
+    mem_storew dropw                  # Save link                                    [account_word, slot, 0, 0, 0, slot_counter++, ...]
+    dup.4 exec.getSlotPointer push.3 add
+    mem_storew dropw                  # Save account                                 [slot, 0, 0, 0, slot_counter++, ...]
+    drop drop drop drop               #                                              [slot_counter++, number_of_slots, ...]
+    dup.1 dup.1 gt                    # Are there more queued orders?                [more_slots_bool, slot_counter++, number_of_slots, ...]
+  end
+  drop drop
+end
+
+proc.LoadFeeSchedule
+  # Inputs:  [fee_schedule_commitment, ...]
+  # Outputs: [...]
+  #
+  #    Comments: Loads the fee tiers from advice stack and asserts they hash to the public commitment.
+  #              A zero commitment is no fee schedule, no fees are charged
+  #              Tier Word Format: [min_notional, maker_bps, taker_bps, 0], strictly sorted by min_notional,
+  #              rates of 100 bps at most
+  #              fee_schedule_commitment = hmerge(...hmerge(hmerge(0, tier_word_1), tier_word_2)..., tier_word_N)
+  #
+  push.0.0.0.0 eqw movdn.8 dropw movup.4  # Is there no fee schedule?                  [noFees_bool, fee_schedule_commitment, ...]
+  if.true
+    dropw
+  else
+    push.0.0.4.0
+    adv.push_mapval adv_loadw         # Load number of fee tiers                     [number_of_tiers, 0, 0, 0, fee_schedule_commitment, ...]
+    movdn.3 drop drop drop            # Isolate number of fee tiers                  [number_of_tiers, fee_schedule_commitment, ...]
+    dup push.0 exec.getFeeTierPointer mem_store
+    push.0.0.0.0 push.0               # Empty accumulator and counter                [i=0, accumulator, number_of_tiers, fee_schedule_commitment, ...]
+    dup.5 dup.1 gt
+    while.true
+      push.1 add                      # Increment counter                            [i++, accumulator, number_of_tiers, ...]
+      push.0.0.4 dup.3                # Build tier key                               [i++, 4, 0, 0, i++, ...]
+      adv.push_mapval adv_loadw       # Load tier                                    [0, taker_bps, maker_bps, min_notional, i++, ...]
+      movdn.3 movdn.2 swap            # Put into correct format                      [tier_word, i++, accumulator, ...]
+      dup.1 push.100 lte assert       # Maker rate is above 100 bps                  [tier_word, i++, ...]
+      dup.2 push.100 lte assert       # Taker rate is above 100 bps                  [tier_word, i++, ...]
+      dup.4 exec.getFeeTierPointer mem_storew  # Save tier                           [tier_word, i++, accumulator, number_of_tiers, ...]
+      dup.4 push.1 gt                 # Is there a previous tier?                    [hasPrevious_bool, tier_word, i++, ...]
+      if.true
+        dup.4 push.1 sub exec.getFeeTierPointer push.0.0.0.0 movup.4 mem_loadw
+        dup.4 lt assert               # Tiers are not sorted by min_notional         [previous_maker_bps, previous_taker_bps, 0, tier_word, i++, ...]
+        drop drop drop                #                                              [tier_word, i++, accumulator, number_of_tiers, ...]
+      end
+      movup.4 movdn.8                 # Counter below accumulator                    [tier_word, accumulator, i++, number_of_tiers, ...]
+      hmerge movup.4                  # Accumulate tier                              [i++, accumulator, number_of_tiers, ...]
+      dup.5 dup.1 gt                  # Are there more tiers?                        [more_tiers_bool, i++, accumulator, number_of_tiers, ...]
+    end
+    drop movup.4 drop                 #                                              [accumulator, fee_schedule_commitment, ...]
+    assert_eqw                        # Fee tiers match commitment                   [...]
+  end
+end
+
+proc.LoadInstrumentParams
+  # Inputs:  [incoming_order_inputs, tick_size, lot_size, min_quantity, max_quantity, reference_price, price_band, ...]
+  # Outputs: [incoming_order_inputs, ...]
+  #
+  # Comments: Saves the instrument parameters kept below the 12 incoming order inputs
+  #
+  movupw.3 mem_storew.2147483638 dropw   # Save tick, lot and quantity bounds [incoming_order_inputs, reference_price, price_band, ...]
+  movup.13 movup.13 push.0.0 movup.3 movup.3  # Build price band word     [reference_price, price_band, 0, 0, incoming_order_inputs, ...]
+  mem_storew.2147483637 dropw            # Save price band                    [incoming_order_inputs, ...]
+end
+
+proc.LoadAllocation
+  # Inputs:  [allocation_algorithm, priority_share_bps, ...]
+  # Outputs: [...]
+  #
+  # Comments: Allocation_algorithm must be '0', '1' or '2' and priority_share_bps at most 10000
+  #
+  dup push.3 lt assert                 # Known algorithm
+  dup.1 push.10000 lte assert          # Share within 10000 bps
+  push.0.0 movup.3 movup.3             # Build allocation word           [allocation_algorithm, priority_share_bps, 0, 0, ...]
+  mem_storew.2147483627 dropw
+end
+
+proc.HashTree
+  # Inputs:  [...]
+  # Outputs: [tree_root, ...]
+  #
+  # Comments: Commits to every NIL-free node up to the creation number, then to every
+  #           NIL-free queued order up to the slot counter, in pointer order, of the selected book.
+  #           Node pointers are committed as counted from the book_base.
+  #           tree_root = hmerge(...hmerge(hmerge(0, node_hash_A), node_hash_B)..., slot_hash_N)
+  #           node_hash = hmerge(order_word, coordinate_word)
+  #           slot_hash = hmerge(hmerge(order_word, link_word), account_word)
+  #
+  push.0.0.0.0 push.0 push.1           # Empty accumulator and counter       [1, j=0, tree_root, ...]
+  while.true
+    dup exec.getNodePointer            # Derive node pointer                 [node_pointer, j, tree_root, ...]
+    dup exec.isNIL                     # Is node slot empty?                 [isNIL_bool, node_pointer, j, tree_root, ...]
+    if.true
+      drop                             # Skip empty slot                     [j, tree_root, ...]
+    else
+      exec.LoadNode drop               # Load node without change flag       [coordinate_word, order_word, j, tree_root, ...]
+      exec.ToBookCoordinates           # Pointers from book_base             [coordinate_word, order_word, j, tree_root, ...]
+      hmerge                           # Hash node                           [node_hash, j, tree_root, ...]
+      movup.4 movdn.8                  # Counter below accumulator           [node_hash, tree_root, j, ...]
+      hmerge movup.4                   # Accumulate node                     [j, tree_root, ...]
+    end
+    push.1 add                         # Increment counter                   [j++, tree_root, ...]
+    dup exec.getCreationCounter lte    # Are there more node slots?          [moreNodes_bool, j++, tree_root, ...]
+  end
+  drop
+
+  push.1                               # Queued orders start at slot 1       [j=1, tree_root, ...]
+  dup push.0 exec.getSlotPointer mem_load lte
+  while.true
+    dup exec.isSlotNIL not
+    if.true
+      dup exec.LoadSlot                # Load queued order                   [link_word, order_word, j, tree_root, ...]
+      hmerge                           # Hash queued order                   [order_hash, j, tree_root, ...]
+      dup.4 exec.getSlotAccount hmerge # Hash account                        [slot_hash, j, tree_root, ...]
+      movup.4 movdn.8                  # Counter below accumulator           [slot_hash, tree_root, j, ...]
+      hmerge movup.4                   # Accumulate queued order             [j, tree_root, ...]
+    end
+    push.1 add                         # Increment counter                   [j++, tree_root, ...]
+    dup push.0 exec.getSlotPointer mem_load lte
+  end
+  drop
+end
+
+proc.AbsoluteDifference
+  # Inputs:  [a, b, ...]
+  # Outputs: [|a-b|, ...]
+  #
+  dup.1 dup.1 gt                     # Is b greater than a?                   [isBGreater_bool, a, b, ...]
+  if.true
+    sub                              # [b-a, ...]
+  else
+    swap sub                         # [a-b, ...]
+  end
+end
+
+proc.getDemandAtPrice
+  # Inputs:  [price, ...]
+  # Outputs: [demand, ...]
+  #
+  # Comments: Sums the quantity of every bid in the book priced at or above price.
+  #           Selects the BUY side
+  #
+  exec.SelectBids
+  push.0 swap                        # Initialize demand                      [price, 0, ...]
+  exec.getBestBidPointer             # Start from max bid level               [bid_pointer, price, demand, ...]
+  dup.1 dup.1 exec.isBidAtOrAbovePrice
+  while.true
+    dup exec.getOrder                # Load bid level                         [bid_quantity, bid_price, head_slot, tail_slot, bid_pointer, price, demand, ...]
+    movup.6 add movdn.5              # Add quantity to demand                 [bid_price, head_slot, tail_slot, bid_pointer, price, demand, ...]
+    drop drop drop                   #                                        [bid_pointer, price, demand, ...]
+    exec.getInOrderPredecessor       # Next lower bid level                   [bid_pointer, price, demand, ...]
+    dup.1 dup.1 exec.isBidAtOrAbovePrice
+  end
+  drop drop
+end
+
+proc.getSupplyAtPrice
+  # Inputs:  [price, ...]
+  # Outputs: [supply, ...]
+  #
+  # Comments: Sums the quantity of every ask in the book priced at or below price.
+  #           Selects the SELL side
+  #
+  exec.SelectAsks
+  push.0 swap                        # Initialize supply                      [price, 0, ...]
+  exec.getBestAskPointer             # Start from min ask level               [ask_pointer, price, supply, ...]
+  dup.1 dup.1 exec.isLevelAtOrBelowPrice
+  while.true
+    dup exec.getOrder                # Load ask level                         [ask_quantity, ask_price, head_slot, tail_slot, ask_pointer, price, supply, ...]
+    movup.6 add movdn.5              # Add quantity to supply                 [ask_price, head_slot, tail_slot, ask_pointer, price, supply, ...]
+    drop drop drop                   #                                        [ask_pointer, price, supply, ...]
+    exec.getInOrderSuccessor         # Next higher ask level                  [ask_pointer, price, supply, ...]
+    dup.1 dup.1 exec.isLevelAtOrBelowPrice
+  end
+  drop drop
+end
+
+proc.isAuctionCrossing
+  # Inputs:  [...]
+  # Outputs: [isCrossing_bool, ...]
+  #
+  # Comments: Checks that the best bid of the book is priced at or above its best ask.
+  #           Leaves the BUY side selected
+  #
+  exec.SelectAsks exec.getBestAskPointer  # Min ask level                     [ask_pointer, ...]
+  dup push.0 neq                     # Is ask not NIL?                        [isNotNIL_bool, ask_pointer, ...]
+  swap exec.getOrder drop            # Load ask level                         [ask_price, head_slot, tail_slot, isNotNIL_bool, ...]
+  movdn.2 drop drop                  # Isolate ask price                      [ask_price, isNotNIL_bool, ...]
+  exec.SelectBids exec.getBestBidPointer  # Max bid level                     [bid_pointer, ask_price, isNotNIL_bool, ...]
+  exec.isBidAtOrAbovePrice and       # Does bid cross ask?                    [isCrossing_bool, ...]
+end
+
+proc.UncrossFills
+  # Inputs:  [...]
+  # Outputs: [marginal_bid_price, marginal_ask_price, ...]
+  #
+  # Comments: Fills the first bid of the best bid level against the first ask of the best ask
+  #           level while they cross, recording fills with a zero price. Bids are the makers.
+  #           Both orders locked their funds when they were placed: the bid releases the funds
+  #           paying for the fill, the base of the ask is already locked. Filled orders leave the
+  #           book and what is left of the others keeps resting.
+  #           Returns the prices of the last bid and ask filled, 0 without fills
+  #
+  push.0.0                           # No marginal prices yet                 [marginal_bid_price, marginal_ask_price, ...]
+  exec.isAuctionCrossing
+  while.true
+    drop drop
+    exec.SelectAsks exec.getBestAskPointer  # Min ask level                   [ask_level, ...]
+    exec.SelectBids exec.getBestBidPointer  # Max bid level                   [bid_level, ask_level, ...]
+    dup.1 exec.getLevelHeadOrder     # Load first ask of level                [ask_word, bid_level, ask_level, ...]
+    dup.4 exec.getLevelHeadOrder     # Load first bid of level                [bid_word, ask_word, bid_level, ask_level, ...]
+
+    ### FILL QUANTITY ###
+    dup dup.5                        # Duplicate quantities                   [ask_quantity, bid_quantity, bid_word, ask_word, ...]
+    dup.1 dup.1 lt                   # Is bid quantity < ask quantity?        [isBidSmaller_bool, ask_quantity, bid_quantity, bid_word, ask_word, ...]
+    if.true
+      drop                           # Fill is bid quantity                   [fill_quantity, bid_word, ask_word, bid_level, ask_level, ...]
+    else
+      swap drop                      # Fill is ask quantity                   [fill_quantity, bid_word, ask_word, bid_level, ask_level, ...]
+    end
+    push.0.0 dup.12                  # Taker sells, no maker time             [ask_level, 0, 0, fill_quantity, ...]
+    exec.getLevelHeadSlot exec.getSlotAccount movdn.3 drop drop drop
+    dup.12                           # Account word                           [bid_level, ask_account_ID, 0, 0, fill_quantity, ...]
+    exec.getLevelHeadSlot exec.getSlotAccount movdn.3 drop drop drop
+    dup.12 dup.9 push.0 dup.7        # Build fill word                        [fill_quantity, 0, bid_ID, ask_ID, account_word, fill_quantity, ...]
+    exec.SaveFill                    # Save fill, price set later             [fill_quantity, bid_word, ask_word, bid_level, ask_level, ...]
+    exec.isSettling
+    if.true
+      dup.9 exec.getLevelHeadSlot exec.getSlotReserve dup.2 add  # Bid reserve included [unfilled_quantity, fill_quantity, ...]
+      push.1 dup.4 movup.2 dup.3     # Bid releases funds                     [fill_quantity, unfilled_quantity, bid_price, 1, fill_quantity, ...]
+      exec.getReleasedFunds
+      mem_load.2147483648 exec.getFillFundsPointer mem_store
+    end
+
+    ### UPDATE LEVELS ###
+    exec.SelectAsks
+    dup.10 dup.1 exec.FillLevelHead drop  # Fill first ask of level           [fill_quantity, bid_word, ask_word, bid_level, ask_level, ...]
+    exec.SelectBids
+    dup.9 dup.1 exec.FillLevelHead drop   # Fill first bid of level           [fill_quantity, bid_word, ask_word, bid_level, ask_level, ...]
+    drop drop                        #                                        [bid_price, bid_time, bid_ID, ask_word, bid_level, ask_level, ...]
+    swap drop swap drop swap drop    #                                        [bid_price, ask_price, ask_time, ask_ID, bid_level, ask_level, ...]
+    movdn.5 movdn.5 dropw            # Marginal prices                        [marginal_bid_price, marginal_ask_price, ...]
+    exec.isAuctionCrossing
+  end
+end
+
+proc.SetFillPrices
+  # Inputs:  [price, first_fill_number, ...]
+  # Outputs: [...]
+  #
+  # Comments: Sets price of every fill from first_fill_number on
+  #
+  swap dup mem_load.2147483648 lte     # Any fill left?                     [moreFills_bool, fill_number, price, ...]
+  while.true
+    dup exec.getFillPointer            # Derive fill pointer                [fill_pointer, fill_number, price, ...]
+    push.0.0.0.0 dup.4 mem_loadw       # Load fill word                     [fill_quantity, 0, maker_ID, taker_ID, fill_pointer, fill_number, price, ...]
+    swap drop dup.5 swap               # Set fill price                     [fill_word, fill_pointer, fill_number, price, ...]
+    movup.4 mem_storew dropw           # Save in RAM                        [fill_number, price, ...]
+    push.1 add                         # Next fill                          [fill_number++, price, ...]
+    dup mem_load.2147483648 lte        # More fills?                        [moreFills_bool, fill_number++, price, ...]
+  end
+  drop drop
+end
+
+proc.Uncross
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Call auction of the bids against the asks resting in the book of the selected
+  #           instrument. Crossing orders are filled in price-time priority, which executes the
+  #           max volume. Every fill clears at the single marginal price, bid or ask, with min
+  #           imbalance. In between marginal prices the demand and supply of the full book exceed
+  #           the ones left by the executed volume, so imbalance is measured on the orders left.
+  #           Orders left after the auction keep resting in the book
+  #
+  mem_load.2147483648 push.1 add loc_store.2   # Save first auction fill number
+  exec.UncrossFills                            # Execute max volume     [marginal_bid_price, marginal_ask_price, ...]
+  loc_store.0 loc_store.1                      # Save marginal prices
+  loc_load.2 mem_load.2147483648 lte           # Were there any fills?  [hasFills_bool, ...]
+  if.true
+    ### IMBALANCE AT MARGINAL BID PRICE ###
+    loc_load.0 exec.getSupplyAtPrice           # [supply_bid, ...]
+    loc_load.0 exec.getDemandAtPrice           # [demand_bid, supply_bid, ...]
+    exec.AbsoluteDifference                    # [imbalance_bid, ...]
+
+    ### IMBALANCE AT MARGINAL ASK PRICE ###
+    loc_load.1 exec.getSupplyAtPrice           # [supply_ask, imbalance_bid, ...]
+    loc_load.1 exec.getDemandAtPrice           # [demand_ask, supply_ask, imbalance_bid, ...]
+    exec.AbsoluteDifference                    # [imbalance_ask, imbalance_bid, ...]
+
+    ### CLEARING PRICE ###
+    gt                                         # Is ask imbalance smaller? [isAskPrice_bool, ...]
+    if.true
+      loc_load.1
+    else
+      loc_load.0
+    end
+    dup exec.SetLastTradePrice                 # [clearing_price, ...]
+    loc_load.2 swap                            # [clearing_price, first_fill_number, ...]
+    exec.SetFillPrices
+  end
+end
+
+proc.ValidateQuantity
+  # Inputs:  [quantity, ...]
+  # Outputs: [...]
+  #
+  # Comments: Asserts quantity is positive, a multiple of lot size and within min and max quantity
+  #
+  dup push.0 gt assert                 # Quantity is not positive                [quantity, ...]
+  exec.getInstrumentParams drop        #                                         [lot_size, min_quantity, max_quantity, reference_price, price_band, quantity, ...]
+  movup.3 drop movup.3 drop            #                                         [lot_size, min_quantity, max_quantity, quantity, ...]
+  dup push.0 neq
+  if.true
+    dup.3 swap u32checked_mod
+    push.0 eq assert                   # Quantity is not a multiple of lot size  [min_quantity, max_quantity, quantity, ...]
+  else
+    drop                               #                                         [min_quantity, max_quantity, quantity, ...]
+  end
+  dup push.0 neq
+  if.true
+    dup.2 lte assert                   # Quantity is below min quantity          [max_quantity, quantity, ...]
+  else
+    drop                               #                                         [max_quantity, quantity, ...]
+  end
+  dup push.0 neq
+  if.true
+    lte assert                         # Quantity is above max quantity          [...]
+  else
+    drop drop                          #                                         [...]
+  end
+end
+
+proc.ValidatePrice
+  # Inputs:  [price, ...]
+  # Outputs: [...]
+  #
+  # Comments: Asserts price is positive, a multiple of tick size and within the price band
+  #           around the reference price
+  #
+  dup push.0 gt assert                 # Price is not positive                   [price, ...]
+  exec.getInstrumentParams             #                                         [tick_size, lot_size, min_quantity, max_quantity, reference_price, price_band, price, ...]
+  movdn.3 drop drop drop               #                                         [tick_size, reference_price, price_band, price, ...]
+  dup push.0 neq
+  if.true
+    dup.3 swap u32checked_mod
+    push.0 eq assert                   # Price is not a multiple of tick size    [reference_price, price_band, price, ...]
+  else
+    drop                               #                                         [reference_price, price_band, price, ...]
+  end
+  dup.1 push.0 neq
+  if.true
+    movup.2 exec.AbsoluteDifference    # Distance to reference price             [distance, price_band, ...]
+    gte assert                         # Price is outside the price band         [...]
+  else
+    drop drop drop                     #                                         [...]
+  end
+end
+
+proc.ValidateOrder
+  # Inputs:  [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price, ...]
+  # Outputs: [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price, ...]
+  #
+  # Comments: Asserts the incoming order against the instrument parameters. Price is checked
+  #           unless the order is a stop market order, trigger price is checked for a stop.
+  #           A market order is no stop and its price is a slippage cap of at most 10000 bps
+  #
+  dup.1 exec.ValidateQuantity
+  dup.5 push.4 eq                      # Is order a market order?
+  if.true
+    dup.9 push.0 eq assert             # Market order must not be a stop
+    dup.2 push.10000 lte assert        # Slippage cap must be 10000 bps at most
+  else
+    dup.9 push.0 eq dup.3 push.0 neq or  # Has a limit price?
+    if.true
+      dup.2 exec.ValidatePrice
+    end
+  end
+  dup.9 push.0 neq                     # Is order a stop?
+  if.true
+    dup.9 exec.ValidatePrice
+  end
+end
+
+proc.SelectOppositeSide
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Selects the side the incoming order matches against
+  #
+  exec.isIncomingBuy
+  if.true
+    exec.SelectAsks
+  else
+    exec.SelectBids
+  end
+end
+
+proc.SelectOwnSide
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Selects the side the incoming order rests on
+  #
+  exec.isIncomingBuy
+  if.true
+    exec.SelectBids
+  else
+    exec.SelectAsks
+  end
+end
+
+proc.isIncomingStopTriggered
+  # Inputs:  [...]
+  # Outputs: [isTriggered_bool, ...]
+  #
+  # Comments: Buy stops trigger once the last trade price reaches their trigger price from below,
+  #           sell stops once it reaches it from above. Nothing triggers before the first trade
+  #
+  exec.getLastTradePrice
+  dup push.0 neq swap                  # Has anything traded?                    [last_trade_price, hasTraded_bool, ...]
+  mem_load.2147483641                  # Load trigger price                      [trigger_price, last_trade_price, hasTraded_bool, ...]
+  exec.isIncomingBuy
+  if.true
+    gte                                # Is last >= trigger?                     [isReached_bool, hasTraded_bool, ...]
+  else
+    lte                                # Is last <= trigger?                     [isReached_bool, hasTraded_bool, ...]
+  end
+  and
+end
+
+proc.ActivateIncomingStop
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Turns the triggered incoming stop into a limit order, or into an IOC market
+  #           order when its limit price is '0'
+  #
+  exec.getIncomingOrder
+  dup.1 push.0 eq                      # Is stop market?                         [isMarket_bool, quantity, price, time, ID, ...]
+  if.true
+    exec.isIncomingBuy push.0 push.1 sub mul  # Market price                     [market_price, quantity, 0, time, ID, ...]
+    movup.2 drop swap                  #                                         [quantity, market_price, time, ID, ...]
+    mem_storew.2147483646 dropw        # Save incoming order                     [...]
+    exec.getIncomingFlags
+    swap drop push.1 swap              # IOC                                     [buy-sell_flag, 1, expiry, post_only, ...]
+    mem_storew.2147483643 dropw        # Save incoming flags                     [...]
+  else
+    dropw
+  end
+  push.0 mem_store.2147483641          # No longer a stop                        [...]
+end
+
+proc.ParkIncomingStop
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Queues the incoming stop in the stop tree of its side at its trigger price.
+  #           Queued order keeps the limit price, '0' for a stop market order, and locks funds at it.
+  #           Reported with status '4' at its trigger price
+  #
+  exec.getIncomingOrder movup.2 drop movup.2 drop
+  exec.LockIncomingFunds               # Lock funds                              [...]
+  exec.isIncomingBuy
+  if.true
+    push.3 exec.SelectSide             # Buy stops                               [...]
+  else
+    push.4 exec.SelectSide             # Sell stops                              [...]
+  end
+  exec.getIncomingOrder                # Incoming order                          [order_word, ...]
+  exec.getIncomingFlags drop push.3 eq mul swap drop
+  push.0.0 movup.2                     # Expiry for GTD                          [expiry, 0, 0, order_word, ...]
+  mem_load.2147483641                  # Load trigger price                      [trigger_price, expiry, 0, 0, order_word, ...]
+  exec.InsertOrderAtLevel              #                                         [...]
+
+  ### REPORT ###
+  exec.getIncomingOrder movdn.3 drop drop  # Order ID                            [ID, quantity, ...]
+  mem_load.2147483641 swap push.4 swap # Build report word                       [ID, 4, trigger_price, quantity, ...]
+  exec.SaveOrderReport
+end
+
+proc.PriceMarketOrder
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Turns the incoming market order into an IOC order limited by its slippage cap, kept
+  #           as its price, in bps of the best opposite price on arrival. A '0' cap sweeps every
+  #           level at the market price. Without opposite levels there is nothing to sweep and
+  #           the price is '0'. The opposite side must be selected
+  #
+  exec.getIncomingOrder                # Load incoming order                     [quantity, slippage_cap, time, ID, ...]
+  exec.getBestOppositePointer          # Best level on arrival                   [level_pointer, quantity, slippage_cap, time, ID, ...]
+  dup push.0 neq dup.3 push.0 neq and  # Is slippage capped?
+  if.true
+    exec.getOrder drop movdn.2 drop drop  # Best opposite price                  [best_price, quantity, slippage_cap, time, ID, ...]
+    push.10000 dup.3 dup.2 exec.MulDiv # Largest price move                      [max_move, best_price, quantity, slippage_cap, time, ID, ...]
+    exec.isIncomingBuy
+    if.true
+      add                              # Above best ask                          [limit_price, quantity, slippage_cap, time, ID, ...]
+    else
+      dup.1 dup.1 lt
+      if.true
+        drop drop push.0               # Down to any bid                         [limit_price, quantity, slippage_cap, time, ID, ...]
+      else
+        sub                            # Below best bid                          [limit_price, quantity, slippage_cap, time, ID, ...]
+      end
+    end
+  else
+    push.0 neq exec.isIncomingBuy and  # Market price                            [isBuyLevel_bool, quantity, slippage_cap, time, ID, ...]
+    push.0 push.1 sub mul              #                                         [limit_price, quantity, slippage_cap, time, ID, ...]
+  end
+  movup.2 drop swap                    #                                         [quantity, limit_price, time, ID, ...]
+  mem_storew.2147483646 dropw          # Save incoming order                     [...]
+  exec.getIncomingFlags
+  swap drop push.1 swap                # IOC                                     [buy-sell_flag, 1, expiry, post_only, ...]
+  mem_storew.2147483643 dropw          # Save incoming flags                     [...]
+end
+
+proc.getAverageFillPrice
+  # Inputs:  [...]
+  # Outputs: [average_price, ...]
+  #
+  # Comments: Volume-weighted average price of the fills of the incoming order, those past the fill
+  #           counter saved at address 2147483629, rounded down. '0' without fills. Fill quantities
+  #           and prices must be u32 values, their products are widened rather than reduced modulo p
+  #
+  push.0.0.0                           # Initialize sums                         [notional_hi, notional_lo, quantity, ...]
+  mem_load.2147483629 push.1 add       # First fill of incoming order            [j, notional_hi, notional_lo, quantity, ...]
+  dup mem_load.2147483648 lte          # Any fill left?                          [moreFills_bool, j, ...]
+  while.true
+    push.0.0.0.0 dup.4 exec.getFillPointer mem_loadw
+    movup.2 drop movup.2 drop          # Load fill                               [fill_quantity, fill_price, j, notional_hi, notional_lo, quantity, ...]
+    dup movup.6 add movdn.5            # Add to quantity                         [fill_quantity, fill_price, j, notional_hi, notional_lo, quantity', ...]
+    u32assert2 u32overflowing_mul      # Fill notional as u64, widened           [fill_notional_hi, fill_notional_lo, j, notional_hi, notional_lo, quantity, ...]
+    movup.2 movdn.4 exec.u64::checked_add  # Add to notional                    [notional_hi', notional_lo', j, quantity, ...]
+    movup.2 push.1 add                 # Next fill                               [j++, notional_hi, notional_lo, quantity, ...]
+    dup mem_load.2147483648 lte        # More fills?                             [moreFills_bool, j++, ...]
+  end
+  drop dup.2 push.0 neq
+  if.true
+    movup.2 u32split exec.u64::checked_div  # Notional over quantity             [average_price_hi, average_price_lo, ...]
+    push.0 assert_eq                   #                                         [average_price, ...]
+  else
+    drop drop drop push.0              # No fills                                [0, ...]
+  end
+end
+
+proc.ExecuteOrder
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Matches and rests the incoming order kept at address 2147483646. Its fills are checked
+  #           for best execution against the opposite side left once matched. A market order is
+  #           marked at address 2147483624. The order locks its funds before matching, its fills
+  #           are paid from them and what does not rest is released
+  #
+  push.0 mem_store.2147483639          # Not cancelled by STP                    [...]
+  mem_load.2147483648                  # Fills before incoming order             [fill_count, ...]
+  mem_store.2147483629                 #                                         [...]
+  exec.SelectOppositeSide
+  exec.getTimeInForce push.4 eq
+  dup mem_store.2147483624             # Is order a market order?                [isMarket_bool, ...]
+  if.true
+    exec.PriceMarketOrder              # Price from slippage cap                 [...]
+  end
+  exec.getIncomingOrder
+  movup.2 drop movup.2 drop            # Isolate quantity and price              [quantity, price, ...]
+  push.0                               # Order accepted                          [status, quantity, price, ...]
+
+  ### POST ONLY ###
+  exec.getPostOnlyFlag
+  dup push.0 neq
+  if.true
+    exec.getBestOppositePointer        # Best opposite level                     [level_pointer, post_only, status, quantity, price, ...]
+    dup dup.5 swap exec.isLevelCrossingPrice
+    if.true
+      swap push.1 eq
+      if.true
+        # Order would cross. Reject it
+        drop drop drop push.0 push.2   #                                         [status, 0, price, ...]
+      else
+        # Order would cross. Reprice it behind best opposite level
+        exec.getOrder drop movdn.2 drop drop
+        exec.isIncomingBuy
+        if.true
+          push.1 sub                   # Below min ask                           [price', status, quantity, price, ...]
+        else
+          push.1 add                   # Above max bid                           [price', status, quantity, price, ...]
+        end
+        movup.3 drop movdn.2           #                                         [status, quantity, price', ...]
+        drop push.3                    #                                         [status, quantity, price', ...]
+      end
+    else
+      drop drop                        #                                         [status, quantity, price, ...]
+    end
+  else
+    drop                               #                                         [status, quantity, price, ...]
+  end
+
+  ### FILL OR KILL ###
+  exec.getTimeInForce push.2 eq
+  if.true
+    dup.2 exec.getFillableQuantity     # Quantity of crossing levels             [fillable_quantity, status, quantity, price, ...]
+    dup.2 lt                           # Can order not fill entirely?            [isKilled_bool, status, quantity, price, ...]
+    if.true
+      # Order is killed
+      # Leave book untouched
+      drop drop push.0 push.1          #                                         [status, 0, price, ...]
+    end
+  end
+
+  ### LOCK FUNDS ###
+  dup.1 mem_store.2147483623           # Unfilled quantity                       [status, quantity, price, ...]
+  dup.2 dup.2 exec.LockIncomingFunds   # Lock funds at price                     [status, quantity, price, ...]
+
+  ### MATCH ###
+  movdn.2                              #                                         [quantity, price, status, ...]
+  exec.MatchOrder                      # Search tree for matching orders         [remaining_quantity, price, status, ...]
+  exec.CheckBestExecution              # No better price bypassed                [remaining_quantity, price, status, ...]
+
+  ### REST ###
+  dup push.0 gt exec.getTimeInForce push.1 neq and
+  if.true
+    # Queue remainder at its price level, with an expiry for GTD. Its funds stay locked
+    mem_load.2147483623 dup.1 sub exec.ReleaseIncoming
+    exec.SelectOwnSide
+    dup.1 dup.1 exec.getIncomingOrder  # Incoming order                          [quantity, price, time, ID, remaining_quantity, price, ...]
+    drop drop movup.3 movup.3          # Remaining order                         [remaining_quantity, price, time, ID, remaining_quantity, price, status, ...]
+    mem_load.2147483642                # Load display size                       [display_size, remaining_quantity, price, time, ID, ...]
+    dup push.0 neq dup.1 dup.3 lt and  # Is order an iceberg?                    [isIceberg_bool, display_size, remaining_quantity, ...]
+    if.true
+      dup movup.2 dup.1 sub            # Hide all but one slice                  [reserve, peak, displayed_quantity, price, time, ID, ...]
+    else
+      drop push.0.0                    # Display everything                      [0, 0, remaining_quantity, price, time, ID, ...]
+    end
+    exec.getIncomingFlags drop push.3 eq mul swap drop
+    exec.InsertOrder                   #                                         [resting_quantity, price, status, ...]
+  else
+    # IOC remainder never rests and releases its funds. A market order reports it unfilled, at its
+    # average fill price
+    mem_load.2147483623 exec.ReleaseIncoming
+    mem_load.2147483624
+    if.true
+      swap drop exec.getAverageFillPrice swap  #                                 [unfilled_quantity, average_price, status, ...]
+    else
+      drop push.0                      #                                         [resting_quantity, price, status, ...]
+    end
+  end
+
+  ### REPORT ###
+  exec.getIncomingOrder drop drop drop # Order ID                                [ID, resting_quantity, price, status, ...]
+  movup.3 swap                         #                                         [ID, status, resting_quantity, price, ...]
+  mem_load.2147483639
+  if.true
+    swap drop push.5 swap              # Cancelled by STP                        [ID, 5, resting_quantity, price, ...]
+  end
+  movup.3 movdn.2                      # Build report word                       [ID, status, price, resting_quantity, ...]
+  exec.SaveOrderReport
+end
+
+proc.ProcessOrder
+  # Inputs:  [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price,
+  #           account_ID, stp_policy, ...]
+  # Outputs: [...]
+  #
+  # Comments: Buy-sell_flag should be '0' for a sell order, '1' for buy order. Buy orders match
+  #           the SELL side and rest on the BUY side, sell orders the other way around.
+  #           Every node is a price level [level_quantity, price, head_slot, tail_slot] whose orders
+  #           are queued in time priority from head_slot, linked by next_slot.
+  #           Time_in_force should be '0' for GTC, '1' for IOC, '2' for FOK, '3' for GTD, '4' for market.
+  #           IOC orders never rest. FOK orders fill entirely or leave the book untouched.
+  #           Market orders sweep the opposite levels as IOC orders, their price being a slippage cap
+  #           in bps of the best opposite price on arrival, '0' for none. A market buy order locks
+  #           its funds at the price its cap limits it to, so it needs a cap when balances are settled.
+  #           GTD orders rest until expiry. Unfilled GTC and GTD remainders rest at the limit price.
+  #           Post_only should be '0' for none, '1' to reject and '2' to reprice an order that
+  #           would cross. A repriced order rests one price step behind the best opposite level.
+  #           Display_size '0' rests the whole remainder, otherwise the remainder rests as an
+  #           iceberg order showing slices of display_size.
+  #           Trigger_price '0' is an ordinary order, otherwise the order is a stop queued in
+  #           a stop tree until the last trade price reaches trigger_price.
+  #           Account_ID '0' is no account. Orders of the same account never fill against each
+  #           other, stp_policy should be '0' to cancel the incoming order, '1' to cancel the
+  #           resting order and '2' to decrement both.
+  #           Incoming order is kept at address 2147483646 and its time is the clock expiries
+  #           are checked against. Every order ends with a report word:
+  #           [ID, status, price, resting_quantity], or [ID, status, average_price, unfilled_quantity]
+  #           for a market order, status '0' accepted, '1' killed,
+  #           '2' rejected, '3' repriced, '4' stopped, '5' cancelled by self-trade prevention
+  #
+  movdn.4                              # Flags behind order                      [order_word, buy-sell_flag, time_in_force, expiry, post_only, ...]
+  mem_storew.2147483646 dropw          # Save incoming order                     [buy-sell_flag, time_in_force, expiry, post_only, ...]
+  mem_storew.2147483643 dropw          # Save incoming flags                     [display_size, trigger_price, ...]
+  mem_store.2147483642                 # Save display size                       [trigger_price, ...]
+  mem_store.2147483641                 # Save trigger price                      [account_ID, stp_policy, ...]
+  push.0.0 movup.3 movup.3             # Build account word                      [account_ID, stp_policy, 0, 0, ...]
+  mem_storew.2147483640 dropw          # Save incoming account                   [...]
+
+  ### STOP ###
+  mem_load.2147483641 push.0 neq
+  if.true
+    exec.isIncomingStopTriggered
+    if.true
+      exec.ActivateIncomingStop
+      exec.ExecuteOrder
+    else
+      exec.ParkIncomingStop
+    end
+  else
+    exec.ExecuteOrder
+  end
+end
+
+proc.PopStop
+  # Inputs:  [level_pointer, buy-sell_flag, ...]
+  # Outputs: [buy-sell_flag, order_word, time_in_force, expiry, 0, 0, 0, account_ID, stp_policy, ...]
+  #
+  # Comments: Dequeues the first stop of a triggered level as incoming order stack. The stop
+  #           tree must be selected. Stop market orders come out as IOC at the market price,
+  #           stop limit orders as GTC, or GTD when they expire. An expired stop comes out empty.
+  #           The stop releases its funds, which the order locks again once processed
+  #
+  dup exec.getLevelHeadSlot            # Head stop                               [slot, level_pointer, buy-sell_flag, ...]
+  dup exec.getSlotOrder movdn.3 drop drop drop
+  dup.3 movdn.2 exec.ReleaseSlotFunds  # Release funds                           [level_pointer, buy-sell_flag, ...]
+  dup exec.getLevelHeadSlot exec.getSlotAccount
+  movup.2 drop movup.2 drop            # Load account                            [account_ID, stp_policy, level_pointer, buy-sell_flag, ...]
+  movdn.3 movdn.3                      #                                         [level_pointer, buy-sell_flag, account_ID, stp_policy, ...]
+  dup exec.getLevelHeadSlot            # Head stop                               [slot, level_pointer, buy-sell_flag, ...]
+  dup exec.isSlotExpired not movdn.3   # Is stop live?                           [slot, level_pointer, buy-sell_flag, isLive_bool, ...]
+  dup exec.getSlotExpiry               # Load expiry                             [expiry, slot, level_pointer, buy-sell_flag, isLive_bool, ...]
+  dup.1 exec.getSlotOrder              # Load stop                               [quantity, price, time, ID, expiry, slot, level_pointer, buy-sell_flag, isLive_bool, ...]
+  dup.1 push.0 eq                      # Is stop market?                         [isMarket_bool, quantity, price, time, ID, expiry, ...]
+  dup dup.9 mul                        # Is stop market buy?                     [isMarketBuy_bool, isMarket_bool, quantity, price, ...]
+  push.0 push.1 sub mul                # Market buy price                        [price_offset, isMarket_bool, quantity, price, ...]
+  movup.3 add movdn.2                  #                                         [isMarket_bool, quantity, price', time, ID, expiry, ...]
+  dup.5 push.0 neq push.3 mul swap     # GTD when expiring, else GTC             [isMarket_bool, time_in_force, quantity, price', ...]
+  if.true
+    drop push.1                        # IOC                                     [time_in_force, quantity, price', ...]
+  end
+  dup.7 dup.2 exec.FillLevelHead drop  # Dequeue stop                            [time_in_force, quantity, price', time, ID, expiry, slot, level_pointer, buy-sell_flag, isLive_bool, ...]
+  movup.9 dup.2 mul swap.2 drop        # Empty if expired                        [time_in_force, quantity', price', time, ID, expiry, slot, level_pointer, buy-sell_flag, ...]
+  movdn.4                              #                                         [order_word, time_in_force, expiry, slot, level_pointer, buy-sell_flag, ...]
+  movup.8                              # Flag in front                           [buy-sell_flag, order_word, time_in_force, expiry, slot, level_pointer, ...]
+  movup.7 drop movup.7 drop            #                                         [buy-sell_flag, order_word, time_in_force, expiry, ...]
+  push.0.0.0 movdn.9 movdn.9 movdn.9   # Not a stop anymore                      [buy-sell_flag, order_word, time_in_force, expiry, 0, 0, 0, account_ID, stp_policy, ...]
+end
+
+proc.PopTriggeredStop
+  # Inputs:  [...]
+  # Outputs: [1, buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price,
+  #           account_ID, stp_policy, ...]
+  #       or [0, ...] when no stop is triggered
+  #
+  # Comments: Buy stops trigger from the lowest trigger price at or below the last trade price,
+  #           sell stops from the highest trigger price at or above it
+  #
+  exec.getLastTradePrice
+  dup push.0 neq
+  if.true
+    push.3 exec.SelectSide
+    exec.getBestAskPointer             # Lowest buy stop level                   [level_pointer, last_trade_price, ...]
+    dup dup.2 swap exec.isLevelAtOrBelowPrice
+    if.true
+      swap drop push.1 swap            #                                         [level_pointer, 1, ...]
+      exec.PopStop push.1
+    else
+      drop
+      push.4 exec.SelectSide
+      exec.getBestBidPointer           # Highest sell stop level                 [level_pointer, last_trade_price, ...]
+      dup dup.2 swap exec.isBidAtOrAbovePrice
+      if.true
+        swap drop push.0 swap          #                                         [level_pointer, 0, ...]
+        exec.PopStop push.1
+      else
+        drop drop push.0
+      end
+    end
+  else
+    drop push.0
+  end
+end
+
+proc.TriggerStops
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Moves stops triggered by the last trade price into the book one at a time,
+  #           so fills of a triggered stop can trigger further stops
+  #
+  exec.PopTriggeredStop
+  while.true
+    exec.ProcessOrder
+    exec.PopTriggeredStop
+  end
+end
+
+proc.LoadBalancesRoot
+  # Inputs:  [balances_root, ...]
+  # Outputs: [...]
+  #
+  mem_storew.2147483634 dropw
+end
+
+proc.ValidateBalance
+  # Inputs:  [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price, account_ID, ...]
+  # Outputs: [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price, account_ID, ...]
+  #
+  # Comments: Asserts a buy order bounds the funds it locks: a market buy order needs a slippage cap
+  #           and a buy stop a limit price. Every order locks its funds when it is executed or parked,
+  #           which fails the proof if its account runs short. Nothing is checked without balances
+  #
+  exec.isSettling
+  if.true
+    dup
+    if.true
+      dup.2 push.0 neq assert          # Buy order has no bound on its notional [buy-sell_flag, ...]
+    end
+  end
+end
+
+proc.getOrderMessage
+  # Inputs:  [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price,
+  #           account_ID, stp_policy, ...]
+  # Outputs: [message, buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size,
+  #           trigger_price, account_ID, stp_policy, ...]
+  #
+  # Comments: Hashes every input of the incoming order and the instrument of the selected book:
+  #           message = hmerge(hmerge(hmerge(order_word, flags_word), options_word), instrument_word)
+  #           flags_word = [buy-sell_flag, time_in_force, expiry, post_only]
+  #           options_word = [display_size, trigger_price, account_ID, stp_policy]
+  #           instrument_word = [instrument_ID, 0, 0, 0]
+  #
+  dup.4 dup.4 dup.4 dup.4              # Copy order word                      [order_word, buy-sell_flag, order_word, ...]
+  dup.11 dup.11 dup.11 dup.7           # Copy flags                           [flags_word, order_word, buy-sell_flag, order_word, ...]
+  hmerge                               # Hash order and flags                 [order_hash, buy-sell_flag, order_word, ...]
+  dup.15 dup.15 dup.15 dup.15          # Copy options                         [options_word, order_hash, buy-sell_flag, order_word, ...]
+  hmerge                               # Hash options                         [order_hash, buy-sell_flag, order_word, ...]
+  push.0.0.0 exec.getBookNumber push.2113929216 add mem_load
+  hmerge                               # Hash instrument                      [message, buy-sell_flag, order_word, ...]
+end
+
+proc.VerifyOrderSignature
+  # Inputs:  [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price,
+  #           account_ID, stp_policy, ...]
+  # Outputs: [buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size, trigger_price,
+  #           account_ID, stp_policy, ...]
+  #
+  # Comments: Verifies the Falcon signature of the incoming order by the public key of its account,
+  #           when signatures are required by the flag kept at address 2147483633.
+  #           The public key comes from the advice map under the order ID and its top element must be
+  #           the account ID. The client signs off-circuit, and `adv.push_sig` in `rpo_falcon512::verify`
+  #           reads its signature from the advice map under hmerge(public_key, message), no key pair is
+  #           ever in the advice map. The signed message commits to every input of the order and its
+  #           instrument, see getOrderMessage
+  #
+  mem_load.2147483633
+  if.true
+    push.0.0.6 dup.7                   # Build public key key                 [ID, 6, 0, 0, buy-sell_flag, order_word, ...]
+    adv.push_mapval adv_loadw          # Load public key                      [public_key, buy-sell_flag, order_word, ...]
+    dup dup.15 assert_eq               # Public key is not of the account     [public_key, buy-sell_flag, order_word, ...]
+    mem_storew.2147483632 dropw        #                                      [buy-sell_flag, order_word, ...]
+    exec.getOrderMessage               # Hash order                           [message, buy-sell_flag, order_word, ...]
+    push.0.0.0.0 mem_loadw.2147483632  # Load public key                      [public_key, message, buy-sell_flag, order_word, ...]
+    exec.rpo_falcon512::verify         # Signature is not valid               [buy-sell_flag, order_word, ...]
+  end
+end
+
+proc.getNullifierRoot
+  # Inputs:  [...]
+  # Outputs: [nullifier_root, ...]
+  #
+  # Comments: Root of the nullifiers of consumed orders is kept at address 2147483631, '0' for no
+  #           replay protection
+  #
+  push.0.0.0.0 mem_loadw.2147483631
+end
+
+proc.LoadNullifierRoot
+  # Inputs:  [nullifier_root, ...]
+  # Outputs: [...]
+  #
+  mem_storew.2147483631 dropw
+end
+
+proc.NullifyOrder
+  # Inputs:  [buy-sell_flag, order_word, ...]
+  # Outputs: [buy-sell_flag, order_word, ...]
+  #
+  # Comments: Nullifiers are the leaves [1, 0, 0, 0] of a sparse Merkle tree of depth 64 indexed by
+  #           order ID, provided by the merkle store. Asserts the incoming order was never consumed,
+  #           then consumes it. Nothing is checked without a nullifier root
+  #
+  exec.getNullifierRoot padw eqw movdn.8 dropw dropw not
+  if.true
+    exec.getNullifierRoot dup.8 push.64 # Leaf of order ID                  [64, ID, nullifier_root, buy-sell_flag, order_word, ...]
+    mtree_get swapw dropw              # Load leaf                          [nullifier, buy-sell_flag, order_word, ...]
+    padw eqw assert dropw dropw        # Order was already consumed         [buy-sell_flag, order_word, ...]
+    push.0.0.0.1                       # Build leaf                         [1, 0, 0, 0, buy-sell_flag, order_word, ...]
+    exec.getNullifierRoot dup.12 push.64
+    mtree_set dropw                    # Update tree                        [nullifier_root', buy-sell_flag, order_word, ...]
+    mem_storew.2147483631 dropw        # Save new root                      [buy-sell_flag, order_word, ...]
+  end
+end
+
+proc.SettleFill
+  # Inputs:  [quantity, price, buyer_account_ID, seller_account_ID, buyer_funds, buyer_bps, seller_bps, ...]
+  # Outputs: [...]
+  #
+  # Comments: Buyer gets the base quantity and seller the quote notional less its fee, fees of
+  #           notional * bps / 10000 quote rounded down credited to account '0'. The seller order
+  #           locked the base quantity and the buyer order released buyer_funds, which pay the
+  #           notional and the fee of the buyer. What is left goes back to the buyer
+  #
+  dup.1 dup.1 exec.getNotional         # Quote notional                     [notional, quantity, price, buyer_account_ID, ...]
+  movup.2 drop                         #                                    [notional, quantity, buyer_account_ID, seller_account_ID, buyer_funds, buyer_bps, seller_bps, ...]
+  dup movup.6 swap exec.getFee         # Fee of buyer                       [buyer_fee, notional, quantity, buyer_account_ID, seller_account_ID, buyer_funds, seller_bps, ...]
+  dup.1 movup.7 swap exec.getFee       # Fee of seller                      [seller_fee, buyer_fee, notional, quantity, buyer_account_ID, seller_account_ID, buyer_funds, ...]
+  dup.1 dup.1 add push.0.0             # Fees to account '0'                [0, 0, fees, seller_fee, buyer_fee, ...]
+  exec.CreditBalance                   #                                    [seller_fee, buyer_fee, notional, ...]
+  dup.2 swap sub                       # Seller quote                       [seller_quote, buyer_fee, notional, quantity, buyer_account_ID, seller_account_ID, buyer_funds, ...]
+  push.0 movup.6 exec.CreditBalance    # Credit seller                      [buyer_fee, notional, quantity, buyer_account_ID, buyer_funds, ...]
+  add movup.3 swap                     # Buyer pays                         [buyer_pays, buyer_funds, quantity, buyer_account_ID, ...]
+  dup.1 dup.1 gte assert               # Buyer funds are not sufficient     [buyer_pays, buyer_funds, quantity, buyer_account_ID, ...]
+  sub swap movup.2                     # Credit buyer                       [buyer_account_ID, quantity, buyer_quote, ...]
+  exec.CreditBalance                   #                                    [...]
+end
+
+proc.SettleFills
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Settles every fill recorded by RecordFill and the call auction against the
+  #           balances, in fill order, at the maker and taker bps of its fee tier. Orders locked
+  #           their funds beforehand, so settlement only credits accounts
+  #
+  push.1                               # Start from first fill              [fill_number=1, ...]
+  dup mem_load.2147483648 lte          # Any fill left?                     [moreFills_bool, fill_number, ...]
+  while.true
+    dup exec.getFillFundsPointer mem_load  # Load buyer funds               [buyer_funds, fill_number, ...]
+    push.0.0.0.0 dup.5 exec.getFillPointer mem_loadw
+    movup.2 drop movup.2 drop          # Load fill word                     [quantity, price, buyer_funds, fill_number, ...]
+    dup.1 dup.1 exec.getNotional exec.getFeeRates  # Fee tier of fill       [maker_bps, taker_bps, quantity, price, buyer_funds, fill_number, ...]
+    push.0.0.0.0 dup.9 exec.getFillPointer push.1073741824 add mem_loadw
+    movup.3 drop movup.2               # Load account word                  [isTakerBuy_bool, maker_account_ID, taker_account_ID, maker_bps, taker_bps, ...]
+    if.true
+      swap movup.3 movdn.2             # Taker buys                         [taker_account_ID, maker_account_ID, taker_bps, maker_bps, ...]
+    end
+    movup.5 movup.5                    #                                    [quantity, price, buyer_account_ID, seller_account_ID, buyer_bps, seller_bps, buyer_funds, fill_number, ...]
+    movup.6 movdn.4                    #                                    [quantity, price, buyer_account_ID, seller_account_ID, buyer_funds, buyer_bps, seller_bps, fill_number, ...]
+    exec.SettleFill                    # Settle fill                        [fill_number, ...]
+    push.1 add                         # Next fill                          [fill_number++, ...]
+    dup mem_load.2147483648 lte        # More fills?                        [moreFills_bool, fill_number++, ...]
+  end
+  drop
+end
+
+proc.LoadBooks
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  #    Comments: Loads the book of every instrument from advice stack and commits to it.
+  #              Instrument ID of book book_number is kept at address 2113929216 + book_number,
+  #              its tree root before the new orders at 2080374784 + book_number and the
+  #              number of books at 2147483635
+  #
+  push.0.0.5.0
+  adv.push_mapval adv_loadw           # Load number of books                         [number_of_books, 0, 0, 0, ...]
+  movdn.3 drop drop drop              # Isolate number of books                      [number_of_books, ...]
+  dup push.0 gt assert                # At least one book                            [number_of_books, ...]
+  dup push.64 lte assert              # Books must fit in memory                     [number_of_books, ...]
+  mem_store.2147483635                #                                              [...]
+  push.0 loc_store.0                  # Initialize counter                           [...]
+  push.1
+  while.true
+    push.0.0.5 loc_load.0 push.1 add  # Build book key                               [book_number+1, 5, 0, 0, ...]
+    adv.push_mapval adv_loadw         # Load instrument                              [instrument_ID, 0, 0, 0, ...]
+    movdn.3 drop drop drop            # Isolate instrument                           [instrument_ID, ...]
+    loc_load.0 push.2113929216 add mem_store
+    loc_load.0 exec.SelectBook        # Load book                                    [...]
+    exec.SelectBids
+    exec.LoadTree
+    exec.LoadSlots
+    exec.HashTree                     # Commit to book                               [tree_root, ...]
+    loc_load.0 push.2080374784 add mem_storew dropw
+    loc_load.0 push.1 add dup loc_store.0
+    mem_load.2147483635 lt            # Are there more books?                        [more_books_bool, ...]
+  end
+end
+
+proc.PrintBooks
+  # Inputs:  [...]
+  # Outputs: [book_count, instrument_ID_1, old_tree_root_1, new_tree_root_1, node_count_1, change_log_1,
+  #           slot_count_1, slot_change_log_1, ..., instrument_ID_N, ...]
+  #
+  #    Comments: Prints every book but the first one, second book closest to the top
+  #
+  mem_load.2147483635 dup loc_store.0 #                                              [number_of_books, ...]
+  push.1 gt                           # Are there more books?                        [more_books_bool, ...]
+  while.true
+    loc_load.0 push.1 sub dup loc_store.0
+    exec.SelectBook                   # Print last book not printed                  [...]
+    exec.PrintSlotChangeLog
+    exec.PrintChangeLog
+    exec.HashTree                     # New tree root                                [new_tree_root, ...]
+    push.0.0.0.0 exec.getBookNumber push.2080374784 add mem_loadw
+    exec.getBookNumber push.2113929216 add mem_load  # [instrument_ID, old_tree_root, new_tree_root, ...]
+    loc_load.0 push.1 gt              # Are there more books?                        [more_books_bool, ...]
+  end
+  mem_load.2147483635 push.1 sub      # Number of books printed                      [book_count, ...]
+end
+
+proc.LoadIncomingOrder
+  # Inputs:  [order_counter, ...]
+  # Outputs: [instrument_ID, buy-sell_flag, order_word, time_in_force, expiry, post_only, display_size,
+  #           trigger_price, account_ID, stp_policy, ...]
+  #
+  #    Comments: Loads incoming order number order_counter of a batch from advice stack
+  #              Advice Format: [0, 0, 0, instrument_ID, stp_policy, account_ID, trigger_price, display_size, post_only, expiry, time_in_force, buy-sell_flag, quantity, price, time, ID]
+  #
+  push.0.0.1 movup.3                  # Build incoming order key                     [order_counter, 1, 0, 0, ...]
+  adv.push_mapval adv_loadw           # Load instrument                              [instrument_ID, 0, 0, 0, ...]
+  movdn.3 drop drop drop              # Isolate instrument                           [instrument_ID, ...]
+  push.0.0.0.0 adv_loadw              # Load display size to STP policy              [display_size, trigger_price, account_ID, stp_policy, ...]
+  push.0.0.0.0 adv_loadw              # Load flags                                   [buy-sell_flag, time_in_force, expiry, post_only, ...]
+  push.0.0.0.0 adv_loadw              # Load incoming order                          [ID, time, price, quantity, buy-sell_flag, time_in_force, expiry, post_only, ...]
+  movdn.3 movdn.2 swap                # Put into correct format                      [order_word, buy-sell_flag, time_in_force, expiry, post_only, ...]
+  movup.4                             # Flag in front                                [buy-sell_flag, order_word, time_in_force, expiry, post_only, ...]
+  movup.13                            # Instrument in front                          [instrument_ID, buy-sell_flag, order_word, ...]
+end
+
+proc.getLevelQuote
+  # Inputs:  [level_pointer, ...]
+  # Outputs: [price, level_quantity, ...]
+  #
+  # Comments: '0' price and quantity for a NIL level pointer
+  #
+  dup push.0 neq
+  if.true
+    exec.getOrder                      # Load level                      [level_quantity, price, head_slot, tail_slot, ...]
+    movup.2 drop movup.2 drop swap     # Isolate price and quantity      [price, level_quantity, ...]
+  else
+    drop push.0.0
+  end
+end
+
+proc.QuoteTopOfBook
+  # Inputs:  [...]
+  # Outputs: [...]
+  #
+  # Comments: Saves quote word [best_bid, best_ask, spread, 0]. A side without levels quotes '0',
+  #           and the spread is '0' then
+  #
+  exec.SelectBids exec.getBestBidPointer
+  exec.getLevelQuote drop              # Best bid                        [best_bid, ...]
+  exec.SelectAsks exec.getBestAskPointer
+  exec.getLevelQuote drop              # Best ask                        [best_ask, best_bid, ...]
+  dup.1 push.0 neq dup.1 push.0 neq and  # Are both sides quoted?
+  if.true
+    dup dup.2 sub                      # Spread                          [spread, best_ask, best_bid, ...]
+  else
+    push.0                             # No spread                       [0, best_ask, best_bid, ...]
+  end
+  push.0 movdn.3 movdn.2 swap          # Build quote word                [best_bid, best_ask, spread, 0, ...]
+  exec.SaveQuote
+end
+
+proc.QuoteLevels
+  # Inputs:  [level_pointer, isBuySide_bool, depth_levels, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves quote word [isBuySide_bool, price, level_quantity, cumulative_quantity] for
+  #           level_pointer and the levels after it, away from the best price, depth_levels at most.
+  #           The side must be selected
+  #
+  push.0 movdn.3                       # Initialize cumulative quantity  [level_pointer, isBuySide_bool, depth_levels, cumulative_quantity, ...]
+  dup push.0 neq dup.3 push.0 gt and   # Any level left to quote?
+  while.true
+    dup exec.getLevelQuote swap        # Load level                      [level_quantity, price, level_pointer, isBuySide_bool, depth_levels, cumulative_quantity, ...]
+    movup.5 dup.1 add dup movdn.6      # Add to cumulative quantity      [cumulative_quantity', level_quantity, price, level_pointer, isBuySide_bool, depth_levels, cumulative_quantity', ...]
+    swap movup.2 dup.4                 # Build quote word                [isBuySide_bool, price, level_quantity, cumulative_quantity', level_pointer, ...]
+    exec.SaveQuote                     #                                 [level_pointer, isBuySide_bool, depth_levels, cumulative_quantity, ...]
+    dup.1
+    if.true
+      exec.getInOrderPredecessor       # Next lower bid                  [level_pointer', isBuySide_bool, depth_levels, cumulative_quantity, ...]
+    else
+      exec.getInOrderSuccessor         # Next higher ask                 [level_pointer', isBuySide_bool, depth_levels, cumulative_quantity, ...]
+    end
+    movup.2 push.1 sub movdn.2         # One level less to quote         [level_pointer', isBuySide_bool, depth_levels--, cumulative_quantity, ...]
+    dup push.0 neq dup.3 push.0 gt and
+  end
+  dropw
+end
+
+proc.QuoteDepth
+  # Inputs:  [depth_levels, ...]
+  # Outputs: [...]
+  #
+  # Comments: Quotes the top depth_levels levels of the bids, best first, then of the asks
+  #
+  exec.SelectBids
+  dup push.1 exec.getBestBidPointer exec.QuoteLevels
+  exec.SelectAsks
+  push.0 exec.getBestAskPointer exec.QuoteLevels
+end
+
+proc.getQuantityUpTo
+  # Inputs:  [price, isBuySide_bool, ...]
+  # Outputs: [quantity, ...]
+  #
+  # Comments: Sums the quantity of the bids at or above price, or of the asks at or below price.
+  #           Selects the side
+  #
+  push.0 movdn.2                       # Initialize quantity             [price, isBuySide_bool, quantity, ...]
+  dup.1
+  if.true
+    exec.SelectBids exec.getBestBidPointer
+    dup.1 dup.1 exec.isBidAtOrAbovePrice  # Is best bid at or above price?  [isReached_bool, level_pointer, price, isBuySide_bool, quantity, ...]
+  else
+    exec.SelectAsks exec.getBestAskPointer
+    dup.1 dup.1 exec.isLevelAtOrBelowPrice  # Is best ask at or below price?  [isReached_bool, level_pointer, price, isBuySide_bool, quantity, ...]
+  end
+  while.true
+    dup exec.getOrder                  # Load level                      [level_quantity, price, head_slot, tail_slot, level_pointer, ...]
+    movdn.3 drop drop drop             # Isolate level quantity          [level_quantity, level_pointer, price, isBuySide_bool, quantity, ...]
+    movup.4 add movdn.3                # Add to quantity                 [level_pointer, price, isBuySide_bool, quantity', ...]
+    dup.2
+    if.true
+      exec.getInOrderPredecessor       # Next lower bid                  [level_pointer', price, isBuySide_bool, quantity, ...]
+      dup.1 dup.1 exec.isBidAtOrAbovePrice
+    else
+      exec.getInOrderSuccessor         # Next higher ask                 [level_pointer', price, isBuySide_bool, quantity, ...]
+      dup.1 dup.1 exec.isLevelAtOrBelowPrice
+    end
+  end
+  drop drop drop
+end
+
+proc.QuoteQuantityUpTo
+  # Inputs:  [price, ...]
+  # Outputs: [...]
+  #
+  # Comments: Saves quote word [price, bid_quantity, ask_quantity, 0], the quantity a sell order at
+  #           price could hit and a buy order at price could lift
+  #
+  push.1 dup.1 exec.getQuantityUpTo    # Bids at or above price          [bid_quantity, price, ...]
+  push.0 dup.2 exec.getQuantityUpTo    # Asks at or below price          [ask_quantity, bid_quantity, price, ...]
+  push.0 movdn.3 swap movup.2          # Build quote word                [price, bid_quantity, ask_quantity, 0, ...]
+  exec.SaveQuote
+end
+
+proc.QueryBook
+  # Inputs:  [query, price, depth_levels, ...]
+  # Outputs: [...]
+  #
+  # Comments: Answers a read-only query of the selected book with quote words, leaving it untouched.
+  #           Query should be '0' for the top of book, '1' for the depth of the top depth_levels
+  #           levels of each side and '2' for the quantity up to price
+  #
+  dup push.3 lt assert                 # Unknown query
+  dup push.0 eq
+  if.true
+    drop drop drop exec.QuoteTopOfBook
+  else
+    push.1 eq
+    if.true
+      drop exec.QuoteDepth
+    else
+      swap drop exec.QuoteQuantityUpTo
+    end
+  end
+end
+
+proc.ProcessBatch
+  # Inputs:  [batch_commitment, ...]
+  # Outputs: [...]
+  #
+  #    Comments: Inserts or matches every incoming order of a batch, in advice map order, in the book
+  #              of its instrument, and asserts the orders hash to the public commitment. The accumulator
+  #              is kept at address 2147483622
+  #              batch_commitment = hmerge(...hmerge(hmerge(0, message_1), message_2)..., message_N)
+  #              with the message of every order as getOrderMessage hashes it
+  #
+  push.0.0.0.0 mem_storew.2147483622 dropw  # Empty accumulator
+  push.0.0.1.0
+  adv.push_mapval adv_loadw           # Load number of incoming orders               [number_of_incoming_orders, 0, 0, 0, ...]
+  movdn.3 drop drop drop              # Isolate number of incoming orders            [number_of_incoming_orders, ...]
+  push.0                              # Initialize counter                           [0, number_of_incoming_orders, ...]
+  dup.1 dup.1 gt                      # Are there incoming orders?                   [more_orders_bool, 0, number_of_incoming_orders, ...]
+  while.true
+    push.1 add                        # Increment counter                            [order_counter++, number_of_incoming_orders, ...]
+    dup exec.LoadIncomingOrder        # Load incoming order                          [instrument_ID, buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.SelectInstrument             # Select book of order                         [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.getOrderMessage              # Hash order                                   [message, buy-sell_flag, order_word, ...]
+    push.0.0.0.0 mem_loadw.2147483622 swapw
+    hmerge mem_storew.2147483622 dropw  # Accumulate order                           [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.ValidateOrder                # Check instrument parameters                  [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.ValidateBalance              # Check account balance                        [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.VerifyOrderSignature         # Check signature of owner                     [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.NullifyOrder                 # Consume order ID                             [buy-sell_flag, order_word, time_in_force, expiry, order_counter++, ...]
+    exec.ProcessOrder                 # Insert or match order                        [order_counter++, number_of_incoming_orders, ...]
+    exec.TriggerStops                 # Activate triggered stops                     [order_counter++, number_of_incoming_orders, ...]
+    dup.1 dup.1 gt                    # Are there more orders?                       [more_orders_bool, order_counter++, number_of_incoming_orders, ...]
+  end
+  drop drop                           #                                              [batch_commitment, ...]
+  push.0.0.0.0 mem_loadw.2147483622   # Load accumulator                             [accumulator, batch_commitment, ...]
+  assert_eqw                          # Orders match batch commitment                [...]
+end
+
+begin
+  # Operand Stack format: [priority_share_bps, allocation_algorithm, nullifier_root, signatures_flag, instrument_ID, balances_root, price_band, reference_price, max_quantity, min_quantity, lot_size, tick_size,
+  #                       fee_schedule_commitment, stp_policy, account_ID, trigger_price, display_size, post_only, expiry, time_in_force, orderID, time, price, quantity, buy-sell flag]
+  #                    or [priority_share_bps, allocation_algorithm, nullifier_root, signatures_flag, 0, balances_root, instrument_parameters, fee_schedule_commitment, 0, 0, 0, 0, 0, 0, 0, batch_commitment, 2] for batch mode
+  #                    or [priority_share_bps, allocation_algorithm, nullifier_root, signatures_flag, instrument_ID, balances_root, instrument_parameters, fee_schedule_commitment, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3] for call auction mode
+  #                    or [priority_share_bps, allocation_algorithm, nullifier_root, signatures_flag, instrument_ID, balances_root, instrument_parameters, fee_schedule_commitment, 0, 0, 0, 0, 0, 0, 0, 0, depth_levels, price, query, 4] for query mode
+  # Advice stack format: 
+  #            0000000000000000000000000000000005000000000000000000000000000000 : [0,0,0, number_of_books],
+  #            0000000000000000000000000000000005000000000000000100000000000000 : [0,0,0, instrument_ID],
+  #  For the first book, other books replace the second element of the key by their book_number:
+  #            0000000000000000000000000000000000000000000000000000000000000000 : [0,0,0, number_of_orders_in_book],
+  #            0000000000000000000000000000000000000000000000000100000000000000 : [0, 0, 0, node_pointer,  color,  parent_pointer, Lchild_pointer, Rchild_pointer, quantity, price, time, ID], 
+  #            0000000000000000000000000000000003000000000000000000000000000000 : [0,0,0, number_of_queued_orders],
+  #            0000000000000000000000000000000003000000000000000100000000000000 : [0, 0, 0, slot, 0, 0, stp_policy, account_ID, peak, reserve, expiry, next_slot, quantity, price, time, ID],
+  #  Fee schedule, only for a non-zero fee_schedule_commitment:
+  #            0000000000000000000000000000000004000000000000000000000000000000 : [0,0,0, number_of_fee_tiers],
+  #            0000000000000000000000000000000004000000000000000100000000000000 : [min_notional, maker_bps, taker_bps, 0],
+  #  Signed incoming orders only, public key whose top element is the account ID:
+  #            0000000000000000000000000000000006000000000000000100000000000000 : [public_key], under the order ID
+  #            hmerge(public_key, message) : [signature advice], nonce, public key and signature polynomials and their product
+  #  Batch mode only:
+  #            0000000000000000000000000000000001000000000000000000000000000000 : [0,0,0, number_of_incoming_orders],
+  #            0000000000000000000000000000000001000000000000000100000000000000 : [0, 0, 0, instrument_ID, stp_policy, account_ID, trigger_price, display_size, post_only, expiry, time_in_force, buy-sell_flag, quantity, price, time, ID],
+  #
+  # Comments: Buy-sell_flag should be '0' for a sell order, '1' for buy order. Orderbook holds the BUY side under node 0
+  #           and the SELL side under the init node with ID 2. Stops wait in trees keyed by trigger price,
+  #           buy stops under the init node with ID 3 and sell stops under ID 4.
+  #           Every node is a price level [level_quantity, price, head_slot, tail_slot] whose orders
+  #           are queued in time priority from head_slot, linked by next_slot.
+  #           Time_in_force should be '0' for GTC, '1' for IOC, '2' for FOK, '3' for GTD, '4' for market,
+  #           expiry is only read for GTD. Post_only should be '0' for none, '1' to reject, '2' to reprice.
+  #           Display_size shows slices of an iceberg order, '0' shows the whole order.
+  #           Trigger_price makes a stop order, triggered once the last trade price, kept as the quantity
+  #           of node 0, reaches it. A stop with limit price '0' triggers as a market order.
+  #           Account_ID ties an order to an account, orders of the same non-zero account never fill against
+  #           each other. Stp_policy should be '0' to cancel the incoming order, '1' to cancel the resting
+  #           order, '2' to decrement both.
+  #           All seven default to '0' when left out of the operand stack.
+  #           Fee_schedule_commitment is the deepest word of the operand stack, '0' for no fees. Every fill
+  #           pays the maker and taker bps of the last fee tier at or below its notional, in 1/10000 of the
+  #           quote unit. No tier charges more than 100 bps.
+  #           Instrument parameters sit below the fee schedule commitment, past the 16 top elements. Every
+  #           incoming order must have a positive quantity, a multiple of lot_size within min_quantity and
+  #           max_quantity, and a positive price, a multiple of tick_size within price_band of
+  #           reference_price. A '0' parameter is not checked. Invalid orders fail the proof.
+  #           Unfilled GTC and GTD remainders rest at their limit price.
+  #           In batch mode the change log and the fill list cover the whole batch, stops triggered by an
+  #           order are activated right after it. Batch_commitment hashes the incoming orders in advice map
+  #           order, see ProcessBatch, and a batch whose orders do not match it fails the proof.
+  #           In call auction mode the bids and asks resting in the book fill each other at a single
+  #           clearing price, what is left of them keeps resting.
+  #           Every instrument has its own book, listed in book_number order, with its own tree root and
+  #           creation number. Incoming orders go to the book of their instrument_ID, the call auction
+  #           uncrosses the book of instrument_ID. Instrument parameters and fees apply to every book.
+  #           Balances_root commits to the base and quote balances of every account, provided by the merkle
+  #           store as a sparse Merkle tree of depth 64 indexed by account ID, '0' for no balances. Every
+  #           incoming order locks its funds, the base quantity of a sell order or the quote notional of a
+  #           buy order and its fee at 100 bps, and fails the proof if its account runs short. A market buy
+  #           order needs a slippage cap and a buy stop a limit price. Resting orders keep their funds locked
+  #           until they fill or leave the book. Every fill moves the base quantity to the buyer and the
+  #           quote notional to the seller, less their fees in whole quote units credited to account '0',
+  #           and gives the buyer back what its locked funds do not spend.
+  #           Signatures_flag '1' requires a Falcon signature on every incoming order by the public key of
+  #           its account, '0' requires none. Resting orders were signed when they came in.
+  #           Nullifier_root commits to the IDs of consumed orders, provided by the merkle store as a
+  #           sparse Merkle tree of depth 64 indexed by order ID, '0' for no replay protection. An
+  #           incoming order whose ID was consumed fails the proof, others are consumed.
+  #           Best_execution_flag is '1' once the fills of every incoming order are checked against the
+  #           opposite side left after it: none at a worse price than the best level left, and none at its
+  #           price of a maker later than its earliest order. It is '2' in call auction and query mode,
+  #           which it does not apply to.
+  #           Allocation_algorithm selects how a level the incoming order does not exhaust is shared
+  #           among its unexpired orders of other accounts: '0' in time priority, '1' pro rata to their
+  #           displayed quantity and '2' hybrid, priority_share_bps of the incoming quantity in time
+  #           priority first, then pro rata. Pro rata shares are rounded down to whole lots and the
+  #           remainder fills in time priority. Time priority is not checked for best execution then.
+  #           Query mode answers a read-only query of the book of instrument_ID, proven against its tree
+  #           root, with quote words. Query '0' quotes [best_bid, best_ask, spread, 0], '0' for a side
+  #           without levels. Query '1' quotes [isBuySide_bool, price, level_quantity, cumulative_quantity]
+  #           for the top depth_levels levels of the bids, best first, then of the asks. Query '2' quotes
+  #           [price, bid_quantity, ask_quantity, 0], the quantity of the bids at or above price and of the
+  #           asks at or below it.
+  #
+  # Output format: [old_tree_root, new_tree_root, new_balances_root, new_nullifier_root, best_execution_flag,
+  #                 maker_fee_total, taker_fee_total, fill_count, fills,
+  #                 report_count, report_words, quote_count, quote_words,
+  #                 node_count, change_log, slot_count, slot_change_log,
+  #                 book_count, instrument_ID, old_tree_root, new_tree_root, node_count, change_log, slot_count, slot_change_log, ...]
+  #                The roots and change logs on top are those of the first book, book_count the number of other books
+  #
+  # ORDER INSERTION EXAMPLE
+  #
+  # Check the fee schedule against its public commitment
+  movupw.3 exec.LoadFeeSchedule
+
+  # Save instrument parameters from below the fee schedule commitment, then the balances root and
+  # the signatures flag
+  exec.LoadInstrumentParams
+  movupw.3 exec.LoadBalancesRoot
+  movup.13 mem_store.2147483633
+
+  # Save the nullifier root from below the instrument ID, set aside meanwhile
+  movup.12 mem_store.2147483630 movupw.3 exec.LoadNullifierRoot
+  mem_load.2147483630 movdn.12
+
+  # Save the allocation algorithm and priority share from below the nullifier root
+  movup.13 movup.14 swap exec.LoadAllocation
+
+  # Load pre-structured tree of every book from advice_stack and commit to it
+  exec.LoadBooks
+  push.1 mem_store.2147483628
+
+  #### Check batch, auction and query flags
+  dup push.2 eq
+  if.true
+    drop
+    exec.ProcessBatch
+  else
+    dup push.3 eq
+    if.true
+      drop
+      push.2 mem_store.2147483628      # Best execution does not apply to the auction
+      movup.11 exec.SelectInstrument
+      exec.Uncross
+    else
+      dup push.4 eq
+      if.true
+        drop
+        push.2 mem_store.2147483628    # Nor to a query
+        movup.11 exec.SelectInstrument
+        exec.QueryBook
+      else
+        movup.12 exec.SelectInstrument
+        exec.ValidateOrder
+        exec.ValidateBalance
+        exec.VerifyOrderSignature
+        exec.NullifyOrder
+        exec.ProcessOrder
+        exec.TriggerStops
+      end
+    end
+  end
+
+  # Settle the fills against the account balances
+  exec.isSettling
+  if.true
+    exec.SettleFills
+  end
+
+  # Output the other books, then all the queued orders and levels of the first book updated as a
+  # result of the new orders, then the quotes of a query, then every fill
+  exec.PrintBooks
+  push.0 exec.SelectBook
+  exec.PrintSlotChangeLog
+  exec.PrintChangeLog
+  exec.PrintQuotes
+  exec.PrintOrderReports
+  exec.PrintFills
+
+  # Output the best execution flag, the nullifier root after the new orders, then the balances
+  # root after settlement
+  mem_load.2147483628
+  exec.getNullifierRoot
+  exec.getBalancesRoot
+
+  # Output the tree roots before and after the new orders on top
+  exec.HashTree
+  push.0.0.0.0 mem_loadw.2080374784
 
   ###### FOR BUILDING DUMMY STACK ####
   #exec.TempLoadOrders
@@ -0,0 +1,562 @@
+use crate::{
+    utils_balances::Balances,
+    utils_nullifiers::NullifierStore,
+    utils_order::IncomingOrder,
+    utils_output::{DecodedOutputs, Fill, QueueEntry},
+    utils_proof::ProofBundle,
+    utils_signatures::key_account_id,
+};
+use miden_lib::notes::create_p2id_note;
+use miden_objects::{
+    accounts::AccountId,
+    assets::{Asset, FungibleAsset},
+    crypto::rand::RpoRandomCoin,
+    notes::{Note, NoteScript},
+    Felt, StarkField, Word, ZERO,
+};
+use std::collections::BTreeMap;
+
+/// Fungible faucets issuing the base and the quote asset of an instrument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Market {
+    pub base_faucet_id: AccountId,
+    pub quote_faucet_id: AccountId,
+}
+
+/// An input note carrying an incoming order: the asset the sender locks for the order, and the
+/// order itself as note inputs, laid out as the batch advice map value of `LoadIncomingOrder`.
+///
+/// The order is of the account ID of the Falcon public key the sender authenticates with, see
+/// `key_account_id`, the account ID signed orders carry. A buy order locks its quote notional and
+/// its fee at `MAX_FEE_BPS`, a sell order its base quantity, see `Balances::locked_funds`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderNote {
+    pub note: Note,
+}
+
+impl OrderNote {
+    /// Creates the note of an order, its script consuming the asset into the venue account.
+    pub fn new(
+        script: NoteScript,
+        sender: AccountId,
+        asset: FungibleAsset,
+        order: &IncomingOrder,
+        serial_num: Word,
+    ) -> Result<Self, String> {
+        let inputs = order.to_batch_advice_value().into_iter().map(Felt::new).collect::<Vec<_>>();
+        let note =
+            Note::new(script, &inputs, &[asset.into()], serial_num, sender, ZERO).map_err(|e| {
+                format!("failed to create the note of order {} - {e:?}", order.order_id)
+            })?;
+        Ok(Self { note })
+    }
+
+    /// Reads the order back from the note inputs.
+    pub fn order(&self) -> Result<IncomingOrder, String> {
+        let inputs = self.note.inputs().inputs();
+        let inputs: &[Felt; 16] =
+            inputs.try_into().map_err(|_| format!("note holds {} inputs, not 16", inputs.len()))?;
+        let input = |index: usize| inputs[index].as_int();
+        Ok(IncomingOrder {
+            instrument_id: input(3),
+            stp_policy: input(4),
+            account_id: input(5),
+            trigger_price: input(6),
+            display_size: input(7),
+            post_only: input(8),
+            expiry: input(9),
+            time_in_force: input(10),
+            buy_sell_flag: input(11),
+            quantity: input(12),
+            price: input(13),
+            time: input(14),
+            order_id: input(15),
+        })
+    }
+
+    /// Returns the single fungible asset of the note.
+    pub fn asset(&self) -> Result<FungibleAsset, String> {
+        let mut assets = self.note.assets().iter();
+        match (assets.next(), assets.next()) {
+            (Some(Asset::Fungible(asset)), None) => Ok(*asset),
+            _ => Err("note does not hold a single fungible asset".to_string()),
+        }
+    }
+
+    /// Checks the order is of the account of `public_key`, the key the sender authenticates
+    /// with, and the note locks what the order can spend.
+    pub fn validate(
+        &self,
+        market: &Market,
+        public_key: Word,
+    ) -> Result<(IncomingOrder, FungibleAsset), String> {
+        let order = self.order()?;
+        let key_account_id = key_account_id(public_key);
+        if order.account_id != key_account_id {
+            return Err(format!(
+                "order {} is of account {}, not of the key of sender {}",
+                order.order_id,
+                order.account_id,
+                u64::from(self.note.metadata().sender())
+            ));
+        }
+
+        let asset = self.asset()?;
+        let (faucet_id, needed) = locked_amount(market, &order, order.quantity, order.price)?;
+        if asset.faucet_id() != faucet_id || asset.amount() < needed {
+            return Err(format!(
+                "order {} needs {needed} of faucet {} but the note holds {} of faucet {}",
+                order.order_id,
+                u64::from(faucet_id),
+                asset.amount(),
+                u64::from(asset.faucet_id())
+            ));
+        }
+        Ok((order, asset))
+    }
+}
+
+/// A pay-to-ID output note of the venue, paying the counterparty of a fill or refunding what an
+/// order no longer needs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentNote {
+    pub note: Note,
+    /// ID of the order the payment settles or refunds.
+    pub order_id: u64,
+    pub is_refund: bool,
+}
+
+/// An order consumed from a note, the account that sent the note and the asset it still holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedOrder {
+    pub order: IncomingOrder,
+    pub sender: AccountId,
+    pub asset: FungibleAsset,
+}
+
+/// Local chain of a single market, standing in for the rollup in tests.
+///
+/// Submitted order notes wait until they are consumed by a proven batch. Their assets are then
+/// held by the orders as `Balances` locks funds, and every proven fill pays them out to the
+/// counterparties as output notes: the base quantity to the buyer, and the quote notional less
+/// its fee to the seller, the buyer paying its fee on top. Whatever an order no longer locks,
+/// from price improvement or a remainder that does not rest, is refunded to the sender of its
+/// note. Consumed notes are nullified by order ID, as `NullifyOrder` does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockChain {
+    pub market: Market,
+    /// Account of the venue consuming the order notes, sending the payment notes and keeping
+    /// the fees.
+    pub venue_id: AccountId,
+    /// Falcon public keys the accounts authenticate with, as their wallets keep them, by account.
+    pub public_keys: BTreeMap<u64, Word>,
+    pub pending_notes: Vec<OrderNote>,
+    pub nullifiers: NullifierStore,
+    /// Orders of consumed notes, by order ID.
+    pub locked: BTreeMap<u64, LockedOrder>,
+    /// Orders resting in the book of the market, by queue slot.
+    pub slots: BTreeMap<u64, u64>,
+    pub output_notes: Vec<PaymentNote>,
+    /// Quote fees of the fills kept by the venue, as account 0 gets them in `Balances`.
+    pub collected_fees: u64,
+}
+
+impl MockChain {
+    pub fn new(market: Market, venue_id: AccountId) -> Self {
+        Self {
+            market,
+            venue_id,
+            public_keys: BTreeMap::new(),
+            pending_notes: Vec::new(),
+            nullifiers: NullifierStore::default(),
+            locked: BTreeMap::new(),
+            slots: BTreeMap::new(),
+            output_notes: Vec::new(),
+            collected_fees: 0,
+        }
+    }
+
+    /// Records the public key an account authenticates with.
+    pub fn register(&mut self, account: AccountId, public_key: Word) {
+        self.public_keys.insert(u64::from(account), public_key);
+    }
+
+    /// Accepts an order note, failing for an invalid note or an already consumed order.
+    pub fn submit(&mut self, note: OrderNote) -> Result<(), String> {
+        let (order, _) = self.validate(&note)?;
+        if self.nullifiers.contains(order.order_id)
+            || self.pending_notes.iter().any(|pending| pending.order() == Ok(order.clone()))
+        {
+            return Err(format!("order {} was already submitted", order.order_id));
+        }
+        self.pending_notes.push(note);
+        Ok(())
+    }
+
+    /// Consumes the pending notes, returning their orders for the next batch.
+    pub fn consume_pending(&mut self) -> Result<Vec<IncomingOrder>, String> {
+        let notes = std::mem::take(&mut self.pending_notes);
+        let orders = notes.iter().map(|note| self.validate(note)).collect::<Result<Vec<_>, _>>()?;
+        let order_ids = orders.iter().map(|(order, _)| order.order_id).collect::<Vec<_>>();
+        self.nullifiers.nullify_all(&order_ids)?;
+
+        for (note, (order, asset)) in notes.iter().zip(&orders) {
+            let sender = note.note.metadata().sender();
+            self.locked.insert(
+                order.order_id,
+                LockedOrder { order: order.clone(), sender, asset: *asset },
+            );
+        }
+        Ok(orders.into_iter().map(|(order, _)| order).collect())
+    }
+
+    /// Validates a note against the public key of its sender.
+    fn validate(&self, note: &OrderNote) -> Result<(IncomingOrder, FungibleAsset), String> {
+        let sender = u64::from(note.note.metadata().sender());
+        let public_key =
+            self.public_keys.get(&sender).ok_or(format!("account {sender} has no public key"))?;
+        note.validate(&self.market, *public_key)
+    }
+
+    /// Verifies a proven batch, then pays out its fills and refunds.
+    pub fn apply_proof(&mut self, bundle: &ProofBundle) -> Result<(), String> {
+        bundle.verify()?;
+        self.apply_outputs(&bundle.decode()?)
+    }
+
+    /// Pays out every fill of the outputs from the assets held by their orders, as `SettleFill`
+    /// settles it: the base quantity to the buyer, and the quote notional less the seller fee to
+    /// the seller. The buyer pays its fee on top, and the venue keeps both.
+    ///
+    /// Then refunds every order of the batch what it holds beyond the funds the quantity still
+    /// resting locks at its resting price, see `QueueEntry`: all of it once it left the book, or
+    /// never rested.
+    pub fn apply_outputs(&mut self, outputs: &DecodedOutputs) -> Result<(), String> {
+        let mut chain = self.clone();
+        for fill in &outputs.fills {
+            chain.settle(fill)?;
+        }
+
+        // Orders resting after the batch, by order ID, and the orders of the batch
+        let mut resting = BTreeMap::new();
+        let mut touched = outputs.reports.iter().map(|report| report.order_id).collect::<Vec<_>>();
+        for entry in &outputs.queue_log {
+            if let Some(order_id) = chain.slots.remove(&entry.slot) {
+                resting.remove(&order_id);
+                touched.push(order_id);
+            }
+            if !entry.is_nil() {
+                chain.slots.insert(entry.slot, entry.order_id);
+                resting.insert(entry.order_id, entry);
+                touched.push(entry.order_id);
+            }
+        }
+        for order_id in touched {
+            chain.refund(order_id, resting.get(&order_id).copied())?;
+        }
+
+        *self = chain;
+        Ok(())
+    }
+
+    fn settle(&mut self, fill: &Fill) -> Result<(), String> {
+        let maker = self
+            .locked
+            .get(&fill.maker_id)
+            .ok_or(format!("order {} was not consumed from a note", fill.maker_id))?;
+        let (buyer_id, seller_id, buyer_fee, seller_fee) = if maker.order.buy_sell_flag == 1 {
+            (fill.maker_id, fill.taker_id, fill.maker_fee, fill.taker_fee)
+        } else {
+            (fill.taker_id, fill.maker_id, fill.taker_fee, fill.maker_fee)
+        };
+        // Fees are in whole quote units rounded down, as `Balances::settle` takes them
+        let (buyer_fee, seller_fee) = (buyer_fee / 10000, seller_fee / 10000);
+
+        let overflow = || format!("settlement of {} at {} overflows", fill.quantity, fill.price);
+        let notional = fill.quantity.checked_mul(fill.price).ok_or_else(overflow)?;
+        let buyer_pays = notional.checked_add(buyer_fee).ok_or_else(overflow)?;
+        let seller_gets = notional.checked_sub(seller_fee).ok_or_else(overflow)?;
+        let buyer = self.take(buyer_id, self.market.quote_faucet_id, buyer_pays)?;
+        let seller = self.take(seller_id, self.market.base_faucet_id, fill.quantity)?;
+        self.pay(&buyer, self.market.base_faucet_id, fill.quantity, false)?;
+        self.pay(&seller, self.market.quote_faucet_id, seller_gets, false)?;
+        self.collected_fees += buyer_fee + seller_fee;
+        Ok(())
+    }
+
+    /// Refunds what an order holds beyond its resting entry, if any, and forgets it once it
+    /// holds nothing more.
+    fn refund(&mut self, order_id: u64, resting: Option<&QueueEntry>) -> Result<(), String> {
+        let Some(LockedOrder { order, asset, .. }) = self.locked.get(&order_id).cloned() else {
+            return Ok(());
+        };
+        let needed = match resting {
+            Some(entry) => {
+                let quantity = entry
+                    .quantity
+                    .checked_add(entry.reserve)
+                    .ok_or(format!("resting quantity of order {order_id} overflows"))?;
+                locked_amount(&self.market, &order, quantity, entry.price)?.1
+            }
+            None => 0,
+        };
+
+        let refund = asset.amount().saturating_sub(needed);
+        if refund > 0 {
+            let order = self.take(order_id, asset.faucet_id(), refund)?;
+            self.pay(&order, asset.faucet_id(), refund, true)?;
+        }
+        if needed == 0 {
+            self.locked.remove(&order_id);
+        }
+        Ok(())
+    }
+
+    /// Takes an amount out of the asset held by an order, returning the order.
+    fn take(
+        &mut self,
+        order_id: u64,
+        faucet_id: AccountId,
+        amount: u64,
+    ) -> Result<LockedOrder, String> {
+        let locked = self
+            .locked
+            .get_mut(&order_id)
+            .ok_or(format!("order {order_id} was not consumed from a note"))?;
+        if locked.asset.faucet_id() != faucet_id || locked.asset.amount() < amount {
+            return Err(format!("order {order_id} holds too little to pay {amount}"));
+        }
+        locked.asset = fungible_asset(faucet_id, locked.asset.amount() - amount)?;
+        Ok(locked.clone())
+    }
+
+    /// Sends a pay-to-ID note of an asset from the venue to the sender of the note of an order.
+    fn pay(
+        &mut self,
+        LockedOrder { order, sender, .. }: &LockedOrder,
+        faucet_id: AccountId,
+        amount: u64,
+        is_refund: bool,
+    ) -> Result<(), String> {
+        let asset = fungible_asset(faucet_id, amount)?;
+        // Serial numbers only need to differ between the notes of the venue
+        let seed =
+            [Felt::new(order.order_id), Felt::new(self.output_notes.len() as u64), ZERO, ZERO];
+        let note =
+            create_p2id_note(self.venue_id, *sender, vec![asset.into()], RpoRandomCoin::new(seed))
+                .map_err(|e| {
+                    format!("failed to create the payment note of order {} - {e:?}", order.order_id)
+                })?;
+        self.output_notes.push(PaymentNote { note, order_id: order.order_id, is_refund });
+        Ok(())
+    }
+}
+
+/// Returns the faucet and the amount an order locks for a quantity at a price, see
+/// `Balances::locked_funds`.
+fn locked_amount(
+    market: &Market,
+    order: &IncomingOrder,
+    quantity: u64,
+    price: u64,
+) -> Result<(AccountId, u64), String> {
+    let is_buy = order.buy_sell_flag == 1;
+    let faucet_id = if is_buy { market.quote_faucet_id } else { market.base_faucet_id };
+    let funds = Balances::locked_funds(is_buy, quantity, price)
+        .map_err(|e| format!("order {}: {e}", order.order_id))?;
+    Ok((faucet_id, funds))
+}
+
+fn fungible_asset(faucet_id: AccountId, amount: u64) -> Result<FungibleAsset, String> {
+    FungibleAsset::new(faucet_id, amount).map_err(|e| {
+        format!("failed to create {amount} of faucet {} - {e:?}", u64::from(faucet_id))
+    })
+}
+
+#[test]
+fn test_mock_chain() {
+    let account = |id: u64| AccountId::try_from(id).unwrap();
+    let market = Market {
+        base_faucet_id: account(0b1010011100 << 54),
+        quote_faucet_id: account(0b1010011101 << 54),
+    };
+    let (buyer_id, seller_id, venue_id) =
+        (0b0110011011 << 54, 0b0110111011 << 54, account(0b0110011111 << 54));
+    let public_key = |account_id: u64| [ZERO, ZERO, ZERO, Felt::new(account_id)];
+    let quote = |amount: u64| fungible_asset(market.quote_faucet_id, amount).unwrap();
+    let base = |amount: u64| fungible_asset(market.base_faucet_id, amount).unwrap();
+    let code = miden_objects::assembly::ProgramAst::parse("begin push.0 drop end").unwrap();
+    let assembler = miden_lib::transaction::TransactionKernel::assembler();
+    let (script, _) = NoteScript::new(code, &assembler).unwrap();
+    let note = |sender: u64, asset: FungibleAsset, order: &IncomingOrder| {
+        let serial_num = [Felt::new(order.order_id), ZERO, ZERO, ZERO];
+        OrderNote::new(script.clone(), account(sender), asset, order, serial_num).unwrap()
+    };
+    let bid = IncomingOrder {
+        quantity: 10,
+        price: 50,
+        order_id: 1,
+        buy_sell_flag: 1,
+        account_id: 1,
+        ..Default::default()
+    };
+    let ask = IncomingOrder {
+        quantity: 6,
+        price: 48,
+        order_id: 2,
+        buy_sell_flag: 0,
+        account_id: 2,
+        ..bid
+    };
+
+    let mut chain = MockChain::new(market, venue_id);
+    assert!(chain.submit(note(buyer_id, quote(505), &bid)).is_err());
+    chain.register(account(buyer_id), public_key(1));
+    chain.register(account(seller_id), public_key(2));
+    // The bid locks its notional and its fee at `MAX_FEE_BPS`, and only the key of account 1 can
+    // send it
+    assert!(chain.submit(note(buyer_id, quote(504), &bid)).is_err());
+    assert!(chain.submit(note(seller_id, quote(505), &bid)).is_err());
+    chain.submit(note(buyer_id, quote(505), &bid)).unwrap();
+    chain.submit(note(seller_id, base(6), &ask)).unwrap();
+    assert_eq!(chain.consume_pending().unwrap().len(), 2);
+    assert!(chain.submit(note(seller_id, base(6), &ask)).is_err());
+
+    // The ask rests, then the bid takes it at 48 and rests the 4 left at 50
+    let entry = |slot, quantity, price, order_id| QueueEntry {
+        slot,
+        quantity,
+        price,
+        order_id,
+        time: 1,
+        ..Default::default()
+    };
+    let ask_rests = DecodedOutputs { queue_log: vec![entry(1, 6, 48, 2)], ..Default::default() };
+    chain.apply_outputs(&ask_rests).unwrap();
+    assert!(chain.output_notes.is_empty());
+    let fill = Fill {
+        quantity: 6,
+        price: 48,
+        maker_id: 2,
+        taker_id: 1,
+        maker_fee: 28_800,
+        taker_fee: 57_600,
+        ..Default::default()
+    };
+    let bid_takes = DecodedOutputs {
+        fills: vec![fill],
+        queue_log: vec![QueueEntry { slot: 1, ..Default::default() }, entry(2, 4, 50, 1)],
+        ..Default::default()
+    };
+    chain.apply_outputs(&bid_takes).unwrap();
+
+    let payments = chain
+        .output_notes
+        .iter()
+        .map(|payment| {
+            let asset = OrderNote { note: payment.note.clone() }.asset().unwrap();
+            (payment.order_id, payment.is_refund, asset)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(payments, [(1, false, base(6)), (2, false, quote(286)), (1, true, quote(10))]);
+    assert_eq!(chain.locked.get(&1).map(|locked| locked.asset), Some(quote(202)));
+    assert_eq!(chain.collected_fees, 7);
+    assert!(!chain.locked.contains_key(&2));
+
+    // The rest of the bid leaves the book unfilled
+    let bid_cancelled = DecodedOutputs {
+        queue_log: vec![QueueEntry { slot: 2, ..Default::default() }],
+        ..Default::default()
+    };
+    chain.apply_outputs(&bid_cancelled).unwrap();
+    let refund = chain.output_notes.last().unwrap();
+    assert_eq!((refund.order_id, refund.is_refund), (1, true));
+    assert_eq!(OrderNote { note: refund.note.clone() }.asset(), Ok(quote(202)));
+    assert!(chain.locked.is_empty());
+}
+
+#[test]
+fn test_prove_note_orders() {
+    use crate::{
+        prove_execution,
+        utils_allocation::Allocation,
+        utils_fees::{FeeSchedule, FeeTier},
+        utils_instrument::InstrumentParams,
+        utils_orderbook::OrderBook,
+        utils_sequencer::Sequencer,
+        utils_signatures::{signature_advice_map, OrderSigner},
+    };
+
+    let account = |id: u64| AccountId::try_from(id).unwrap();
+    let market = Market {
+        base_faucet_id: account(0b1010011100 << 54),
+        quote_faucet_id: account(0b1010011101 << 54),
+    };
+    let (buyer_id, seller_id) = (account(0b0110011011 << 54), account(0b0110111011 << 54));
+    let (buyer, seller) = (OrderSigner::new().unwrap(), OrderSigner::new().unwrap());
+    let code = miden_objects::assembly::ProgramAst::parse("begin push.0 drop end").unwrap();
+    let assembler = miden_lib::transaction::TransactionKernel::assembler();
+    let (script, _) = NoteScript::new(code, &assembler).unwrap();
+    let note = |sender: AccountId, asset: FungibleAsset, order: &IncomingOrder| {
+        let serial_num = [Felt::new(order.order_id), ZERO, ZERO, ZERO];
+        OrderNote::new(script.clone(), sender, asset, order, serial_num).unwrap()
+    };
+    let ask = IncomingOrder {
+        quantity: 60,
+        price: 48,
+        time: 1,
+        order_id: 1,
+        account_id: seller.account_id(),
+        instrument_id: 1,
+        ..Default::default()
+    };
+    let bid = IncomingOrder {
+        quantity: 100,
+        price: 50,
+        time: 2,
+        order_id: 2,
+        buy_sell_flag: 1,
+        account_id: buyer.account_id(),
+        ..ask
+    };
+
+    let mut chain = MockChain::new(market, account(0b0110011111 << 54));
+    chain.register(buyer_id, buyer.public_key());
+    chain.register(seller_id, seller.public_key());
+    let params = InstrumentParams { tick_size: 1, lot_size: 1, ..Default::default() };
+    let fees =
+        FeeSchedule { tiers: vec![FeeTier { min_notional: 0, maker_bps: 10, taker_bps: 20 }] };
+    let books = BTreeMap::from([(1, OrderBook::default())]);
+    let mut sequencer = Sequencer::new(books, params, fees, Allocation::default());
+    sequencer.signatures = true;
+
+    // The ask rests, then the bid takes it at 48 and rests the 40 left at 50
+    let notes = [
+        (seller_id, &seller, fungible_asset(market.base_faucet_id, 60).unwrap(), &ask),
+        (buyer_id, &buyer, fungible_asset(market.quote_faucet_id, 5050).unwrap(), &bid),
+    ];
+    for (sender, signer, asset, order) in notes {
+        chain.submit(note(sender, asset, order)).unwrap();
+        let orders = chain.consume_pending().unwrap();
+        assert_eq!(orders, [order.clone()]);
+
+        let signature = signer.sign_order(order).unwrap();
+        let advice = signature_advice_map(signer.public_key(), order, &signature).unwrap();
+        let transition = sequencer.sequence(order, &advice).unwrap();
+        let bundle =
+            ProofBundle::new(&transition.inputs, prove_execution(&transition.inputs).unwrap());
+        chain.apply_proof(&bundle).unwrap();
+    }
+
+    let payments = chain
+        .output_notes
+        .iter()
+        .map(|payment| {
+            let asset = OrderNote { note: payment.note.clone() }.asset().unwrap();
+            (payment.order_id, payment.is_refund, asset.amount())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(payments, [(2, false, 60), (1, false, 2878), (2, true, 145)]);
+    assert_eq!(chain.locked.get(&2).map(|locked| locked.asset.amount()), Some(2020));
+    assert_eq!(chain.collected_fees, 7);
+}
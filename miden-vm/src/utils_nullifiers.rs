@@ -0,0 +1,88 @@
+use crate::utils_orderbook::stack_word;
+use miden_vm::{crypto::SimpleSmt, math::StarkField, Word};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Operand stack position, counted from the top, of the nullifier root. The root sits below the
+/// signatures flag and is '0' for no replay protection.
+pub const NULLIFIER_ROOT_POSITION: usize = 28;
+
+/// Off-circuit mirror of the consumed orders committed to by the nullifier root.
+///
+/// Nullifiers are the leaves [1, 0, 0, 0] of a sparse Merkle tree of depth 64 indexed by order
+/// ID, the tree built from the `sparse_merkle_tree` merkle data of an input file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NullifierStore {
+    pub order_ids: BTreeSet<u64>,
+}
+
+impl NullifierStore {
+    /// Returns the nullifier root the program reads below the signatures flag, in stack order.
+    pub fn root(&self) -> Result<[u64; 4], String> {
+        let leaves = self.leaves().collect::<Vec<_>>();
+        let smt = SimpleSmt::with_leaves(SimpleSmt::MAX_DEPTH, leaves)
+            .map_err(|e| format!("failed to build nullifier tree - {e}"))?;
+
+        let root = Word::from(smt.root());
+        Ok([root[3].as_int(), root[2].as_int(), root[1].as_int(), root[0].as_int()])
+    }
+
+    /// Reads the nullifiers from the `sparse_merkle_tree` merkle data of an input file.
+    pub fn from_merkle_data(merkle_data: &[(u64, String)]) -> Self {
+        Self { order_ids: merkle_data.iter().map(|(order_id, _)| *order_id).collect() }
+    }
+
+    /// Returns the `sparse_merkle_tree` merkle data holding the nullifiers, leaves in hex.
+    pub fn to_merkle_data(&self) -> Vec<(u64, String)> {
+        self.leaves()
+            .map(|(order_id, leaf)| {
+                let hex =
+                    leaf.iter().map(|element| format!("{:016x}", element.as_int().swap_bytes()));
+                (order_id, hex.collect())
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, order_id: u64) -> bool {
+        self.order_ids.contains(&order_id)
+    }
+
+    /// Mirrors `NullifyOrder`: consumes an order, failing if it was already consumed.
+    pub fn nullify(&mut self, order_id: u64) -> Result<(), String> {
+        if !self.order_ids.insert(order_id) {
+            return Err(format!("order {order_id} was already consumed"));
+        }
+        Ok(())
+    }
+
+    /// Consumes the orders of a batch, leaving the store untouched if any was already consumed,
+    /// including twice within the batch.
+    pub fn nullify_all(&mut self, order_ids: &[u64]) -> Result<(), String> {
+        let mut nullified = self.clone();
+        for order_id in order_ids {
+            nullified.nullify(*order_id)?;
+        }
+
+        *self = nullified;
+        Ok(())
+    }
+
+    /// Returns the leaves of the nullifier tree, by order ID.
+    fn leaves(&self) -> impl Iterator<Item = (u64, Word)> + '_ {
+        self.order_ids.iter().map(|order_id| (*order_id, stack_word([1, 0, 0, 0])))
+    }
+}
+
+#[test]
+fn test_nullify() {
+    let mut store = NullifierStore::default();
+    store.nullify(3).unwrap();
+
+    assert!(store.contains(3));
+    assert!(store.nullify(3).is_err());
+    assert!(store.nullify_all(&[4, 5, 4]).is_err());
+    assert!(!store.contains(4));
+
+    store.nullify_all(&[4, 5]).unwrap();
+    assert_eq!(store.order_ids.len(), 3);
+}
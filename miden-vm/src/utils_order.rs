@@ -0,0 +1,220 @@
+use crate::{
+    utils_instrument::{stack_element, INSTRUMENT_ID_POSITION},
+    utils_orderbook::stack_word,
+};
+use miden_vm::{
+    crypto::{Rpo256, RpoDigest},
+    math::{Felt, StarkField},
+    Word,
+};
+use serde::{Deserialize, Serialize};
+
+/// Time in force of a market order. It sweeps the opposite levels as an IOC order, its price
+/// being a slippage cap in bps of the best opposite price on arrival, '0' for none.
+pub const MARKET_ORDER: u64 = 4;
+
+/// Operand stack position, counted from the top, of the batch commitment in batch mode, right
+/// below the batch mode flag.
+pub const BATCH_COMMITMENT_POSITION: usize = 1;
+
+/// The inputs of an incoming order, as read by `LoadIncomingOrder` or from the top of the operand
+/// stack.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IncomingOrder {
+    pub quantity: u64,
+    pub price: u64,
+    pub time: u64,
+    pub order_id: u64,
+    pub buy_sell_flag: u64,
+    pub time_in_force: u64,
+    pub expiry: u64,
+    pub post_only: u64,
+    pub display_size: u64,
+    pub trigger_price: u64,
+    pub account_id: u64,
+    pub stp_policy: u64,
+    pub instrument_id: u64,
+}
+
+impl IncomingOrder {
+    /// Reads the order from an operand stack listed bottom first, as in input files.
+    pub fn from_operand_stack(operand_stack: &[u64]) -> Self {
+        let input = |position: usize| stack_element(operand_stack, position);
+        Self {
+            buy_sell_flag: input(0),
+            quantity: input(1),
+            price: input(2),
+            time: input(3),
+            order_id: input(4),
+            time_in_force: input(5),
+            expiry: input(6),
+            post_only: input(7),
+            display_size: input(8),
+            trigger_price: input(9),
+            account_id: input(10),
+            stp_policy: input(11),
+            instrument_id: input(INSTRUMENT_ID_POSITION),
+        }
+    }
+
+    /// Returns the 12 order inputs on top of the operand stack, listed bottom first. The
+    /// instrument ID goes at `INSTRUMENT_ID_POSITION`, below the instrument parameters.
+    pub fn to_operand_stack(&self) -> Vec<u64> {
+        vec![
+            self.stp_policy,
+            self.account_id,
+            self.trigger_price,
+            self.display_size,
+            self.post_only,
+            self.expiry,
+            self.time_in_force,
+            self.order_id,
+            self.time,
+            self.price,
+            self.quantity,
+            self.buy_sell_flag,
+        ]
+    }
+
+    /// Reads the order from its batch advice map value, see `to_batch_advice_value`.
+    pub fn from_batch_advice_value(value: &[u64]) -> Result<Self, String> {
+        let value: &[u64; 16] =
+            value.try_into().map_err(|_| format!("batch order of {} elements", value.len()))?;
+        Ok(Self {
+            quantity: value[12],
+            price: value[13],
+            time: value[14],
+            order_id: value[15],
+            buy_sell_flag: value[11],
+            time_in_force: value[10],
+            expiry: value[9],
+            post_only: value[8],
+            display_size: value[7],
+            trigger_price: value[6],
+            account_id: value[5],
+            stp_policy: value[4],
+            instrument_id: value[3],
+        })
+    }
+
+    /// Returns the batch advice map value `LoadIncomingOrder` reads for the order.
+    pub fn to_batch_advice_value(&self) -> Vec<u64> {
+        vec![
+            0,
+            0,
+            0,
+            self.instrument_id,
+            self.stp_policy,
+            self.account_id,
+            self.trigger_price,
+            self.display_size,
+            self.post_only,
+            self.expiry,
+            self.time_in_force,
+            self.buy_sell_flag,
+            self.quantity,
+            self.price,
+            self.time,
+            self.order_id,
+        ]
+    }
+
+    /// Mirrors `PriceMarketOrder`: the limit price a market order sweeps the opposite levels up to,
+    /// given the best opposite price on arrival. Without opposite levels it is 0. Fails where
+    /// `MulDiv` fails, on a best price, slippage cap or price move beyond u32.
+    pub fn market_limit_price(&self, best_price: Option<u64>) -> Result<u64, String> {
+        let is_buy = self.buy_sell_flag == 1;
+        match best_price {
+            Some(best_price) if self.price != 0 => {
+                let overflow = || {
+                    format!(
+                        "slippage cap of {} bps on {best_price} overflows in order {}",
+                        self.price, self.order_id
+                    )
+                };
+                let max_move = best_price
+                    .checked_mul(self.price)
+                    .filter(|_| best_price <= u32::MAX as u64 && self.price <= u32::MAX as u64)
+                    .map(|product| product / 10000)
+                    .filter(|max_move| *max_move <= u32::MAX as u64)
+                    .ok_or_else(overflow)?;
+                if is_buy {
+                    best_price.checked_add(max_move).ok_or_else(overflow)
+                } else {
+                    Ok(best_price.saturating_sub(max_move))
+                }
+            }
+            // Market price
+            Some(_) if is_buy => Ok(Felt::MODULUS - 1),
+            _ => Ok(0),
+        }
+    }
+
+    /// Returns the message signed by the owner of the order, as hashed by `VerifyOrderSignature`.
+    pub fn message(&self) -> Word {
+        let order_word = stack_word([self.quantity, self.price, self.time, self.order_id]);
+        let flags_word =
+            stack_word([self.buy_sell_flag, self.time_in_force, self.expiry, self.post_only]);
+        let options_word =
+            stack_word([self.display_size, self.trigger_price, self.account_id, self.stp_policy]);
+        let instrument_word = stack_word([self.instrument_id, 0, 0, 0]);
+
+        let message = [flags_word, options_word, instrument_word]
+            .into_iter()
+            .fold(RpoDigest::from(order_word), |hash, word| Rpo256::merge(&[hash, word.into()]));
+        message.into()
+    }
+}
+
+/// Returns the commitment asserted by `ProcessBatch` for the incoming orders of a batch, in stack
+/// order: the messages of the orders, see `IncomingOrder::message`, hashed in batch order.
+pub fn batch_commitment(orders: &[IncomingOrder]) -> [u64; 4] {
+    let commitment = orders
+        .iter()
+        .fold(RpoDigest::default(), |hash, order| Rpo256::merge(&[hash, order.message().into()]));
+
+    let commitment = Word::from(commitment);
+    [commitment[3].as_int(), commitment[2].as_int(), commitment[1].as_int(), commitment[0].as_int()]
+}
+
+#[test]
+fn test_operand_stack() {
+    let order = IncomingOrder {
+        quantity: 20,
+        price: 48,
+        time: 7,
+        order_id: 3,
+        buy_sell_flag: 1,
+        account_id: 9,
+        instrument_id: 2,
+        ..Default::default()
+    };
+
+    let mut operand_stack = vec![0; INSTRUMENT_ID_POSITION + 1 - order.to_operand_stack().len()];
+    operand_stack[0] = order.instrument_id;
+    operand_stack.extend(order.to_operand_stack());
+    assert_eq!(IncomingOrder::from_operand_stack(&operand_stack), order);
+    let batch_value = order.to_batch_advice_value();
+    assert_eq!(IncomingOrder::from_batch_advice_value(&batch_value), Ok(order.clone()));
+
+    let moved = IncomingOrder { account_id: 10, ..order.clone() };
+    assert_ne!(moved.message(), order.message());
+    // The batch commitment binds the orders and their order, an empty batch commits to zero
+    let batch = [order.clone(), moved.clone()];
+    assert_ne!(batch_commitment(&batch), batch_commitment(&[moved, order.clone()]));
+    assert_eq!(batch_commitment(&[]), [0; 4]);
+
+    // Up to 2% above the best ask of 50, or down to 2% below the best bid
+    let market = IncomingOrder { price: 200, time_in_force: MARKET_ORDER, ..order };
+    assert_eq!(market.market_limit_price(Some(50)), Ok(51));
+    let sell = IncomingOrder { buy_sell_flag: 0, ..market.clone() };
+    assert_eq!(sell.market_limit_price(Some(50)), Ok(49));
+    let uncapped = IncomingOrder { price: 0, ..market.clone() };
+    assert_eq!(uncapped.market_limit_price(Some(50)), Ok(Felt::MODULUS - 1));
+
+    // An empty opposite side leaves nothing to sweep, capped or not
+    assert_eq!(market.market_limit_price(None), Ok(0));
+    assert_eq!(uncapped.market_limit_price(None), Ok(0));
+    assert_eq!(sell.market_limit_price(None), Ok(0));
+    assert!(market.market_limit_price(Some(u64::MAX)).is_err());
+}
@@ -0,0 +1,462 @@
+use crate::{
+    utils_input::InputFile,
+    utils_output::{ChangeLogEntry, DecodedOutputs, QueueEntry},
+};
+use miden_vm::{
+    crypto::{Rpo256, RpoDigest},
+    math::{Felt, StarkField},
+    utils::collections::BTreeMap,
+    Word,
+};
+
+/// Advice map namespace holding the nodes of the book, see `LoadTree`.
+pub const BOOK_NAMESPACE: u64 = 0;
+
+/// Advice map namespace holding the incoming orders of a batch, see `LoadIncomingOrder`.
+pub const BATCH_NAMESPACE: u64 = 1;
+
+/// Advice map namespace holding the orders queued at the price levels, see `LoadSlots`.
+pub const QUEUE_NAMESPACE: u64 = 3;
+
+/// Advice map namespace holding the fee tiers, see `LoadFeeSchedule`.
+pub const FEE_NAMESPACE: u64 = 4;
+
+/// Advice map namespace holding the instrument ID of every book, see `LoadBooks`.
+pub const INSTRUMENT_NAMESPACE: u64 = 5;
+
+/// Advice map namespace holding the public key of the owner of every signed incoming order, see
+/// `VerifyOrderSignature`.
+pub const SIGNER_NAMESPACE: u64 = 6;
+
+/// ID of the init node of the BUY side, see `SelectBids`.
+pub const BUY_SIDE_ID: u64 = 0;
+
+/// ID of the init node of the SELL side, see `SelectAsks`.
+pub const SELL_SIDE_ID: u64 = 2;
+
+/// ID of the init node of the tree of buy stops, see `ParkIncomingStop`.
+pub const BUY_STOP_SIDE_ID: u64 = 3;
+
+/// ID of the init node of the tree of sell stops, see `ParkIncomingStop`.
+pub const SELL_STOP_SIDE_ID: u64 = 4;
+
+/// Color of the init nodes, which the trees of every side hang off.
+const INIT_COLOR: u64 = 2;
+
+/// Largest number of books `LoadBooks` fits in memory.
+pub const MAX_BOOKS: usize = 64;
+
+/// Returns the hex advice map key of word [0, 0, namespace, index].
+pub fn advice_map_key(namespace: u64, index: u64) -> String {
+    book_advice_map_key(0, namespace, index)
+}
+
+/// Returns the hex advice map key of word [0, book_number, namespace, index]. Book and queue
+/// entries of the book with number `book_number` live under these keys.
+pub fn book_advice_map_key(book_number: u64, namespace: u64, index: u64) -> String {
+    [0, book_number, namespace, index]
+        .iter()
+        .map(|element| format!("{:016x}", element.swap_bytes()))
+        .collect()
+}
+
+// ORDER BOOK
+// ================================================================================================
+
+/// Off-circuit mirror of the rbBST kept in Miden memory.
+///
+/// Nodes are price levels keyed by node pointer and share the layout printed by
+/// `PrintChangeLog`. The BUY side hangs off init node 0, the SELL side off the init node with
+/// order ID 2, and the buy and sell stops, keyed by trigger price, off the init nodes with order
+/// IDs 3 and 4. Init node 0 keeps the last trade price as its quantity. The orders queued at
+/// each level are keyed by slot and share the layout printed by `PrintSlotChangeLog`.
+///
+/// Node pointers count from the base address of the book, so the books of every instrument
+/// share the layout of a single book.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderBook {
+    /// Creation number stored at `mem_load.0` once the tree is loaded.
+    pub creation_counter: u64,
+    pub nodes: BTreeMap<u64, ChangeLogEntry>,
+    /// Slot counter stored at `getSlotPointer(0)` once the queues are loaded.
+    pub slot_counter: u64,
+    pub slots: BTreeMap<u64, QueueEntry>,
+}
+
+impl OrderBook {
+    /// Builds the book from the advice map of an input file.
+    pub fn from_inputs(inputs: &str) -> Result<Self, String> {
+        let input_file: InputFile = serde_json::from_str(inputs).map_err(|e| e.to_string())?;
+        let advice_map = input_file.advice_map.ok_or("input file has no advice map")?;
+
+        Self::from_advice_map(&advice_map)
+    }
+
+    /// Builds the book from advice map entries in the format expected by `LoadTree` and
+    /// `LoadSlots`.
+    pub fn from_advice_map(advice_map: &BTreeMap<String, Vec<u64>>) -> Result<Self, String> {
+        Self::from_book_advice_map(advice_map, 0)
+    }
+
+    /// Builds the book with number `book_number` from advice map entries in the format expected
+    /// by `LoadTree` and `LoadSlots`.
+    fn from_book_advice_map(
+        advice_map: &BTreeMap<String, Vec<u64>>,
+        book_number: u64,
+    ) -> Result<Self, String> {
+        let advice_map_key =
+            |namespace: u64, index: u64| book_advice_map_key(book_number, namespace, index);
+        let count_key = advice_map_key(BOOK_NAMESPACE, 0);
+        let creation_counter = advice_map
+            .get(&count_key)
+            .and_then(|value| value.get(3).copied())
+            .ok_or("advice map has no number of orders in book")?;
+
+        let mut nodes = BTreeMap::new();
+        for index in 1..=creation_counter {
+            let key = advice_map_key(BOOK_NAMESPACE, index);
+            let value = advice_map.get(&key).ok_or(format!("advice map has no node `{key}`"))?;
+            if value.len() != 12 {
+                return Err(format!("node `{key}` holds {} values instead of 12", value.len()));
+            }
+            let node = ChangeLogEntry {
+                node_pointer: value[3],
+                color: value[4],
+                parent_pointer: value[5],
+                left_child_pointer: value[6],
+                right_child_pointer: value[7],
+                quantity: value[8],
+                price: value[9],
+                time: value[10],
+                order_id: value[11],
+            };
+            nodes.insert(node.node_pointer, node);
+        }
+
+        let count_key = advice_map_key(QUEUE_NAMESPACE, 0);
+        let slot_counter = advice_map
+            .get(&count_key)
+            .and_then(|value| value.get(3).copied())
+            .ok_or("advice map has no number of queued orders")?;
+
+        let mut slots = BTreeMap::new();
+        for index in 1..=slot_counter {
+            let key = advice_map_key(QUEUE_NAMESPACE, index);
+            let value =
+                advice_map.get(&key).ok_or(format!("advice map has no queued order `{key}`"))?;
+            if value.len() != 16 {
+                return Err(format!(
+                    "queued order `{key}` holds {} values instead of 16",
+                    value.len()
+                ));
+            }
+            let entry = QueueEntry {
+                slot: value[3],
+                account_id: value[7],
+                stp_policy: value[6],
+                next_slot: value[11],
+                expiry: value[10],
+                reserve: value[9],
+                peak: value[8],
+                quantity: value[12],
+                price: value[13],
+                time: value[14],
+                order_id: value[15],
+            };
+            slots.insert(entry.slot, entry);
+        }
+
+        Ok(Self { creation_counter, nodes, slot_counter, slots })
+    }
+
+    /// Returns the advice map entries `LoadTree` and `LoadSlots` expect for this book.
+    ///
+    /// `LoadTree` uses the number of orders as creation number, so every node slot up to the
+    /// creation number is written, empty slots included. Queued orders are written the same way
+    /// up to the slot counter. The book is listed as the only book, of instrument 0.
+    pub fn to_advice_map(&self) -> BTreeMap<String, Vec<u64>> {
+        let mut advice_map = self.to_book_advice_map(0);
+        advice_map.extend(book_list_advice_map(&[0]));
+        advice_map
+    }
+
+    /// Returns the advice map entries `LoadTree` and `LoadSlots` expect for this book when it has
+    /// number `book_number`.
+    fn to_book_advice_map(&self, book_number: u64) -> BTreeMap<String, Vec<u64>> {
+        let advice_map_key =
+            |namespace: u64, index: u64| book_advice_map_key(book_number, namespace, index);
+        let number_of_orders = self.creation_counter + 1;
+
+        let mut advice_map = BTreeMap::new();
+        advice_map.insert(advice_map_key(BOOK_NAMESPACE, 0), vec![0, 0, 0, number_of_orders]);
+        for creation_number in 0..number_of_orders {
+            let node_pointer = creation_number * 3 + 1;
+            let node = self
+                .nodes
+                .get(&node_pointer)
+                .cloned()
+                .unwrap_or(ChangeLogEntry { node_pointer, ..Default::default() });
+            advice_map.insert(
+                advice_map_key(BOOK_NAMESPACE, creation_number + 1),
+                vec![
+                    0,
+                    0,
+                    0,
+                    node.node_pointer,
+                    node.color,
+                    node.parent_pointer,
+                    node.left_child_pointer,
+                    node.right_child_pointer,
+                    node.quantity,
+                    node.price,
+                    node.time,
+                    node.order_id,
+                ],
+            );
+        }
+
+        advice_map.insert(advice_map_key(QUEUE_NAMESPACE, 0), vec![0, 0, 0, self.slot_counter]);
+        for slot in 1..=self.slot_counter {
+            let entry =
+                self.slots.get(&slot).cloned().unwrap_or(QueueEntry { slot, ..Default::default() });
+            advice_map.insert(
+                advice_map_key(QUEUE_NAMESPACE, slot),
+                vec![
+                    0,
+                    0,
+                    0,
+                    entry.slot,
+                    0,
+                    0,
+                    entry.stp_policy,
+                    entry.account_id,
+                    entry.peak,
+                    entry.reserve,
+                    entry.expiry,
+                    entry.next_slot,
+                    entry.quantity,
+                    entry.price,
+                    entry.time,
+                    entry.order_id,
+                ],
+            );
+        }
+        advice_map
+    }
+
+    /// Applies the change logs of a proven transition to the book. Node pointers start at 1, so
+    /// a log holding pointer 0 is rejected and leaves the book as it was.
+    pub fn apply_change_log(
+        &mut self,
+        change_log: &[ChangeLogEntry],
+        queue_log: &[QueueEntry],
+    ) -> Result<(), String> {
+        if change_log.iter().any(|node| node.node_pointer == 0) {
+            return Err("change log holds node pointer 0".to_string());
+        }
+        for node in change_log {
+            self.creation_counter = self.creation_counter.max((node.node_pointer - 1) / 3);
+            self.nodes.insert(node.node_pointer, node.clone());
+        }
+        for entry in queue_log {
+            self.slot_counter = self.slot_counter.max(entry.slot);
+            self.slots.insert(entry.slot, entry.clone());
+        }
+        Ok(())
+    }
+
+    /// Returns the last trade price kept by init node 0, 0 before the first trade.
+    pub fn last_trade_price(&self) -> u64 {
+        self.nodes.get(&1).map_or(0, |node| node.quantity)
+    }
+
+    /// Returns the quantity resting at every price level of the side whose init node has ID
+    /// `side_id`, by price.
+    pub fn depth(&self, side_id: u64) -> BTreeMap<u64, u64> {
+        let mut depth = BTreeMap::new();
+        for (node_pointer, node) in &self.nodes {
+            if !node.is_nil()
+                && node.color != INIT_COLOR
+                && self.side_id(*node_pointer) == Some(side_id)
+            {
+                *depth.entry(node.price).or_default() += node.quantity;
+            }
+        }
+        depth
+    }
+
+    /// Returns the side, `BUY_SIDE_ID` or `SELL_SIDE_ID`, of the resting order with ID `order_id`:
+    /// the side of the price level whose queue holds it.
+    pub fn order_side_id(&self, order_id: u64) -> Option<u64> {
+        self.queued_orders()
+            .into_iter()
+            .find(|(_, entry)| entry.order_id == order_id)
+            .map(|(side_id, _)| side_id)
+            .filter(|side_id| *side_id == BUY_SIDE_ID || *side_id == SELL_SIDE_ID)
+    }
+
+    /// Returns every queued order with the ID of the init node of its tree, walking the queue of
+    /// every price level from its head slot.
+    pub fn queued_orders(&self) -> Vec<(u64, &QueueEntry)> {
+        let mut queued_orders = Vec::new();
+        for (node_pointer, node) in &self.nodes {
+            if node.is_nil() || node.color == INIT_COLOR {
+                continue;
+            }
+            let Some(side_id) = self.side_id(*node_pointer) else {
+                continue;
+            };
+            let mut slot = node.time;
+            for _ in 0..self.slots.len() {
+                match self.slots.get(&slot).filter(|entry| !entry.is_nil()) {
+                    Some(entry) => {
+                        queued_orders.push((side_id, entry));
+                        slot = entry.next_slot;
+                    }
+                    None => break,
+                }
+            }
+        }
+        queued_orders
+    }
+
+    /// Returns the ID of the init node a node hangs off, walking up its parents. Init node 0,
+    /// the first node, is the BUY side whatever its ID.
+    fn side_id(&self, mut node_pointer: u64) -> Option<u64> {
+        for _ in 0..self.nodes.len() {
+            let node = self.nodes.get(&node_pointer)?;
+            if node.color == INIT_COLOR {
+                return Some(if node_pointer == 1 { BUY_SIDE_ID } else { node.order_id });
+            }
+            node_pointer = node.parent_pointer;
+        }
+        None
+    }
+
+    /// Returns the tree root computed by `HashTree`, in stack order.
+    pub fn root(&self) -> [u64; 4] {
+        let mut root = RpoDigest::default();
+        for node in self.nodes.values().filter(|node| !node.is_nil()) {
+            let coordinate_word = stack_word([
+                node.color,
+                node.parent_pointer,
+                node.left_child_pointer,
+                node.right_child_pointer,
+            ]);
+            let order_word = stack_word([node.quantity, node.price, node.time, node.order_id]);
+
+            let node_hash = Rpo256::merge(&[order_word.into(), coordinate_word.into()]);
+            root = Rpo256::merge(&[root, node_hash]);
+        }
+        for entry in self.slots.values().filter(|entry| !entry.is_nil()) {
+            let link_word = stack_word([entry.next_slot, entry.expiry, entry.reserve, entry.peak]);
+            let order_word = stack_word([entry.quantity, entry.price, entry.time, entry.order_id]);
+
+            let account_word = stack_word([entry.account_id, entry.stp_policy, 0, 0]);
+
+            let order_hash = Rpo256::merge(&[order_word.into(), link_word.into()]);
+            let slot_hash = Rpo256::merge(&[order_hash, account_word.into()]);
+            root = Rpo256::merge(&[root, slot_hash]);
+        }
+
+        let root = Word::from(root);
+        [root[3].as_int(), root[2].as_int(), root[1].as_int(), root[0].as_int()]
+    }
+}
+
+// BOOKS
+// ================================================================================================
+
+/// Returns the advice map entries `LoadBooks` expects for the book of every instrument.
+///
+/// Books are numbered in instrument ID order, so the book of the smallest instrument ID is the
+/// first book, whose roots and change logs are printed on top of the outputs. At most `MAX_BOOKS`
+/// books fit in memory.
+pub fn books_to_advice_map(books: &BTreeMap<u64, OrderBook>) -> BTreeMap<String, Vec<u64>> {
+    let instrument_ids = books.keys().copied().collect::<Vec<_>>();
+
+    let mut advice_map = book_list_advice_map(&instrument_ids);
+    for (book_number, book) in books.values().enumerate() {
+        advice_map.extend(book.to_book_advice_map(book_number as u64));
+    }
+    advice_map
+}
+
+/// Builds the book of every instrument from advice map entries in the format expected by
+/// `LoadBooks`.
+pub fn books_from_advice_map(
+    advice_map: &BTreeMap<String, Vec<u64>>,
+) -> Result<BTreeMap<u64, OrderBook>, String> {
+    instrument_ids(advice_map)?
+        .into_iter()
+        .enumerate()
+        .map(|(book_number, instrument_id)| {
+            OrderBook::from_book_advice_map(advice_map, book_number as u64)
+                .map(|book| (instrument_id, book))
+                .map_err(|e| format!("instrument {instrument_id}: {e}"))
+        })
+        .collect()
+}
+
+/// Returns the instrument IDs of the books listed in the advice map, in book number order.
+pub fn instrument_ids(advice_map: &BTreeMap<String, Vec<u64>>) -> Result<Vec<u64>, String> {
+    let number_of_books = advice_map
+        .get(&advice_map_key(INSTRUMENT_NAMESPACE, 0))
+        .and_then(|value| value.get(3).copied())
+        .ok_or("advice map has no number of books")?;
+    if number_of_books == 0 || number_of_books as usize > MAX_BOOKS {
+        return Err(format!(
+            "advice map lists {number_of_books} books instead of 1 to {MAX_BOOKS}"
+        ));
+    }
+
+    (1..=number_of_books)
+        .map(|index| {
+            let key = advice_map_key(INSTRUMENT_NAMESPACE, index);
+            advice_map
+                .get(&key)
+                .and_then(|value| value.get(3).copied())
+                .ok_or(format!("advice map has no instrument `{key}`"))
+        })
+        .collect()
+}
+
+/// Applies the change logs of a proven transition to the book of every instrument.
+///
+/// The change logs on top of the outputs belong to the first book, the smallest instrument ID.
+pub fn apply_outputs(
+    books: &mut BTreeMap<u64, OrderBook>,
+    outputs: &DecodedOutputs,
+) -> Result<(), String> {
+    let first_book = books.values_mut().next().ok_or("no book to apply the outputs to")?;
+    first_book.apply_change_log(&outputs.change_log, &outputs.queue_log)?;
+
+    for book_outputs in &outputs.books {
+        books
+            .get_mut(&book_outputs.instrument_id)
+            .ok_or(format!("instrument {} has no book", book_outputs.instrument_id))?
+            .apply_change_log(&book_outputs.change_log, &book_outputs.queue_log)?;
+    }
+    Ok(())
+}
+
+/// Returns the advice map entries listing the books of `instrument_ids`, in book number order.
+fn book_list_advice_map(instrument_ids: &[u64]) -> BTreeMap<String, Vec<u64>> {
+    let mut advice_map = BTreeMap::new();
+    advice_map.insert(
+        advice_map_key(INSTRUMENT_NAMESPACE, 0),
+        vec![0, 0, 0, instrument_ids.len() as u64],
+    );
+    for (book_number, instrument_id) in instrument_ids.iter().enumerate() {
+        advice_map.insert(
+            advice_map_key(INSTRUMENT_NAMESPACE, book_number as u64 + 1),
+            vec![0, 0, 0, *instrument_id],
+        );
+    }
+    advice_map
+}
+
+/// Converts a word listed in stack order, top element first, into a `Word`.
+pub(crate) fn stack_word(elements: [u64; 4]) -> Word {
+    [Felt::new(elements[3]), Felt::new(elements[2]), Felt::new(elements[1]), Felt::new(elements[0])]
+}
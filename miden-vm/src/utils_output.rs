@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of stack elements printed for every node of the change log:
+/// node_pointer, coordinate_word and order_word.
+pub const CHANGE_LOG_ENTRY_LEN: usize = 9;
+
+/// Number of stack elements printed for every queued order of the slot change log:
+/// slot, account_word, link_word and order_word.
+pub const QUEUE_LOG_ENTRY_LEN: usize = 13;
+
+/// Number of stack elements printed for every fill: a fill word, maker fee and taker fee.
+pub const FILL_LEN: usize = 6;
+
+/// Number of stack elements printed for every incoming order: a single report word.
+pub const ORDER_REPORT_LEN: usize = 4;
+
+/// Number of stack elements printed for every quote of a query: a single quote word.
+pub const QUOTE_LEN: usize = 4;
+
+// CHANGE LOG
+// ================================================================================================
+
+/// A node printed by `PrintChangeLog`.
+///
+/// Every node is a price level: `quantity` is the quantity resting at `price`, while `time` and
+/// `order_id` hold the head and tail slots of the queue of orders at that price.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ChangeLogEntry {
+    pub node_pointer: u64,
+    pub color: u64,
+    pub parent_pointer: u64,
+    pub left_child_pointer: u64,
+    pub right_child_pointer: u64,
+    pub quantity: u64,
+    pub price: u64,
+    pub time: u64,
+    pub order_id: u64,
+}
+
+impl ChangeLogEntry {
+    /// Mirrors `isNIL`: a node slot is empty when its order word is zero.
+    pub fn is_nil(&self) -> bool {
+        self.quantity == 0 && self.price == 0 && self.time == 0 && self.order_id == 0
+    }
+
+    fn from_elements(elements: &[u64]) -> Self {
+        Self {
+            node_pointer: elements[0],
+            color: elements[1],
+            parent_pointer: elements[2],
+            left_child_pointer: elements[3],
+            right_child_pointer: elements[4],
+            quantity: elements[5],
+            price: elements[6],
+            time: elements[7],
+            order_id: elements[8],
+        }
+    }
+}
+
+// QUEUE LOG
+// ================================================================================================
+
+/// Self-trade prevention cancels the incoming order.
+pub const STP_CANCEL_NEWEST: u64 = 0;
+
+/// Self-trade prevention cancels the resting order.
+pub const STP_CANCEL_OLDEST: u64 = 1;
+
+/// Self-trade prevention decrements both orders by the smaller quantity.
+pub const STP_DECREMENT_BOTH: u64 = 2;
+
+/// A queued order printed by `PrintSlotChangeLog`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct QueueEntry {
+    pub slot: u64,
+    /// Account the order belongs to, 0 for no account.
+    pub account_id: u64,
+    /// One of `STP_CANCEL_NEWEST`, `STP_CANCEL_OLDEST` or `STP_DECREMENT_BOTH`.
+    pub stp_policy: u64,
+    /// Slot of the order queued behind this one at the same price, 0 for the tail.
+    pub next_slot: u64,
+    /// Time after which a GTD order is skipped and removed while matching, 0 for no expiry.
+    pub expiry: u64,
+    /// Hidden quantity of an iceberg order, refilled into `quantity` by slices of `peak`.
+    pub reserve: u64,
+    pub peak: u64,
+    pub quantity: u64,
+    pub price: u64,
+    pub time: u64,
+    pub order_id: u64,
+}
+
+impl QueueEntry {
+    /// Mirrors `isSlotNIL`: a slot is empty when its order word is zero.
+    pub fn is_nil(&self) -> bool {
+        self.quantity == 0 && self.price == 0 && self.time == 0 && self.order_id == 0
+    }
+
+    fn from_elements(elements: &[u64]) -> Self {
+        Self {
+            slot: elements[0],
+            account_id: elements[1],
+            stp_policy: elements[2],
+            next_slot: elements[5],
+            expiry: elements[6],
+            reserve: elements[7],
+            peak: elements[8],
+            quantity: elements[9],
+            price: elements[10],
+            time: elements[11],
+            order_id: elements[12],
+        }
+    }
+}
+
+// FILLS
+// ================================================================================================
+
+/// A fill printed by `PrintFills`. The taker is the incoming order, the maker a resting order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Fill {
+    pub quantity: u64,
+    pub price: u64,
+    pub maker_id: u64,
+    pub taker_id: u64,
+    /// Notional times the maker bps of the fee tier, in 1/10000 of the quote unit.
+    pub maker_fee: u64,
+    /// Notional times the taker bps of the fee tier, in 1/10000 of the quote unit.
+    pub taker_fee: u64,
+}
+
+impl Fill {
+    fn from_elements(elements: &[u64]) -> Self {
+        Self {
+            quantity: elements[0],
+            price: elements[1],
+            maker_id: elements[2],
+            taker_id: elements[3],
+            maker_fee: elements[4],
+            taker_fee: elements[5],
+        }
+    }
+}
+
+// ORDER REPORTS
+// ================================================================================================
+
+/// The order was matched and its GTC or GTD remainder rested at its limit price.
+pub const ORDER_ACCEPTED: u64 = 0;
+
+/// A FOK order could not fill entirely and left the book untouched.
+pub const ORDER_KILLED: u64 = 1;
+
+/// A post-only order would have crossed and was rejected.
+pub const ORDER_REJECTED: u64 = 2;
+
+/// A post-only order would have crossed and rests one price step behind the best opposite level.
+pub const ORDER_REPRICED: u64 = 3;
+
+/// A stop order waits in a stop tree at its trigger price, reported in place of its price.
+pub const ORDER_STOPPED: u64 = 4;
+
+/// A self-trade cancelled the rest of the order under `STP_CANCEL_NEWEST`.
+pub const ORDER_SELF_TRADE_CANCELLED: u64 = 5;
+
+/// The outcome of an incoming order printed by `PrintOrderReports`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OrderReport {
+    pub order_id: u64,
+    /// One of `ORDER_ACCEPTED`, `ORDER_KILLED`, `ORDER_REJECTED`, `ORDER_REPRICED`,
+    /// `ORDER_STOPPED` or `ORDER_SELF_TRADE_CANCELLED`.
+    pub status: u64,
+    /// Price the order was processed at, after repricing. The volume-weighted average fill price of
+    /// a market order, 0 without fills.
+    pub price: u64,
+    /// Quantity left resting in the book, 0 for an IOC remainder. The unfilled quantity of a
+    /// market order.
+    pub resting_quantity: u64,
+}
+
+impl OrderReport {
+    fn from_elements(elements: &[u64]) -> Self {
+        Self {
+            order_id: elements[0],
+            status: elements[1],
+            price: elements[2],
+            resting_quantity: elements[3],
+        }
+    }
+}
+
+// PROGRAM OUTPUTS
+// ================================================================================================
+
+/// Number of stack elements printed on top of the outputs: the old and the new tree roots.
+pub const ROOTS_LEN: usize = 8;
+
+/// Number of stack elements printed below the tree roots: the balances root after settlement.
+pub const BALANCES_ROOT_LEN: usize = 4;
+
+/// Number of stack elements printed below the balances root: the nullifier root after the new
+/// orders.
+pub const NULLIFIER_ROOT_LEN: usize = 4;
+
+/// Number of stack elements printed below the nullifier root: the best execution flag.
+pub const BEST_EXECUTION_FLAG_LEN: usize = 1;
+
+/// Number of stack elements printed below the best execution flag: the maker and taker fee
+/// totals.
+pub const FEE_TOTALS_LEN: usize = 2;
+
+/// Number of stack elements printed on top of every other book: its instrument ID and its old
+/// and new tree roots.
+pub const BOOK_HEADER_LEN: usize = 9;
+
+/// Tree roots and change logs of a book other than the first one, printed by `PrintBooks`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BookOutputs {
+    pub instrument_id: u64,
+    pub old_root: [u64; 4],
+    pub new_root: [u64; 4],
+    pub change_log: Vec<ChangeLogEntry>,
+    pub queue_log: Vec<QueueEntry>,
+}
+
+/// Decoded stack outputs: [old_tree_root, new_tree_root, new_balances_root, new_nullifier_root,
+/// best_execution_flag, maker_fee_total, taker_fee_total,
+/// fill_count, fills..., report_count, reports..., quote_count, quotes..., node_count, nodes...,
+/// slot_count, slots..., book_count, books...]
+///
+/// Tree roots and change logs on top belong to the first book, `books` holds the other ones.
+/// Roots are kept in stack order, top element first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DecodedOutputs {
+    pub old_root: [u64; 4],
+    pub new_root: [u64; 4],
+    /// Root of the account balances after settlement, 0 without balances.
+    pub new_balances_root: [u64; 4],
+    /// Root of the nullifiers of consumed orders after the new orders, 0 without replay
+    /// protection.
+    pub new_nullifier_root: [u64; 4],
+    /// Whether every fill of the incoming orders passed `CheckBestExecution`: no better price
+    /// left resting and no earlier order at the same price bypassed. `None` in call auction and
+    /// query mode, which best execution does not apply to.
+    pub best_execution: Option<bool>,
+    /// Sum of the maker fees of all fills.
+    pub maker_fee_total: u64,
+    /// Sum of the taker fees of all fills.
+    pub taker_fee_total: u64,
+    pub fills: Vec<Fill>,
+    pub reports: Vec<OrderReport>,
+    /// Quote words answering a query mode query, see `BookQuery`.
+    pub quotes: Vec<[u64; 4]>,
+    pub change_log: Vec<ChangeLogEntry>,
+    pub queue_log: Vec<QueueEntry>,
+    pub books: Vec<BookOutputs>,
+}
+
+/// Decodes the stack outputs of the program, for a single order or a batch.
+pub fn decode_outputs(stack: &[u64]) -> Result<DecodedOutputs, String> {
+    let header_len = ROOTS_LEN
+        + BALANCES_ROOT_LEN
+        + NULLIFIER_ROOT_LEN
+        + BEST_EXECUTION_FLAG_LEN
+        + FEE_TOTALS_LEN;
+    if stack.len() <= header_len {
+        return Err(format!("stack output holds only {} elements", stack.len()));
+    }
+    let old_root = [stack[0], stack[1], stack[2], stack[3]];
+    let new_root = [stack[4], stack[5], stack[6], stack[7]];
+    let new_balances_root = [stack[8], stack[9], stack[10], stack[11]];
+    let new_nullifier_root = [stack[12], stack[13], stack[14], stack[15]];
+    let best_execution = match stack[16] {
+        0 => Some(false),
+        1 => Some(true),
+        2 => None,
+        flag => return Err(format!("best execution flag is {flag} instead of 0, 1 or 2")),
+    };
+    let maker_fee_total = stack[header_len - FEE_TOTALS_LEN];
+    let taker_fee_total = stack[header_len - FEE_TOTALS_LEN + 1];
+
+    let (fills, rest) = decode_counted(&stack[header_len..], FILL_LEN, "fills")?;
+    let (reports, rest) = decode_counted(rest, ORDER_REPORT_LEN, "order reports")?;
+    let (quotes, rest) = decode_counted(rest, QUOTE_LEN, "quotes")?;
+    let (change_log, rest) = decode_counted(rest, CHANGE_LOG_ENTRY_LEN, "nodes")?;
+    let (queue_log, rest) = decode_counted(rest, QUEUE_LOG_ENTRY_LEN, "queued orders")?;
+
+    let book_count = *rest.first().ok_or("stack output holds no number of books")?;
+    let mut rest = &rest[1..];
+    let mut books = Vec::new();
+    for _ in 0..book_count {
+        if rest.len() < BOOK_HEADER_LEN {
+            return Err(format!("stack output holds only {} elements of a book", rest.len()));
+        }
+        let (book_change_log, book_rest) =
+            decode_counted(&rest[BOOK_HEADER_LEN..], CHANGE_LOG_ENTRY_LEN, "nodes")?;
+        let (book_queue_log, book_rest) =
+            decode_counted(book_rest, QUEUE_LOG_ENTRY_LEN, "queued orders")?;
+        books.push(BookOutputs {
+            instrument_id: rest[0],
+            old_root: [rest[1], rest[2], rest[3], rest[4]],
+            new_root: [rest[5], rest[6], rest[7], rest[8]],
+            change_log: book_change_log.into_iter().map(ChangeLogEntry::from_elements).collect(),
+            queue_log: book_queue_log.into_iter().map(QueueEntry::from_elements).collect(),
+        });
+        rest = book_rest;
+    }
+
+    Ok(DecodedOutputs {
+        old_root,
+        new_root,
+        new_balances_root,
+        new_nullifier_root,
+        best_execution,
+        maker_fee_total,
+        taker_fee_total,
+        fills: fills.into_iter().map(Fill::from_elements).collect(),
+        reports: reports.into_iter().map(OrderReport::from_elements).collect(),
+        quotes: quotes.into_iter().map(|quote| [quote[0], quote[1], quote[2], quote[3]]).collect(),
+        change_log: change_log.into_iter().map(ChangeLogEntry::from_elements).collect(),
+        queue_log: queue_log.into_iter().map(QueueEntry::from_elements).collect(),
+        books,
+    })
+}
+
+/// Splits a list printed as [count, entries...] off the top of the stack.
+fn decode_counted<'a>(
+    stack: &'a [u64],
+    entry_len: usize,
+    name: &str,
+) -> Result<(Vec<&'a [u64]>, &'a [u64]), String> {
+    let count = *stack.first().ok_or(format!("stack output holds no number of {name}"))?;
+    let end = usize::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_mul(entry_len))
+        .and_then(|len| len.checked_add(1));
+    let Some(end) = end.filter(|end| *end <= stack.len()) else {
+        return Err(format!(
+            "stack output holds {} elements but {count} {name} were printed",
+            stack.len()
+        ));
+    };
+
+    Ok((stack[1..end].chunks_exact(entry_len).collect(), &stack[end..]))
+}
+
+#[test]
+fn test_decode_outputs() {
+    let stack = vec![
+        1, 2, 3, 4, // old root
+        5, 6, 7, 8, // new root
+        21, 22, 23, 24, // new balances root
+        31, 32, 33, 34, // new nullifier root
+        1,  // best execution flag
+        1460, 2920, // fee totals
+        2,    // fill count
+        10, 49, 1001, 2002, 980, 1960, // fill 1
+        5, 48, 1003, 2002, 480, 960, // fill 2
+        1,   // report count
+        2002, 3, 49, 25, // report
+        0,  // quote count
+        1,  // node count
+        274, 0, 0, 0, 0, 5, 48, 12, 12, // change log
+        1,  // slot count
+        12, 77, 1, 0, 0, 0, 1688480000, 40, 5, 5, 48, 1688473234, 1003, // queue log
+        1,    // book count
+        7,    // instrument
+        11, 12, 13, 14, // old root
+        15, 16, 17, 18, // new root
+        1,  // node count
+        4, 0, 0, 0, 0, 60, 51, 1, 1, // change log
+        0, // slot count
+    ];
+
+    let outputs = decode_outputs(&stack).unwrap();
+
+    assert_eq!(outputs.old_root, [1, 2, 3, 4]);
+    assert_eq!(outputs.new_root, [5, 6, 7, 8]);
+    assert_eq!(outputs.new_balances_root, [21, 22, 23, 24]);
+    assert_eq!(outputs.new_nullifier_root, [31, 32, 33, 34]);
+    assert_eq!(outputs.best_execution, Some(true));
+    assert_eq!(outputs.maker_fee_total, 1460);
+    assert_eq!(outputs.fills.len(), 2);
+    assert_eq!(
+        outputs.fills[1],
+        Fill {
+            quantity: 5,
+            price: 48,
+            maker_id: 1003,
+            taker_id: 2002,
+            maker_fee: 480,
+            taker_fee: 960
+        }
+    );
+    assert_eq!(outputs.reports.len(), 1);
+    assert_eq!(outputs.reports[0].status, ORDER_REPRICED);
+    assert_eq!(outputs.reports[0].resting_quantity, 25);
+    assert_eq!(outputs.change_log.len(), 1);
+    assert_eq!(outputs.change_log[0].node_pointer, 274);
+    assert_eq!(outputs.change_log[0].order_id, 12);
+    assert_eq!(outputs.queue_log.len(), 1);
+    assert_eq!(outputs.queue_log[0].slot, 12);
+    assert_eq!(outputs.queue_log[0].account_id, 77);
+    assert_eq!(outputs.queue_log[0].stp_policy, STP_CANCEL_OLDEST);
+    assert_eq!(outputs.queue_log[0].expiry, 1688480000);
+    assert_eq!(outputs.queue_log[0].reserve, 40);
+    assert_eq!(outputs.queue_log[0].order_id, 1003);
+    assert_eq!(outputs.books.len(), 1);
+    assert_eq!(outputs.books[0].instrument_id, 7);
+    assert_eq!(outputs.books[0].new_root, [15, 16, 17, 18]);
+    assert_eq!(outputs.books[0].change_log[0].quantity, 60);
+    assert!(outputs.books[0].queue_log.is_empty());
+
+    let mut auction_stack = stack.clone();
+    auction_stack[16] = 2;
+    assert_eq!(decode_outputs(&auction_stack).unwrap().best_execution, None);
+    auction_stack[16] = 3;
+    assert!(decode_outputs(&auction_stack).is_err());
+
+    // A count no stack could hold fails instead of overflowing
+    let mut overflowing_stack = stack.clone();
+    overflowing_stack[19] = u64::MAX;
+    assert!(decode_outputs(&overflowing_stack).is_err());
+}
@@ -0,0 +1,123 @@
+use crate::{
+    utils_output::{self, DecodedOutputs},
+    verify_execution, Outputs,
+};
+use serde::{Deserialize, Serialize};
+
+// PROOF BUNDLE
+// ================================================================================================
+
+/// Everything a verifier needs to check one proven transition of the book: the input file the
+/// program was run with and the outputs returned by `prove_program`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ProofBundle {
+    pub inputs: String,
+    pub outputs: Outputs,
+}
+
+impl ProofBundle {
+    pub fn new(inputs: &str, outputs: Outputs) -> Self {
+        Self { inputs: inputs.to_string(), outputs }
+    }
+
+    /// Decodes tree roots, fills and change log from the stack outputs.
+    pub fn decode(&self) -> Result<DecodedOutputs, String> {
+        utils_output::decode_outputs(&self.outputs.stack_output)
+    }
+
+    /// Verifies the proof against the inputs and stack outputs of the bundle.
+    pub fn verify(&self) -> Result<(), String> {
+        let proof = self.outputs.proof.as_ref().ok_or("proof bundle holds no proof")?;
+        let overflow_addrs = self.outputs.overflow_addrs.clone().unwrap_or_default();
+
+        verify_execution(&self.inputs, proof, &self.outputs.stack_output, &overflow_addrs)
+    }
+}
+
+// PROOF CHAIN
+// ================================================================================================
+
+/// Transition proofs of a session, taking the book from `initial_root` to `final_root`.
+///
+/// This is not the recursive aggregation into one succinct proof that was asked for, which is
+/// out of scope: the Miden VM version this crate is built against has no recursive STARK
+/// verifier. Whoever checks the chain still verifies every bundle. Building it checks each
+/// bundle is valid and starts from the tree root the one before it left.
+#[derive(Deserialize, Serialize)]
+pub struct ProofChain {
+    pub initial_root: [u64; 4],
+    pub final_root: [u64; 4],
+    pub bundles: Vec<ProofBundle>,
+}
+
+/// Chains the transition proofs of a session, in execution order.
+pub fn chain_proofs(bundles: Vec<ProofBundle>) -> Result<ProofChain, String> {
+    let roots = bundles
+        .iter()
+        .map(|bundle| bundle.decode().map(|outputs| (outputs.old_root, outputs.new_root)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (initial_root, _) = *roots.first().ok_or("no proof bundle to chain")?;
+    for (i, pair) in roots.windows(2).enumerate() {
+        if pair[0].1 != pair[1].0 {
+            return Err(format!(
+                "proof bundle {} does not start from the tree root left by bundle {i}",
+                i + 1
+            ));
+        }
+    }
+    let (_, final_root) = roots[roots.len() - 1];
+
+    for (i, bundle) in bundles.iter().enumerate() {
+        bundle.verify().map_err(|err| format!("proof bundle {i} is invalid - {err}"))?;
+    }
+
+    Ok(ProofChain { initial_root, final_root, bundles })
+}
+
+#[test]
+fn test_chain_proofs() {
+    use crate::{Allocation, FeeSchedule, IncomingOrder, InstrumentParams, OrderBook, Sequencer};
+    use miden_vm::utils::collections::BTreeMap;
+
+    let params = InstrumentParams { tick_size: 1, lot_size: 1, ..Default::default() };
+    let books = BTreeMap::from([(1, OrderBook::default())]);
+    let mut sequencer =
+        Sequencer::new(books, params, FeeSchedule::default(), Allocation::default());
+    let initial_root = sequencer.roots()[&1];
+    let ask = IncomingOrder {
+        instrument_id: 1,
+        quantity: 10,
+        price: 50,
+        time: 1,
+        order_id: 1,
+        ..Default::default()
+    };
+    let bid = IncomingOrder { buy_sell_flag: 1, quantity: 4, time: 2, order_id: 2, ..ask };
+    let bundles = [ask, bid]
+        .iter()
+        .map(|order| {
+            let transition = sequencer.sequence(order, &BTreeMap::new()).unwrap();
+            ProofBundle::new(
+                &transition.inputs,
+                crate::prove_execution(&transition.inputs).unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let chain = chain_proofs(bundles.clone()).unwrap();
+    assert_eq!(chain.initial_root, initial_root);
+    assert_eq!(chain.final_root, sequencer.roots()[&1]);
+
+    let swapped = chain_proofs(vec![bundles[1].clone(), bundles[0].clone()]);
+    assert_eq!(
+        swapped.err(),
+        Some("proof bundle 1 does not start from the tree root left by bundle 0".to_string())
+    );
+
+    // Outputs the proof does not attest to, the roots left as they are
+    let mut forged = bundles.clone();
+    forged[1].outputs.stack_output[8] += 1;
+    let forged = chain_proofs(forged).err().unwrap_or_default();
+    assert!(forged.starts_with("proof bundle 1 is invalid"), "{forged}");
+}
@@ -0,0 +1,184 @@
+use crate::utils_orderbook::{OrderBook, BUY_SIDE_ID, SELL_SIDE_ID};
+use serde::{Deserialize, Serialize};
+
+/// Operand stack flag selecting query mode, see `QueryBook`.
+pub const QUERY_MODE: u64 = 4;
+
+/// Quotes the best bid, the best ask and the spread.
+pub const TOP_OF_BOOK_QUERY: u64 = 0;
+
+/// Quotes the top levels of each side with their cumulative quantity.
+pub const DEPTH_QUERY: u64 = 1;
+
+/// Quotes the quantity of the bids at or above a price and of the asks at or below it.
+pub const QUANTITY_UP_TO_QUERY: u64 = 2;
+
+/// A read-only query of the book of an instrument, proven against its tree root in query mode.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BookQuery {
+    pub instrument_id: u64,
+    /// One of `TOP_OF_BOOK_QUERY`, `DEPTH_QUERY` or `QUANTITY_UP_TO_QUERY`.
+    pub query: u64,
+    /// Price a `QUANTITY_UP_TO_QUERY` sums the quantity up to.
+    pub price: u64,
+    /// Number of levels of each side a `DEPTH_QUERY` quotes.
+    pub depth_levels: u64,
+}
+
+impl BookQuery {
+    /// Returns the 12 inputs on top of the operand stack, listed bottom first, in place of those
+    /// of an incoming order. The instrument ID goes at `INSTRUMENT_ID_POSITION` as for an order.
+    pub fn to_operand_stack(&self) -> Vec<u64> {
+        let mut operand_stack = vec![0; 8];
+        operand_stack.extend([self.depth_levels, self.price, self.query, QUERY_MODE]);
+        operand_stack
+    }
+
+    /// Mirrors `QueryBook`: returns the quote words answering the query of a book.
+    pub fn quotes(&self, book: &OrderBook) -> Result<Vec<[u64; 4]>, String> {
+        let bids = book.depth(BUY_SIDE_ID);
+        let asks = book.depth(SELL_SIDE_ID);
+        match self.query {
+            TOP_OF_BOOK_QUERY => {
+                let best_bid = bids.keys().next_back().copied().unwrap_or(0);
+                let best_ask = asks.keys().next().copied().unwrap_or(0);
+                let spread = if best_bid != 0 && best_ask != 0 { best_ask - best_bid } else { 0 };
+                Ok(vec![[best_bid, best_ask, spread, 0]])
+            }
+            DEPTH_QUERY => {
+                let mut quotes = Vec::new();
+                for (is_buy, levels) in [
+                    (1, Box::new(bids.iter().rev()) as Box<dyn Iterator<Item = _>>),
+                    (0, Box::new(asks.iter())),
+                ] {
+                    let mut cumulative_quantity = 0;
+                    for (price, quantity) in levels.take(self.depth_levels as usize) {
+                        cumulative_quantity += quantity;
+                        quotes.push([is_buy, *price, *quantity, cumulative_quantity]);
+                    }
+                }
+                Ok(quotes)
+            }
+            QUANTITY_UP_TO_QUERY => {
+                let bid_quantity = bids.range(self.price..).map(|(_, quantity)| quantity).sum();
+                let ask_quantity = asks.range(..=self.price).map(|(_, quantity)| quantity).sum();
+                Ok(vec![[self.price, bid_quantity, ask_quantity, 0]])
+            }
+            query => Err(format!("query {query} is unknown")),
+        }
+    }
+}
+
+/// Answer of a `TOP_OF_BOOK_QUERY`. A side without levels quotes 0, and the spread is 0 then.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TopOfBook {
+    pub best_bid: u64,
+    pub best_ask: u64,
+    pub spread: u64,
+}
+
+impl TopOfBook {
+    pub fn from_quotes(quotes: &[[u64; 4]]) -> Result<Self, String> {
+        match quotes {
+            [[best_bid, best_ask, spread, 0]] => {
+                Ok(Self { best_bid: *best_bid, best_ask: *best_ask, spread: *spread })
+            }
+            _ => Err("quotes do not answer a top of book query".to_string()),
+        }
+    }
+}
+
+/// A level quoted by a `DEPTH_QUERY`, bids best first, then asks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DepthLevel {
+    pub is_buy: bool,
+    pub price: u64,
+    pub quantity: u64,
+    /// Quantity of the level and of the better levels of its side.
+    pub cumulative_quantity: u64,
+}
+
+impl DepthLevel {
+    pub fn from_quotes(quotes: &[[u64; 4]]) -> Vec<Self> {
+        quotes
+            .iter()
+            .map(|quote| Self {
+                is_buy: quote[0] == 1,
+                price: quote[1],
+                quantity: quote[2],
+                cumulative_quantity: quote[3],
+            })
+            .collect()
+    }
+}
+
+/// Answer of a `QUANTITY_UP_TO_QUERY`: the quantity a sell order at price could hit and a buy
+/// order at price could lift.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct QuantityUpTo {
+    pub price: u64,
+    pub bid_quantity: u64,
+    pub ask_quantity: u64,
+}
+
+impl QuantityUpTo {
+    pub fn from_quotes(quotes: &[[u64; 4]]) -> Result<Self, String> {
+        match quotes {
+            [[price, bid_quantity, ask_quantity, 0]] => {
+                Ok(Self { price: *price, bid_quantity: *bid_quantity, ask_quantity: *ask_quantity })
+            }
+            _ => Err("quotes do not answer a quantity up to price query".to_string()),
+        }
+    }
+}
+
+#[test]
+fn test_quotes() {
+    use crate::utils_output::ChangeLogEntry;
+
+    let init = |node_pointer: u64, order_id: u64| ChangeLogEntry {
+        node_pointer,
+        color: 2,
+        order_id,
+        ..Default::default()
+    };
+    let level =
+        |node_pointer: u64, parent_pointer: u64, quantity: u64, price: u64| ChangeLogEntry {
+            node_pointer,
+            parent_pointer,
+            quantity,
+            price,
+            time: 1,
+            order_id: node_pointer,
+            ..Default::default()
+        };
+    let mut book = OrderBook::default();
+    book.nodes.insert(1, init(1, 0));
+    book.nodes.insert(4, init(4, 2));
+    book.nodes.insert(7, level(7, 1, 30, 48));
+    book.nodes.insert(10, level(10, 1, 10, 47));
+    book.nodes.insert(13, level(13, 4, 20, 50));
+    book.nodes.insert(16, level(16, 4, 5, 52));
+
+    let query = |query: u64| BookQuery { query, price: 50, depth_levels: 1, ..Default::default() };
+    let quotes = query(TOP_OF_BOOK_QUERY).quotes(&book).unwrap();
+    assert_eq!(
+        TopOfBook::from_quotes(&quotes),
+        Ok(TopOfBook { best_bid: 48, best_ask: 50, spread: 2 })
+    );
+
+    let quotes = query(DEPTH_QUERY).quotes(&book).unwrap();
+    let levels = DepthLevel::from_quotes(&quotes);
+    assert_eq!(levels.len(), 2);
+    assert_eq!((levels[0].is_buy, levels[0].price, levels[0].quantity), (true, 48, 30));
+    assert_eq!((levels[1].is_buy, levels[1].price, levels[1].quantity), (false, 50, 20));
+    let quotes = BookQuery { depth_levels: 5, ..query(DEPTH_QUERY) }.quotes(&book).unwrap();
+    assert_eq!(quotes[1], [1, 47, 10, 40]);
+
+    let quotes = query(QUANTITY_UP_TO_QUERY).quotes(&book).unwrap();
+    assert_eq!(
+        QuantityUpTo::from_quotes(&quotes),
+        Ok(QuantityUpTo { price: 50, bid_quantity: 0, ask_quantity: 20 })
+    );
+    assert!(query(3).quotes(&book).is_err());
+}
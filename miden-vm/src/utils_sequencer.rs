@@ -0,0 +1,268 @@
+use crate::{
+    execute_program,
+    utils_allocation::Allocation,
+    utils_balances::{Balances, BALANCES_ROOT_POSITION},
+    utils_fees::{FeeSchedule, FEE_COMMITMENT_POSITION},
+    utils_input::{InputFile, MerkleData},
+    utils_instrument::{stack_element, InstrumentParams},
+    utils_nullifiers::{NullifierStore, NULLIFIER_ROOT_POSITION},
+    utils_order::IncomingOrder,
+    utils_orderbook::{apply_outputs, books_from_advice_map, books_to_advice_map, OrderBook},
+    utils_output::{decode_outputs, DecodedOutputs},
+    utils_query::BookQuery,
+    utils_signatures::SIGNATURES_POSITION,
+};
+use miden_vm::utils::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// A transition of the books by one incoming order: the input file to prove and the outputs the
+/// proof must produce.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Transition {
+    pub inputs: String,
+    pub outputs: DecodedOutputs,
+}
+
+/// Orders incoming orders into transitions of the book of every instrument.
+///
+/// Every order is executed against the current books, which move on right away, so its proof can
+/// be generated later, concurrently with the proofs of the orders after it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sequencer {
+    pub books: BTreeMap<u64, OrderBook>,
+    pub params: InstrumentParams,
+    pub fees: FeeSchedule,
+    pub allocation: Allocation,
+    /// Account balances the orders settle against, `None` for no balances.
+    pub balances: Option<Balances>,
+    /// Consumed orders, `None` for no replay protection.
+    pub nullifiers: Option<NullifierStore>,
+    /// Whether every incoming order must be signed by the owner of its account.
+    pub signatures: bool,
+}
+
+impl Sequencer {
+    pub fn new(
+        books: BTreeMap<u64, OrderBook>,
+        params: InstrumentParams,
+        fees: FeeSchedule,
+        allocation: Allocation,
+    ) -> Self {
+        Self { books, params, fees, allocation, ..Default::default() }
+    }
+
+    /// Reads the books, the instrument parameters, the fees, the allocation, the balances, the
+    /// nullifiers and the signatures flag of an input file, checking the fee schedule, balances
+    /// and nullifiers against their commitments on the operand stack.
+    pub fn from_inputs(inputs: &str) -> Result<Self, String> {
+        let input_file: InputFile = serde_json::from_str(inputs).map_err(|e| e.to_string())?;
+        let operand_stack = input_file
+            .operand_stack
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(|v| v.parse::<u64>().map_err(|e| format!("failed to parse operand `{v}` - {e}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let stack_word = |position: usize| -> [u64; 4] {
+            core::array::from_fn(|i| stack_element(&operand_stack, position + i))
+        };
+        let stack_root =
+            |position: usize| Some(stack_word(position)).filter(|root| *root != [0; 4]);
+        let allocation = Allocation::from_operand_stack(&operand_stack);
+        allocation.validate()?;
+
+        let advice_map = input_file.advice_map.unwrap_or_default();
+        let fees = FeeSchedule::from_advice_map(&advice_map)?;
+        if fees.commitment() != stack_word(FEE_COMMITMENT_POSITION) {
+            return Err("fee schedule does not match its commitment".to_string());
+        }
+
+        // Balances and nullifiers are the sparse Merkle trees of the merkle store with their roots
+        let mut trees = vec![Vec::new()];
+        for merkle_data in input_file.merkle_store.iter().flatten() {
+            if let MerkleData::SparseMerkleTree(leaves) = merkle_data {
+                trees.push(leaves.clone());
+            }
+        }
+        let balances = match stack_root(BALANCES_ROOT_POSITION) {
+            Some(root) => {
+                let trees = trees
+                    .iter()
+                    .map(|leaves| Balances::from_merkle_data(leaves))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let balances = trees.into_iter().find(|balances| balances.root() == Ok(root));
+                Some(balances.ok_or("merkle store holds no tree with the balances root")?)
+            }
+            None => None,
+        };
+        let nullifiers = match stack_root(NULLIFIER_ROOT_POSITION) {
+            Some(root) => {
+                let mut trees = trees.iter().map(|leaves| NullifierStore::from_merkle_data(leaves));
+                let nullifiers = trees.find(|nullifiers| nullifiers.root() == Ok(root));
+                Some(nullifiers.ok_or("merkle store holds no tree with the nullifier root")?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            books: books_from_advice_map(&advice_map)?,
+            params: InstrumentParams::from_operand_stack(&operand_stack),
+            fees,
+            allocation,
+            balances,
+            nullifiers,
+            signatures: stack_element(&operand_stack, SIGNATURES_POSITION) == 1,
+        })
+    }
+
+    /// Returns the tree root of every book, by instrument ID.
+    pub fn roots(&self) -> BTreeMap<u64, [u64; 4]> {
+        self.books.iter().map(|(instrument_id, book)| (*instrument_id, book.root())).collect()
+    }
+
+    /// Returns the input file of an incoming order against the current books. The signature
+    /// advice of the order, see `signature_advice_map`, is only needed with signatures on.
+    pub fn inputs(
+        &self,
+        order: &IncomingOrder,
+        signature_advice: &BTreeMap<String, Vec<u64>>,
+    ) -> Result<String, String> {
+        self.input_file(order.instrument_id, order.to_operand_stack(), signature_advice)
+    }
+
+    /// Returns the input file of a read-only query of the current book of an instrument, see
+    /// `BookQuery`.
+    pub fn query_inputs(&self, query: &BookQuery) -> Result<String, String> {
+        self.input_file(query.instrument_id, query.to_operand_stack(), &BTreeMap::new())
+    }
+
+    /// Returns the input file with the 12 inputs on top of the operand stack, listed bottom first.
+    fn input_file(
+        &self,
+        instrument_id: u64,
+        inputs: Vec<u64>,
+        signature_advice: &BTreeMap<String, Vec<u64>>,
+    ) -> Result<String, String> {
+        let mut merkle_store = Vec::new();
+        let nullifier_root = match &self.nullifiers {
+            Some(nullifiers) => {
+                merkle_store
+                    .push(serde_json::json!({ "sparse_merkle_tree": nullifiers.to_merkle_data() }));
+                nullifiers.root()?
+            }
+            None => [0; 4],
+        };
+        let balances_root = match &self.balances {
+            Some(balances) => {
+                merkle_store
+                    .push(serde_json::json!({ "sparse_merkle_tree": balances.to_merkle_data() }));
+                balances.root()?
+            }
+            None => [0; 4],
+        };
+
+        let mut operand_stack = self.allocation.to_operand_stack();
+        operand_stack.extend(nullifier_root.iter().rev());
+        operand_stack.extend([self.signatures as u64, instrument_id]);
+        operand_stack.extend(balances_root.iter().rev());
+        operand_stack.extend(self.params.to_operand_stack());
+        operand_stack.extend(self.fees.commitment().iter().rev());
+        operand_stack.extend(inputs);
+
+        let mut advice_map = books_to_advice_map(&self.books);
+        advice_map.extend(self.fees.to_advice_map());
+        advice_map.extend(signature_advice.clone());
+
+        Ok(serde_json::json!({
+            "operand_stack": operand_stack.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "advice_map": advice_map,
+            "merkle_store": merkle_store,
+        })
+        .to_string())
+    }
+
+    /// Executes an incoming order and moves the books, the balances and the nullifiers to the
+    /// state it leads to, once the new roots match them.
+    pub fn sequence(
+        &mut self,
+        order: &IncomingOrder,
+        signature_advice: &BTreeMap<String, Vec<u64>>,
+    ) -> Result<Transition, String> {
+        let inputs = self.inputs(order, signature_advice)?;
+        let outputs = decode_outputs(&execute_program(&inputs)?)?;
+
+        let mut books = self.books.clone();
+        apply_outputs(&mut books, &outputs)?;
+        let first_book = *books.keys().next().ok_or("no book to sequence orders on")?;
+        let new_roots = outputs.books.iter().map(|book| (book.instrument_id, book.new_root));
+        for (instrument_id, new_root) in
+            [(first_book, outputs.new_root)].into_iter().chain(new_roots)
+        {
+            if books[&instrument_id].root() != new_root {
+                return Err(format!(
+                    "book of instrument {instrument_id} does not match its new root"
+                ));
+            }
+        }
+
+        let mut balances = self.balances.clone();
+        if let Some(balances) = &mut balances {
+            balances.apply_transition(order, &self.books, &books, &outputs.fills)?;
+            if balances.root()? != outputs.new_balances_root {
+                return Err("balances do not match the new balances root".to_string());
+            }
+        }
+        let mut nullifiers = self.nullifiers.clone();
+        if let Some(nullifiers) = &mut nullifiers {
+            nullifiers.nullify(order.order_id)?;
+            if nullifiers.root()? != outputs.new_nullifier_root {
+                return Err("nullifiers do not match the new nullifier root".to_string());
+            }
+        }
+
+        self.books = books;
+        self.balances = balances;
+        self.nullifiers = nullifiers;
+        Ok(Transition { inputs, outputs })
+    }
+}
+
+#[test]
+fn test_inputs() {
+    use crate::{utils_balances::Balance, utils_fees::FeeTier};
+
+    let order = IncomingOrder {
+        quantity: 20,
+        price: 48,
+        order_id: 3,
+        instrument_id: 2,
+        ..Default::default()
+    };
+    let params = InstrumentParams { tick_size: 2, lot_size: 5, ..Default::default() };
+    let allocation = Allocation { algorithm: 2, priority_share_bps: 2500 };
+    let fees =
+        FeeSchedule { tiers: vec![FeeTier { min_notional: 100, maker_bps: 2, taker_bps: 4 }] };
+    let mut sequencer = Sequencer::new(BTreeMap::new(), params.clone(), fees, allocation.clone());
+    sequencer.books.insert(2, OrderBook::default());
+    let mut balances = Balances::default();
+    balances.accounts.insert(1, Balance { base: 10, quote: 500 });
+    sequencer.balances = Some(balances);
+    sequencer.nullifiers = Some(NullifierStore::default());
+    sequencer.signatures = true;
+
+    let inputs = sequencer.inputs(&order, &BTreeMap::new()).unwrap();
+    // Loading the books moves their creation counter on, see `LoadTree`, not their roots
+    let loaded = Sequencer::from_inputs(&inputs).unwrap();
+    assert_eq!(loaded.roots(), sequencer.roots());
+    assert_eq!(Sequencer { books: sequencer.books.clone(), ..loaded }, sequencer);
+    let inputs: serde_json::Value = serde_json::from_str(&inputs).unwrap();
+    let operand_stack = inputs["operand_stack"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_str().unwrap().parse::<u64>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(IncomingOrder::from_operand_stack(&operand_stack), order);
+    assert_eq!(InstrumentParams::from_operand_stack(&operand_stack), params);
+    assert_eq!(Allocation::from_operand_stack(&operand_stack), allocation);
+}
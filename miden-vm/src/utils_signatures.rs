@@ -0,0 +1,244 @@
+use crate::{
+    utils_order::IncomingOrder,
+    utils_orderbook::{advice_map_key, SIGNER_NAMESPACE},
+};
+use core::borrow::Borrow;
+use miden_vm::{
+    crypto::{
+        dsa::rpo_falcon512::{KeyPair, Polynomial, Signature},
+        MerklePath, MerkleStore, Rpo256, RpoDigest,
+    },
+    math::{Felt, StarkField},
+    utils::{collections::BTreeMap, Deserializable, Serializable},
+    AdviceInputs, AdviceProvider, AdviceSource, ExecutionError, MemAdviceProvider, ProcessState,
+    SignatureKind, Word,
+};
+
+/// Operand stack position, counted from the top, of the signatures flag, below the instrument
+/// ID. '1' requires every incoming order to be signed, '0' requires none.
+pub const SIGNATURES_POSITION: usize = 27;
+
+/// Returns the account ID of the owner of a public key: its top element on the stack, which ties
+/// an order to its signer in `VerifyOrderSignature`. Order notes are checked against the key
+/// their sender authenticates with the same way, see `OrderNote::validate`.
+pub fn key_account_id(public_key: Word) -> u64 {
+    public_key[3].as_int()
+}
+
+/// Falcon key pair of an account, kept by its client to sign the orders it submits off-circuit.
+pub struct OrderSigner {
+    key_pair: KeyPair,
+}
+
+impl OrderSigner {
+    /// Generates a new key pair.
+    pub fn new() -> Result<Self, String> {
+        let key_pair =
+            KeyPair::new().map_err(|e| format!("failed to generate key pair - {e:?}"))?;
+        Ok(Self { key_pair })
+    }
+
+    /// Reads a key pair serialized by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let key_pair = KeyPair::read_from_bytes(bytes)
+            .map_err(|e| format!("failed to read key pair - {e}"))?;
+        Ok(Self { key_pair })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.key_pair.to_bytes()
+    }
+
+    pub fn public_key(&self) -> Word {
+        self.key_pair.public_key().into()
+    }
+
+    /// Returns the account ID the orders of this signer must carry.
+    pub fn account_id(&self) -> u64 {
+        key_account_id(self.public_key())
+    }
+
+    /// Signs the message of an order of this account.
+    pub fn sign_order(&self, order: &IncomingOrder) -> Result<Signature, String> {
+        if order.account_id != self.account_id() {
+            return Err(format!(
+                "order {} is of account {}, not of signer {}",
+                order.order_id,
+                order.account_id,
+                self.account_id()
+            ));
+        }
+        self.key_pair.sign(order.message()).map_err(|e| format!("failed to sign order - {e:?}"))
+    }
+}
+
+/// Returns the advice map entries `VerifyOrderSignature` needs for a signed order: the public key
+/// under the order ID, and the signature under the hash of the public key and the order message.
+///
+/// The client signs the order off-circuit with `OrderSigner::sign_order`, so the inputs hold no
+/// key pair and whoever builds them cannot sign orders. The signature is checked first.
+pub fn signature_advice_map(
+    public_key: Word,
+    order: &IncomingOrder,
+    signature: &Signature,
+) -> Result<BTreeMap<String, Vec<u64>>, String> {
+    verify_order(public_key, order, signature)?;
+
+    let mut advice_map = BTreeMap::new();
+    advice_map.insert(
+        advice_map_key(SIGNER_NAMESPACE, order.order_id),
+        public_key.iter().map(|element| element.as_int()).collect(),
+    );
+    advice_map.insert(
+        hex::encode(signature_key(public_key, order.message())),
+        signature_advice(signature).iter().map(|element| element.as_int()).collect(),
+    );
+    Ok(advice_map)
+}
+
+/// Advice map key of the signature of a message by a public key.
+fn signature_key(public_key: Word, message: Word) -> [u8; 32] {
+    Rpo256::merge(&[public_key.into(), message.into()]).as_bytes()
+}
+
+/// Returns what `adv.push_sig.rpo_falcon512` puts on the advice stack for `rpo_falcon512::verify`,
+/// as the advice provider computes it when signing: the nonce, the public key polynomial, the
+/// signature polynomial and their product, reversed.
+fn signature_advice(signature: &Signature) -> Vec<Felt> {
+    let h = signature.pub_key_poly();
+    let s2 = signature.sig_poly();
+    let pi = Polynomial::mul_modulo_p(&h, &s2);
+
+    let mut advice = signature.nonce().to_vec();
+    advice.extend(h.to_elements());
+    advice.extend(s2.to_elements());
+    advice.extend(pi.iter().map(|element| Felt::new(*element)));
+    advice.reverse();
+    advice
+}
+
+// ADVICE PROVIDER
+// ================================================================================================
+
+/// Advice provider of the program, answering `adv.push_sig` with the signatures of the advice map,
+/// see `signature_advice_map`, instead of signing with a key pair kept in the advice map.
+pub struct SignatureAdviceProvider(MemAdviceProvider);
+
+impl Default for SignatureAdviceProvider {
+    fn default() -> Self {
+        Self(MemAdviceProvider::default())
+    }
+}
+
+impl From<AdviceInputs> for SignatureAdviceProvider {
+    fn from(inputs: AdviceInputs) -> Self {
+        Self(MemAdviceProvider::from(inputs))
+    }
+}
+
+impl AdviceProvider for SignatureAdviceProvider {
+    fn pop_stack<S: ProcessState>(&mut self, process: &S) -> Result<Felt, ExecutionError> {
+        self.0.pop_stack(process)
+    }
+
+    fn pop_stack_word<S: ProcessState>(&mut self, process: &S) -> Result<Word, ExecutionError> {
+        self.0.pop_stack_word(process)
+    }
+
+    fn pop_stack_dword<S: ProcessState>(
+        &mut self,
+        process: &S,
+    ) -> Result<[Word; 2], ExecutionError> {
+        self.0.pop_stack_dword(process)
+    }
+
+    fn push_stack(&mut self, source: AdviceSource) -> Result<(), ExecutionError> {
+        self.0.push_stack(source)
+    }
+
+    fn insert_into_map(&mut self, key: Word, values: Vec<Felt>) -> Result<(), ExecutionError> {
+        self.0.insert_into_map(key, values)
+    }
+
+    /// Returns the signature the client made off-circuit, never signing itself.
+    fn get_signature(
+        &self,
+        _kind: SignatureKind,
+        pub_key: Word,
+        msg: Word,
+    ) -> Result<Vec<Felt>, ExecutionError> {
+        self.0
+            .get_mapped_values(&signature_key(pub_key, msg))
+            .map(<[Felt]>::to_vec)
+            .ok_or(ExecutionError::AdviceMapKeyNotFound(pub_key))
+    }
+
+    fn get_mapped_values(&self, key: &[u8; 32]) -> Option<&[Felt]> {
+        self.0.get_mapped_values(key)
+    }
+
+    fn get_tree_node(
+        &self,
+        root: Word,
+        depth: &Felt,
+        index: &Felt,
+    ) -> Result<Word, ExecutionError> {
+        self.0.get_tree_node(root, depth, index)
+    }
+
+    fn get_merkle_path(
+        &self,
+        root: Word,
+        depth: &Felt,
+        index: &Felt,
+    ) -> Result<MerklePath, ExecutionError> {
+        self.0.get_merkle_path(root, depth, index)
+    }
+
+    fn get_leaf_depth(
+        &self,
+        root: Word,
+        tree_depth: &Felt,
+        index: &Felt,
+    ) -> Result<u8, ExecutionError> {
+        self.0.get_leaf_depth(root, tree_depth, index)
+    }
+
+    fn update_merkle_node(
+        &mut self,
+        root: Word,
+        depth: &Felt,
+        index: &Felt,
+        value: Word,
+    ) -> Result<(MerklePath, Word), ExecutionError> {
+        self.0.update_merkle_node(root, depth, index, value)
+    }
+
+    fn merge_roots(&mut self, lhs: Word, rhs: Word) -> Result<Word, ExecutionError> {
+        self.0.merge_roots(lhs, rhs)
+    }
+
+    fn get_store_subset<I, R>(&self, roots: I) -> MerkleStore
+    where
+        I: Iterator<Item = R>,
+        R: Borrow<RpoDigest>,
+    {
+        self.0.get_store_subset(roots)
+    }
+}
+
+/// Checks the signature of an order against the public key of its account, as
+/// `VerifyOrderSignature` does.
+pub fn verify_order(
+    public_key: Word,
+    order: &IncomingOrder,
+    signature: &Signature,
+) -> Result<(), String> {
+    if key_account_id(public_key) != order.account_id {
+        return Err(format!("public key is not of account {}", order.account_id));
+    }
+    if !signature.verify(order.message(), public_key) {
+        return Err(format!("signature of order {} is not valid", order.order_id));
+    }
+    Ok(())
+}
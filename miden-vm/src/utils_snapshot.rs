@@ -0,0 +1,255 @@
+use crate::{
+    utils_orderbook::OrderBook,
+    utils_output::{ChangeLogEntry, QueueEntry},
+};
+use serde::{Deserialize, Serialize};
+
+/// Leading bytes of a binary snapshot.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"CLOB";
+
+/// Version of the binary snapshot layout, bumped on every change to it.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Number of values of a node in a binary snapshot.
+const NODE_LEN: usize = 9;
+
+/// Number of values of a queued order in a binary snapshot.
+const SLOT_LEN: usize = 11;
+
+// SNAPSHOT
+// ================================================================================================
+
+/// Self-contained copy of a book, committed to by the tree root `HashTree` computes over it.
+///
+/// Nil nodes and queued orders are left out, they do not change the root and `to_advice_map`
+/// writes them back up to the counters.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// Creation number stored at `mem_load.0` once the tree is loaded.
+    pub creation_counter: u64,
+    pub nodes: Vec<ChangeLogEntry>,
+    pub slot_counter: u64,
+    pub slots: Vec<QueueEntry>,
+    /// Tree root of the book, in stack order.
+    pub root: [u64; 4],
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("failed to serialize snapshot - {e}"))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("failed to deserialize snapshot - {e}"))
+    }
+
+    /// Returns the binary snapshot: the magic and the version, then the counters, the root, the
+    /// nodes and the queued orders as little-endian `u64` values, each list after its length.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut values = vec![self.creation_counter, self.slot_counter];
+        values.extend(self.root);
+        values.push(self.nodes.len() as u64);
+        for node in &self.nodes {
+            values.extend([
+                node.node_pointer,
+                node.color,
+                node.parent_pointer,
+                node.left_child_pointer,
+                node.right_child_pointer,
+                node.quantity,
+                node.price,
+                node.time,
+                node.order_id,
+            ]);
+        }
+        values.push(self.slots.len() as u64);
+        for entry in &self.slots {
+            values.extend([
+                entry.slot,
+                entry.account_id,
+                entry.stp_policy,
+                entry.next_slot,
+                entry.expiry,
+                entry.reserve,
+                entry.peak,
+                entry.quantity,
+                entry.price,
+                entry.time,
+                entry.order_id,
+            ]);
+        }
+
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend(SNAPSHOT_VERSION.to_le_bytes());
+        bytes.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        bytes
+    }
+
+    /// Reads a binary snapshot written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let header_len = SNAPSHOT_MAGIC.len() + 4;
+        if bytes.len() < header_len || &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err("not a book snapshot".to_string());
+        }
+        let version =
+            u32::from_le_bytes(bytes[SNAPSHOT_MAGIC.len()..header_len].try_into().unwrap());
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("unsupported snapshot version {version}"));
+        }
+        let chunks = bytes[header_len..].chunks_exact(8);
+        if !chunks.remainder().is_empty() {
+            return Err("snapshot is truncated".to_string());
+        }
+
+        let values =
+            chunks.map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect::<Vec<_>>();
+        let mut values = values.iter().copied();
+        let mut next = |count: usize| -> Result<Vec<u64>, String> {
+            let taken = values.by_ref().take(count).collect::<Vec<_>>();
+            if taken.len() != count {
+                return Err("snapshot is truncated".to_string());
+            }
+            Ok(taken)
+        };
+
+        let header = next(6)?;
+        let node_count = next(1)?[0] as usize;
+        let mut nodes = Vec::new();
+        for _ in 0..node_count {
+            let value = next(NODE_LEN)?;
+            nodes.push(ChangeLogEntry {
+                node_pointer: value[0],
+                color: value[1],
+                parent_pointer: value[2],
+                left_child_pointer: value[3],
+                right_child_pointer: value[4],
+                quantity: value[5],
+                price: value[6],
+                time: value[7],
+                order_id: value[8],
+            });
+        }
+        let slot_count = next(1)?[0] as usize;
+        let mut slots = Vec::new();
+        for _ in 0..slot_count {
+            let value = next(SLOT_LEN)?;
+            slots.push(QueueEntry {
+                slot: value[0],
+                account_id: value[1],
+                stp_policy: value[2],
+                next_slot: value[3],
+                expiry: value[4],
+                reserve: value[5],
+                peak: value[6],
+                quantity: value[7],
+                price: value[8],
+                time: value[9],
+                order_id: value[10],
+            });
+        }
+        if values.next().is_some() {
+            return Err("snapshot has trailing bytes".to_string());
+        }
+
+        Ok(Self {
+            creation_counter: header[0],
+            nodes,
+            slot_counter: header[1],
+            slots,
+            root: [header[2], header[3], header[4], header[5]],
+        })
+    }
+}
+
+impl OrderBook {
+    /// Returns the snapshot of the book, see `Snapshot`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            creation_counter: self.creation_counter,
+            nodes: self.nodes.values().filter(|node| !node.is_nil()).cloned().collect(),
+            slot_counter: self.slot_counter,
+            slots: self.slots.values().filter(|entry| !entry.is_nil()).cloned().collect(),
+            root: self.root(),
+        }
+    }
+
+    /// Rebuilds a book from its snapshot, failing if a node or queued order lies past the
+    /// counters or if the book does not hash to `expected_root`.
+    ///
+    /// The root a snapshot carries is only checked for consistency, since whoever forged the book
+    /// could forge it too. `expected_root` must come from a trusted source, such as the new tree
+    /// root of the last verified proof of the book, in stack order.
+    pub fn restore(snapshot: &Snapshot, expected_root: [u64; 4]) -> Result<Self, String> {
+        let mut book = Self {
+            creation_counter: snapshot.creation_counter,
+            slot_counter: snapshot.slot_counter,
+            ..Default::default()
+        };
+        for node in &snapshot.nodes {
+            if node.node_pointer % 3 != 1 || (node.node_pointer - 1) / 3 > book.creation_counter {
+                return Err(format!("node pointer {} is out of the tree", node.node_pointer));
+            }
+            if book.nodes.insert(node.node_pointer, node.clone()).is_some() {
+                return Err(format!("node pointer {} is repeated", node.node_pointer));
+            }
+        }
+        for entry in &snapshot.slots {
+            if entry.slot == 0 || entry.slot > book.slot_counter {
+                return Err(format!("slot {} is out of the queues", entry.slot));
+            }
+            if book.slots.insert(entry.slot, entry.clone()).is_some() {
+                return Err(format!("slot {} is repeated", entry.slot));
+            }
+        }
+
+        let root = book.root();
+        if root != expected_root {
+            return Err(format!(
+                "snapshot book hashes to {root:?}, expected root is {expected_root:?}"
+            ));
+        }
+        if snapshot.root != root {
+            return Err(format!(
+                "snapshot root {:?} does not match the root {root:?} of its book",
+                snapshot.root
+            ));
+        }
+        Ok(book)
+    }
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let mut book = OrderBook { creation_counter: 1, slot_counter: 1, ..Default::default() };
+    book.apply_change_log(
+        &[ChangeLogEntry {
+            node_pointer: 4,
+            quantity: 5,
+            price: 100,
+            order_id: 7,
+            ..Default::default()
+        }],
+        &[QueueEntry { slot: 1, quantity: 5, price: 100, order_id: 7, ..Default::default() }],
+    )
+    .unwrap();
+    let mut unchanged = book.clone();
+    let nil_pointer = ChangeLogEntry { quantity: 6, ..Default::default() };
+    assert!(unchanged.apply_change_log(&[nil_pointer], &[]).is_err());
+    assert_eq!(unchanged, book);
+
+    let snapshot = book.snapshot();
+    assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(), snapshot);
+    assert_eq!(Snapshot::from_json(&snapshot.to_json().unwrap()).unwrap(), snapshot);
+    assert_eq!(OrderBook::restore(&snapshot, book.root()).unwrap(), book);
+
+    // A tampered book fails even with a matching root of its own
+    let mut tampered = snapshot.clone();
+    tampered.nodes[0].quantity = 6;
+    assert!(OrderBook::restore(&tampered, book.root()).is_err());
+    let mut forged = book.clone();
+    forged.nodes.get_mut(&4).unwrap().quantity = 6;
+    tampered.root = forged.root();
+    assert!(OrderBook::restore(&tampered, book.root()).is_err());
+    assert!(OrderBook::restore(&snapshot, tampered.root).is_err());
+    assert!(Snapshot::from_bytes(&snapshot.to_bytes()[..20]).is_err());
+}
@@ -1,40 +1,69 @@
-import init, {Outputs, prove_program, verify_program} from "miden-vm";
+import init, {Outputs, decode_outputs, prove_program, verify_bundle, verify_program} from "miden-vm";
 import initData from "./data.json";
-import RbTree from "red-black-tree-js";
 
-let treeInitiated = false;
-let rbTree:any;
-let inputData:typeof initData;
-export let uiTree : UiNode[] = new Array();
+// Operand stack positions, counted from the top, see `Sequencer::input_file`. The stack of an
+// input file is listed bottom first.
+const ORDER_INPUTS_LEN = 12;
+const INSTRUMENT_ID_POSITION = 26;
 
-interface OrderWord {
-    quantity : number,
-    price : number,
-    timestamp : number,
-    id: number
-}
+// Modes selected by the top element in place of the buy/sell flag
+const QUERY_MODE = 4;
+const TOP_OF_BOOK_QUERY = 0;
+
+// Time in force of a market order, its price being a slippage cap in bps, 0 for none
+const MARKET_ORDER = 4;
+
+// Local prover service, proxied by the dev server, see `vite.config.js`
+const SERVICE_URL = "/service";
+
+// WebSocket feed of the prover service, proxied by the dev server
+const FEED_PATH = "/feed";
+
+// Advice map namespaces, see `utils_orderbook.rs`
+const BOOK_NAMESPACE = 0;
+const QUEUE_NAMESPACE = 3;
+const INSTRUMENT_NAMESPACE = 5;
 
-interface CoordinateWord {
+let inputData: typeof initData;
+let books: Book[] = [];
+export let uiTree : UiNode[] = new Array();
+
+// A price level, as printed by `PrintChangeLog`
+interface LevelNode {
+    nodePointer: number,
     color: number,
-    parentId: number,
-    leftChildId: number,
-    rightChildId: number
+    parentPointer: number,
+    leftChildPointer: number,
+    rightChildPointer: number,
+    quantity: number,
+    price: number,
+    headSlot: number,
+    tailSlot: number
 }
 
-interface MemoryWord {
-    pad1: number,
-    pad2: number,
-    pad3: number,
-    memoryLocation: number
+// An order queued at a price level, as printed by `PrintSlotChangeLog`
+interface QueuedOrder {
+    slot: number,
+    accountId: number,
+    stpPolicy: number,
+    nextSlot: number,
+    expiry: number,
+    reserve: number,
+    peak: number,
+    quantity: number,
+    price: number,
+    time: number,
+    orderId: number
 }
 
-interface Node {
-    memory : MemoryWord,
-    coordinate : CoordinateWord,
-    order : OrderWord
+interface Book {
+    instrumentId: number,
+    creationCounter: number,
+    nodes: Map<number, LevelNode>,
+    slotCounter: number,
+    slots: Map<number, QueuedOrder>
 }
 
-
 interface UiNode {
     id :number,
     price:number,
@@ -49,236 +78,277 @@ export enum Side {
     Buy
 }
 
-function BNtoNumber(n: bigint) {
-    return Number(n);
+export type JobStatus = "queued" | "proving" | "done" | "failed";
+
+export interface Job {
+    id: number,
+    status: JobStatus,
+    error: string | null,
+    bundle: { outputs: { proof: number[] | null } } | null
 }
 
-function initializeTrees() {
-    if (treeInitiated)
-        return;
+export interface DepthLevel {
+    price: number,
+    amount: number
+}
 
-    rbTree = new RbTree();
-    inputData = initData;
-
-    const nodeArray = Object.values(inputData.advice_map);
-    for (let i = 1; i< nodeArray.length; i++) {
-        const nodeEl = nodeArray[i];
-        const node : Node = {
-            memory : { pad1 : 0, pad2 : 0, pad3 : 0, memoryLocation : nodeEl[3]},
-            coordinate: {
-                color:nodeEl[4], 
-                parentId: nodeEl[5], 
-                leftChildId: nodeEl[6],
-                rightChildId: nodeEl[7]
-            },
-            order: {
-                quantity: nodeEl[8],
-                price: nodeEl[9],
-                timestamp: nodeEl[10],
-                id: nodeEl[11]
-            }
-        };
-        rbTree.insert(node.memory.memoryLocation, node);
+// Market data of one proven transition, see `FeedUpdate`. Level quantities are the new quantity
+// resting at the price, 0 once the level is gone.
+export interface FeedUpdate {
+    proof_id: number,
+    roots: Record<string, number[]>,
+    levels: { instrument_id: number, is_buy: boolean, price: number, quantity: number }[],
+    trades: { instrument_id: number, quantity: number, price: number, maker_id: number, taker_id: number }[]
+}
 
-        uiTree.push({
-            id: node.memory.memoryLocation,
-            price: node.order.price,
-            children: [node.coordinate.leftChildId, node.coordinate.rightChildId],
-            parent: node.coordinate.parentId,
-            quantity: node.order.quantity,
-            color: node.coordinate.color ? "red" : "black"
-        });
-    }
-    treeInitiated = true;
+export interface TopOfBook {
+    bestBid: number,
+    bestAsk: number,
+    spread: number
 }
 
-export function getOrders() {
-    initializeTrees();
-    return uiTree;
+// Returns the hex advice map key of word [0, bookNumber, namespace, index], every element in
+// little endian as `book_advice_map_key` writes them.
+function adviceMapKey(bookNumber: number, namespace: number, index: number) {
+    return [0, bookNumber, namespace, index].map((element) => {
+        let value = BigInt(element);
+        let hex = "";
+        for (let i = 0; i < 8; i++) {
+            hex += Number(value & 0xffn).toString(16).padStart(2, "0");
+            value >>= 8n;
+        }
+        return hex;
+    }).join("");
 }
 
-function prove_program_mock(input:string) {
-    //modification, deletion and addition
-    //modification
-    const outputStack = new BigUint64Array(45);
-    outputStack[0] = 268n;    //memoryLocation
-    outputStack[1] = 1n;      //color = red (changed)
-    outputStack[2] = 259n;    //(parentId)
-    outputStack[3] = 0n;      //leftChildId
-    outputStack[4] = 0n;      //rightChildId
-    outputStack[5] = 2n;      //quantity (changed)
-    outputStack[6] = 49n;     //price
-    outputStack[7] = 1688473018n;  //timestamp
-    outputStack[8] = 4929032n;     //orderId
-
-    //deleted node
-    outputStack[9] = 274n;    //memoryLocation
-    outputStack[10] = 0n;      //color = black
-    outputStack[11] = 0n;    //(parentId)
-    outputStack[12] = 0n;      //leftChildId
-    outputStack[13] = 0n;      //rightChildId
-    outputStack[14] = 0n;      //quantity
-    outputStack[15] = 48n;     //price
-    outputStack[16] = 1688473234n;  //timestamp
-    outputStack[17] = 3248575n;     //orderId
-
-    //parent of deleted node
-    outputStack[18] = 271n;    //memoryLocation
-    outputStack[19] = 0n;      //color = black
-    outputStack[20] = 277n;    //(parentId)
-    outputStack[21] = 280n;      //leftChildId
-    outputStack[22] = 0n;      //rightChildId
-    outputStack[23] = 40n;      //quantity
-    outputStack[24] = 49n;     //price
-    outputStack[25] = 1688473214n;  //timestamp
-    outputStack[26] = 2697766n;     //orderId   
-
-    //addition
-    outputStack[27] = 300n;    //memoryLocation
-    outputStack[28] = 1n;      //color = red 
-    outputStack[29] = 286n;    //(parentId)
-    outputStack[30] = 0n;      //leftChildId
-    outputStack[31] = 0n;      //rightChildId
-    outputStack[32] = 25n;      //quantity (changed)
-    outputStack[33] = 49n;     //price
-    outputStack[34] = 1688473018n;  //timestamp
-    outputStack[35] = 4929032n;     //orderId
-
-    //parent of added node
-    outputStack[36] = 286n;    //memoryLocation
-    outputStack[37] = 0n;      //color = black 
-    outputStack[38] = 283n;    //(parentId)
-    outputStack[39] = 300n;      //leftChildId
-    outputStack[40] = 0n;      //rightChildId
-    outputStack[41] = 5n;      //quantity (changed)
-    outputStack[42] = 49n;     //price
-    outputStack[43] = 1688474789n;  //timestamp
-    outputStack[44] = 4306835n;     //orderId
-
-    const proof = new Uint8Array(3);
-    proof[0] = 200;
-    proof[1] = 300;
-    proof[2] = 400;
-
-    const output : Outputs = { 
-        stack_output : outputStack,
-        overflow_addrs : outputStack,
-        trace_len : 64,
-        proof : proof,
-        free : () => {}
+function adviceValue(key: string) : number[] {
+    const value = (inputData.advice_map as Record<string, number[]>)[key];
+    if (value === undefined) {
+        throw new Error(`advice map has no entry ${key}`);
     }
-    return output;
+    return value;
 }
 
-export async function createOrder(quantity: number, price: number | null, side : Side) {
-    initializeTrees();
-    await init();
- 
-    //construct the operand stack as a new order
-    inputData.operand_stack[4] = side.toString();
-    inputData.operand_stack[3] = quantity.toString();
-    let sPrice : string;
-    if (price === null) {
-        if (side == Side.Sell) {
-            sPrice = "0";
+// Reads the books listed in the advice map, in book number order, see `books_from_advice_map`
+function readBooks() : Book[] {
+    const numberOfBooks = adviceValue(adviceMapKey(0, INSTRUMENT_NAMESPACE, 0))[3];
+    const books = new Array();
+    for (let bookNumber = 0; bookNumber < numberOfBooks; bookNumber++) {
+        const instrumentId = adviceValue(adviceMapKey(0, INSTRUMENT_NAMESPACE, bookNumber + 1))[3];
+        const creationCounter = adviceValue(adviceMapKey(bookNumber, BOOK_NAMESPACE, 0))[3];
+        const nodes = new Map();
+        for (let index = 1; index <= creationCounter; index++) {
+            const value = adviceValue(adviceMapKey(bookNumber, BOOK_NAMESPACE, index));
+            nodes.set(value[3], {
+                nodePointer: value[3],
+                color: value[4],
+                parentPointer: value[5],
+                leftChildPointer: value[6],
+                rightChildPointer: value[7],
+                quantity: value[8],
+                price: value[9],
+                headSlot: value[10],
+                tailSlot: value[11]
+            });
         }
-        else {
-            sPrice = "18446744073709551615";
+        const slotCounter = adviceValue(adviceMapKey(bookNumber, QUEUE_NAMESPACE, 0))[3];
+        const slots = new Map();
+        for (let index = 1; index <= slotCounter; index++) {
+            const value = adviceValue(adviceMapKey(bookNumber, QUEUE_NAMESPACE, index));
+            slots.set(value[3], {
+                slot: value[3],
+                stpPolicy: value[6],
+                accountId: value[7],
+                peak: value[8],
+                reserve: value[9],
+                expiry: value[10],
+                nextSlot: value[11],
+                quantity: value[12],
+                price: value[13],
+                time: value[14],
+                orderId: value[15]
+            });
         }
+        books.push({instrumentId, creationCounter, nodes, slotCounter, slots});
     }
-    else {
-        sPrice = price.toString();
-    }
-    inputData.operand_stack[2] = sPrice;
-    inputData.operand_stack[1] = Math.floor((new Date().getTime()) /1000).toString();
-    inputData.operand_stack[0] = "9999999"; //TBI
-
-    console.log(JSON.stringify(inputData));
-    const { stack_output, trace_len, overflow_addrs, proof }: Outputs =
-        prove_program(JSON.stringify(inputData));
-    console.log("hello");
-    const sProof = Array.from(proof!)
-        .map((b) => b.toString(16).padStart(2, "0"))
-        .join("");
+    return books;
+}
 
-    const outputArray = Array.from(stack_output);
-
-    //update rbTree
-    for(let i = 0; i < outputArray.length; i+=9) {
-        if (i+8 >= outputArray.length)
-            break;
-
-        const newNode: Node = {
-            memory : { pad1 : 0, pad2 : 0, pad3 : 0, memoryLocation : BNtoNumber(outputArray[i])},
-            coordinate: {
-                color:BNtoNumber(outputArray[i+1]), 
-                parentId: BNtoNumber(outputArray[i+2]), 
-                leftChildId: BNtoNumber(outputArray[i+3]),
-                rightChildId: BNtoNumber(outputArray[i+4])
-            },
-            order: {
-                quantity: BNtoNumber(outputArray[i+5]),
-                price: BNtoNumber(outputArray[i+6]),
-                timestamp: BNtoNumber(outputArray[i+7]),
-                id: BNtoNumber(outputArray[i+8])
-            }
-        };
+// Writes the books back to the advice map as `LoadBooks`, `LoadTree` and `LoadSlots` read it,
+// see `books_to_advice_map`: every node up to the creation number and every slot up to the slot
+// counter, empty ones included.
+function writeBooks() {
+    const adviceMap : Record<string, number[]> = {};
+    adviceMap[adviceMapKey(0, INSTRUMENT_NAMESPACE, 0)] = [0, 0, 0, books.length];
+    books.forEach((book, bookNumber) => {
+        adviceMap[adviceMapKey(0, INSTRUMENT_NAMESPACE, bookNumber + 1)] = [0, 0, 0, book.instrumentId];
+
+        const numberOfOrders = book.creationCounter + 1;
+        adviceMap[adviceMapKey(bookNumber, BOOK_NAMESPACE, 0)] = [0, 0, 0, numberOfOrders];
+        for (let creationNumber = 0; creationNumber < numberOfOrders; creationNumber++) {
+            const nodePointer = creationNumber * 3 + 1;
+            const node = book.nodes.get(nodePointer);
+            adviceMap[adviceMapKey(bookNumber, BOOK_NAMESPACE, creationNumber + 1)] = node === undefined
+                ? [0, 0, 0, nodePointer, 0, 0, 0, 0, 0, 0, 0, 0]
+                : [0, 0, 0, nodePointer, node.color, node.parentPointer, node.leftChildPointer,
+                    node.rightChildPointer, node.quantity, node.price, node.headSlot, node.tailSlot];
+        }
 
-        let modifiedNode : Node = rbTree.find(newNode.memory.memoryLocation);
-        if (modifiedNode === null) { //insert
-            rbTree.insert(newNode.memory.memoryLocation, newNode);
+        adviceMap[adviceMapKey(bookNumber, QUEUE_NAMESPACE, 0)] = [0, 0, 0, book.slotCounter];
+        for (let slot = 1; slot <= book.slotCounter; slot++) {
+            const order = book.slots.get(slot);
+            adviceMap[adviceMapKey(bookNumber, QUEUE_NAMESPACE, slot)] = order === undefined
+                ? [0, 0, 0, slot, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+                : [0, 0, 0, slot, 0, 0, order.stpPolicy, order.accountId, order.peak, order.reserve,
+                    order.expiry, order.nextSlot, order.quantity, order.price, order.time, order.orderId];
         }
-        else if(newNode.coordinate.parentId == 0 && newNode.coordinate.leftChildId == 0 && newNode.coordinate.rightChildId == 0) { //delete
-            rbTree.remove(newNode.memory.memoryLocation);
+    });
+    inputData.advice_map = adviceMap as typeof initData.advice_map;
+}
+
+// Applies the change logs of a proven transition, see `apply_outputs`. Those on top belong to the
+// first book.
+function applyOutputs(outputs: any) {
+    const applyChangeLog = (book: Book, changeLog: any[], queueLog: any[]) => {
+        for (const entry of changeLog) {
+            const nodePointer = Number(entry.node_pointer);
+            if (nodePointer == 0) {
+                throw new Error("change log holds node pointer 0");
+            }
+            book.creationCounter = Math.max(book.creationCounter, Math.floor((nodePointer - 1) / 3));
+            book.nodes.set(nodePointer, {
+                nodePointer,
+                color: Number(entry.color),
+                parentPointer: Number(entry.parent_pointer),
+                leftChildPointer: Number(entry.left_child_pointer),
+                rightChildPointer: Number(entry.right_child_pointer),
+                quantity: Number(entry.quantity),
+                price: Number(entry.price),
+                headSlot: Number(entry.time),
+                tailSlot: Number(entry.order_id)
+            });
+        }
+        for (const entry of queueLog) {
+            const slot = Number(entry.slot);
+            book.slotCounter = Math.max(book.slotCounter, slot);
+            book.slots.set(slot, {
+                slot,
+                accountId: Number(entry.account_id),
+                stpPolicy: Number(entry.stp_policy),
+                nextSlot: Number(entry.next_slot),
+                expiry: Number(entry.expiry),
+                reserve: Number(entry.reserve),
+                peak: Number(entry.peak),
+                quantity: Number(entry.quantity),
+                price: Number(entry.price),
+                time: Number(entry.time),
+                orderId: Number(entry.order_id)
+            });
         }
-        else { //modified
-            rbTree.update(newNode.memory.memoryLocation, newNode);
+    };
+
+    applyChangeLog(books[0], outputs.change_log, outputs.queue_log);
+    for (const bookOutputs of outputs.books) {
+        const book = books.find((book) => book.instrumentId == Number(bookOutputs.instrument_id));
+        if (book === undefined) {
+            throw new Error(`instrument ${bookOutputs.instrument_id} has no book`);
         }
+        applyChangeLog(book, bookOutputs.change_log, bookOutputs.queue_log);
     }
+}
 
-    //update uiTree and adviceMap based on rbTree
-    const iterator = rbTree.createIterator();
+function updateUiTree(instrumentId: number) {
+    const book = books.find((book) => book.instrumentId == instrumentId);
     uiTree = new Array();
-
-    let newAdviceMap : any= {};
-    let i = 1;
-    const keyPrefix = "00000000000000000000000000000000000000000000000000";
-    const keySuffix = "00000000000000";
-
-    while (iterator.hasNext()) {
-        const element = iterator.next().value as Node;
-        const ordinalNumber = i.toString(16);
-        const key = keyPrefix.substring(0, keyPrefix.length - ordinalNumber.length) + ordinalNumber + keySuffix;
-        const value = [0, 0, 0, element.memory.memoryLocation, 
-            element.coordinate.color, element.coordinate.parentId, element.coordinate.leftChildId, element.coordinate.rightChildId,
-            element.order.quantity, element.order.price, element.order.timestamp, element.order.id
-        ];
-        newAdviceMap[key] = value;
-
+    for (const node of book?.nodes.values() ?? []) {
+        // Init nodes and empty slots are no price levels
+        if (node.color == 2 || (node.quantity == 0 && node.price == 0)) {
+            continue;
+        }
         uiTree.push({
-            id: element.memory.memoryLocation,
-            price: element.order.price,
-            children: [element.coordinate.leftChildId, element.coordinate.rightChildId],
-            parent: element.coordinate.parentId,
-            quantity: element.order.quantity,
-            color: element.coordinate.color ? "red" : "black"
+            id: node.nodePointer,
+            price: node.price,
+            children: [node.leftChildPointer, node.rightChildPointer],
+            parent: node.parentPointer,
+            quantity: node.quantity,
+            color: node.color ? "red" : "black"
         });
+    }
+}
+
+function initializeBooks() {
+    if (inputData !== undefined)
+        return;
+
+    inputData = structuredClone(initData);
+    books = readBooks();
+    updateUiTree(books[0].instrumentId);
+}
+
+// Puts the 12 inputs of an order or a query, listed bottom first, on top of the operand stack,
+// and its instrument ID at `INSTRUMENT_ID_POSITION`. The instrument parameters, the fee
+// commitment, the balances and nullifier roots and the allocation below them are kept.
+function setOperandStack(inputs: bigint[], instrumentId: number) {
+    const operandStack = inputData.operand_stack;
+    const top = operandStack.length - ORDER_INPUTS_LEN;
+    inputs.forEach((input, i) => {
+        operandStack[top + i] = input.toString();
+    });
+    operandStack[operandStack.length - 1 - INSTRUMENT_ID_POSITION] = instrumentId.toString();
+}
 
-        i++;
+export function getOrders(instrumentId?: number) {
+    initializeBooks();
+    if (instrumentId !== undefined) {
+        updateUiTree(instrumentId);
     }
+    return uiTree;
+}
 
-    newAdviceMap[keyPrefix+keySuffix] = [0, 0, 0, i-1];
-    inputData.advice_map = newAdviceMap;
+export async function createOrder(quantity: number, price: number | null, side : Side, instrumentId?: number) {
+    initializeBooks();
+    await init();
+    instrumentId = instrumentId ?? books[0].instrumentId;
+
+    // A market order without a price sweeps the opposite side without a slippage cap
+    const time = Math.floor((new Date().getTime()) / 1000);
+    const orderId = new Date().getTime();
+    const timeInForce = price === null ? MARKET_ORDER : 0;
+    // stp policy, account ID, trigger price, display size, post only, expiry, time in force,
+    // order ID, time, price, quantity and buy/sell flag
+    setOperandStack([0n, 0n, 0n, 0n, 0n, 0n, BigInt(timeInForce), BigInt(orderId), BigInt(time),
+        BigInt(price ?? 0), BigInt(quantity), BigInt(side)], instrumentId);
+
+    const inputs = JSON.stringify(inputData);
+    const { stack_output, overflow_addrs, proof }: Outputs = prove_program(inputs);
+    const sProof = Array.from(proof!)
+        .map((b) => b.toString(16).padStart(2, "0"))
+        .join("");
 
-    console.log(uiTree);
-    console.log(inputData);
-    console.log(sProof);
+    applyOutputs(decode_outputs(stack_output));
+    writeBooks();
+    updateUiTree(instrumentId);
 
     const inputOperandStack = JSON.stringify({
-        "operand_stack" : inputData.operand_stack
+        "operand_stack" : JSON.parse(inputs).operand_stack
     });
 
-    return {sProof, inputOperandStack, stack_output, overflow_addrs, proof};
+    return {sProof, inputs, inputOperandStack, stack_output, overflow_addrs, proof};
+}
+
+// Proves the best bid, the best ask and the spread of the book of an instrument in query mode,
+// which leaves the book unchanged.
+export async function queryTopOfBook(instrumentId?: number) : Promise<TopOfBook> {
+    initializeBooks();
+    await init();
+    instrumentId = instrumentId ?? books[0].instrumentId;
+
+    // Eight unused inputs, the number of depth levels, the price, the query and the mode
+    setOperandStack([0n, 0n, 0n, 0n, 0n, 0n, 0n, 0n, 0n, 0n, BigInt(TOP_OF_BOOK_QUERY),
+        BigInt(QUERY_MODE)], instrumentId);
+    const { stack_output }: Outputs = prove_program(JSON.stringify(inputData));
+    const [bestBid, bestAsk, spread] = decode_outputs(stack_output).quotes[0].map(Number);
+    return {bestBid, bestAsk, spread};
 }
 
 export function verifyProof(input : string, output : BigUint64Array, overflowAddress : BigUint64Array, proof : Uint8Array) {
@@ -289,4 +359,120 @@ export function verifyProof(input : string, output : BigUint64Array, overflowAdd
     catch (error) {
         return false;
     }
-}
\ No newline at end of file
+}
+
+async function serviceRequest(path: string, init?: RequestInit) {
+    const response = await fetch(SERVICE_URL + path, init);
+    const body = await response.json();
+    if (!response.ok) {
+        throw new Error(body.error ?? `${path} answered ${response.status}`);
+    }
+    return body;
+}
+
+// Replaces the books with those of the prover service
+export async function loadBook(instrumentId?: number) {
+    initializeBooks();
+    const { advice_map } = await serviceRequest("/book");
+    inputData.advice_map = advice_map;
+    books = readBooks();
+    updateUiTree(instrumentId ?? books[0].instrumentId);
+    return uiTree;
+}
+
+// Sends an order to the prover service, which sequences it and proves it on one of its workers,
+// and returns the ID of its job
+export async function submitOrder(quantity: number, price: number | null, side : Side, instrumentId?: number) : Promise<number> {
+    initializeBooks();
+    const order = {
+        quantity,
+        price: price ?? 0,
+        time: Math.floor((new Date().getTime()) / 1000),
+        order_id: new Date().getTime(),
+        buy_sell_flag: side,
+        time_in_force: price === null ? MARKET_ORDER : 0,
+        expiry: 0,
+        post_only: 0,
+        display_size: 0,
+        trigger_price: 0,
+        account_id: 0,
+        stp_policy: 0,
+        instrument_id: instrumentId ?? books[0].instrumentId
+    };
+    const { id } = await serviceRequest("/orders", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(order)
+    });
+    return id;
+}
+
+// Polls the job until it is proven or failed
+export async function waitForJob(id: number, onStatus?: (status: JobStatus) => void) : Promise<Job> {
+    for (;;) {
+        const job : Job = await serviceRequest(`/jobs/${id}`);
+        onStatus?.(job.status);
+        if (job.status == "done" || job.status == "failed") {
+            return job;
+        }
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+    }
+}
+
+// Fetches the proof bundle of a proven job and verifies it. The bundle is passed on as served,
+// as its stack outputs do not all fit in JavaScript numbers.
+export async function verifyJob(id: number) {
+    await init();
+    const response = await fetch(`${SERVICE_URL}/jobs/${id}/bundle`);
+    if (!response.ok) {
+        return false;
+    }
+    try {
+        verify_bundle(await response.text());
+        return true;
+    }
+    catch (error) {
+        return false;
+    }
+}
+
+// Returns the instrument ID of every book, in book number order
+export function getInstrumentIds() {
+    initializeBooks();
+    return books.map((book) => book.instrumentId);
+}
+
+// Returns the quantity resting at every price level of each side of a book, see
+// `OrderBook::depth`. A level belongs to the side of the init node it hangs off, init node 0 being
+// the BUY side.
+export function getDepth(instrumentId?: number) {
+    initializeBooks();
+    const book = books.find((book) => book.instrumentId == (instrumentId ?? books[0].instrumentId));
+    const bids = new Map<number, number>();
+    const asks = new Map<number, number>();
+    for (const node of book?.nodes.values() ?? []) {
+        if (node.color == 2 || (node.quantity == 0 && node.price == 0)) {
+            continue;
+        }
+        let parent : LevelNode | undefined = node;
+        for (let i = 0; i < book!.nodes.size && parent !== undefined && parent.color != 2; i++) {
+            parent = book!.nodes.get(parent.parentPointer);
+        }
+        if (parent === undefined || parent.color != 2) {
+            continue;
+        }
+        const sideId = parent.nodePointer == 1 ? 0 : parent.tailSlot;
+        const side = sideId == 0 ? bids : sideId == 2 ? asks : null;
+        side?.set(node.price, (side.get(node.price) ?? 0) + node.quantity);
+    }
+    return {bids, asks};
+}
+
+// Subscribes to the updates of every proven transition, in job order. Returns a function ending
+// the subscription.
+export function subscribeFeed(onUpdate: (update: FeedUpdate) => void) {
+    const protocol = location.protocol == "https:" ? "wss:" : "ws:";
+    const socket = new WebSocket(`${protocol}//${location.host}${FEED_PATH}`);
+    socket.onmessage = (event) => onUpdate(JSON.parse(event.data));
+    return () => socket.close();
+}
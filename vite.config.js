@@ -10,7 +10,17 @@ export default defineConfig({
         sveltekit(),
     ],
      server: {
-
+        // Local prover service and its feed, see `miden-vm/src/bin/server`
+        proxy: {
+            '/service': {
+                target: 'http://127.0.0.1:8080',
+                rewrite: (path) => path.replace(/^\/service/, '')
+            },
+            '/feed': {
+                target: 'ws://127.0.0.1:8081',
+                ws: true
+            }
+        },
         fs: {
             allow: [searchForWorkspaceRoot(process.cwd()),
                 'miden-vm/**/*.js',